@@ -3,6 +3,7 @@ use std::path::{Path, PathBuf};
 
 use crate::utils::hash_object;
 use crate::utils::index::{Index, IndexEntry};
+use crate::utils::workdir;
 
 pub fn add(path: &str) {
     let path_buf = PathBuf::from(path);
@@ -62,7 +63,7 @@ fn add_file(path: &Path, index: &mut Index) {
     let entry = IndexEntry {
         path: rel_str,
         sha,
-        mode: "100644".to_string(),
+        mode: workdir::file_mode(path.to_str().unwrap()),
     };
 
     index.add(entry);
@@ -1,8 +1,10 @@
 // a branch refers to the part of a tree that is smaller than the trunk, in this case, a branch of the commit history
 
+use crate::utils::hash_object::resolve_head;
+use crate::utils::refs;
+use crate::utils::repo;
 use std::fs;
 use std::io;
-use std::path::{Path, PathBuf};
 
 /// Create a new branch or list all branches
 pub fn branch(branch_name: Option<&str>) -> Result<(), io::Error> {
@@ -19,60 +21,55 @@ pub fn branch(branch_name: Option<&str>) -> Result<(), io::Error> {
 
 /// Create a new branch pointing to the current commit
 fn create_branch(branch_name: &str) -> Result<(), io::Error> {
-    // Validate branch name (simple validation)
-    if branch_name.contains('/') || branch_name.contains('\\') || branch_name.trim().is_empty() {
-        eprintln!("Error: Invalid branch name '{}'", branch_name);
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            "Invalid branch name",
-        ));
-    }
+    let commit_sha = resolve_head().ok_or_else(|| {
+        eprintln!("Error: Not a valid object name: 'HEAD'");
+        io::Error::new(io::ErrorKind::NotFound, "HEAD has no commits")
+    })?;
+
+    create_branch_at(branch_name, &commit_sha, "HEAD", false).map_err(|e| {
+        eprintln!("Error: {}", e);
+        io::Error::new(io::ErrorKind::InvalidInput, e)
+    })?;
+    println!("Created branch '{}'", branch_name);
 
-    // Read the current commit SHA from HEAD
-    let head_contents = fs::read_to_string(".hit/HEAD")?;
-    let commit_sha = if head_contents.starts_with("ref: ") {
-        // HEAD points to a ref
-        let ref_path = head_contents[5..].trim();
-        let full_ref_path = Path::new(".hit").join(ref_path);
-        fs::read_to_string(full_ref_path)?.trim().to_string()
+    Ok(())
+}
+
+/// Create (or with `force`, move) a branch ref pointing at `commit_sha`
+///
+/// `start` is only used to describe the change in the reflog.
+pub fn create_branch_at(
+    branch_name: &str,
+    commit_sha: &str,
+    start: &str,
+    force: bool,
+) -> Result<(), String> {
+    refs::validate_branch_name(branch_name)?;
+
+    let message = if refs::branch_exists(branch_name) {
+        if !force {
+            return Err(format!("Branch '{}' already exists", branch_name));
+        }
+        format!("branch: Reset to {}", start)
     } else {
-        // Detached HEAD
-        head_contents.trim().to_string()
+        format!("branch: Created from {}", start)
     };
 
-    // Create branch ref
-    let branch_path = PathBuf::from(format!(".hit/refs/heads/{}", branch_name));
-
-    // Check if branch already exists
-    if branch_path.exists() {
-        eprintln!("Error: Branch '{}' already exists", branch_name);
-        return Err(io::Error::new(
-            io::ErrorKind::AlreadyExists,
-            "Branch already exists",
-        ));
-    }
-
-    // Create the branch (write commit SHA to branch ref file)
-    fs::write(&branch_path, format!("{}\n", commit_sha))?;
-    println!("Created branch '{}'", branch_name);
-
-    Ok(())
+    refs::update_ref(&refs::branch_ref(branch_name), commit_sha, &message)
 }
 
 /// List all branches in the repository
 fn list_branches() -> Result<(), io::Error> {
-    let heads_dir = Path::new(".hit/refs/heads");
+    let heads_dir = repo::path("refs/heads");
     if !heads_dir.exists() {
         return Ok(()); // No branches yet
     }
 
     // Read HEAD to determine current branch
-    let head_contents = fs::read_to_string(".hit/HEAD")?;
-    let current_branch = if head_contents.starts_with("ref: refs/heads/") {
-        Some(head_contents["ref: refs/heads/".len()..].trim())
-    } else {
-        None // Detached HEAD
-    };
+    let head_contents = fs::read_to_string(repo::path("HEAD"))?;
+    let current_branch = head_contents
+        .strip_prefix("ref: refs/heads/")
+        .map(|name| name.trim()); // None means detached HEAD
 
     // List all files in refs/heads directory
     let entries = fs::read_dir(heads_dir)?;
@@ -82,14 +79,14 @@ fn list_branches() -> Result<(), io::Error> {
         let entry = entry?;
         let path = entry.path();
 
-        if path.is_file() {
-            if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                found_branches = true;
-                if Some(name) == current_branch {
-                    println!("* {}", name);
-                } else {
-                    println!("  {}", name);
-                }
+        if path.is_file()
+            && let Some(name) = path.file_name().and_then(|n| n.to_str())
+        {
+            found_branches = true;
+            if Some(name) == current_branch {
+                println!("* {}", name);
+            } else {
+                println!("  {}", name);
             }
         }
     }
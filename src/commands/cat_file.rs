@@ -1,10 +1,8 @@
 // to my dismay, this file is not a photo of a cat.
 
 pub fn cat_file(hash: &str, print: bool) {
-    // look up file at .hit/objects/ab/cdef...
-    let object_dir = format!(".hit/objects/{}", &hash[..2]);
-    let object_file = hash[2..].to_string();
-    let object_path = std::path::PathBuf::from(&object_dir).join(object_file);
+    // look up file at objects/ab/cdef...
+    let object_path = crate::utils::repo::object_path(hash);
     // check if file exists
     if !object_path.exists() {
         eprintln!("Error: Object {} not found", hash);
@@ -1,104 +1,149 @@
 // checkout refers to the area of a grocery store where you pay for your items
 
-use crate::utils::objects::Object;
-use std::fs;
-use std::path::{Path, PathBuf};
-
-/// Main checkout command — accepts a branch or commit SHA
-pub fn checkout(target: &str) {
-    let branch_path = format!(".hit/refs/heads/{}", target);
-
-    if Path::new(&branch_path).exists() {
-        // It's a branch name
-        let sha = fs::read_to_string(&branch_path)
-            .expect("Failed to read branch ref")
-            .trim()
-            .to_string();
-
-        restore_commit(&sha);
-        update_head_to_branch(target, &sha);
-    } else {
-        // Assume it's a commit SHA (detached)
-        restore_commit(target);
-        update_head_to_commit(target);
+use crate::commands::branch::create_branch_at;
+use crate::commands::worktree;
+use crate::utils::fatal::fatal;
+use crate::utils::hash_object::resolve_head;
+use crate::utils::hooks;
+use crate::utils::refs;
+use crate::utils::workdir;
+
+/// Main checkout command — accepts a branch or any revision
+///
+/// `force` throws away local changes that are in the way, like `-f`.
+pub fn checkout(target: &str, force: bool) {
+    if refs::branch_exists(target) {
+        switch(target, force);
+        return;
     }
-}
-
-/// Restore the working directory to the state of a commit
-fn restore_commit(commit_sha: &str) {
-    let commit_obj = Object::read(commit_sha).expect("Failed to read commit object");
-
-    let tree_sha = match commit_obj {
-        Object::Commit(commit) => commit.tree,
-        _ => panic!("{} is not a commit object", commit_sha),
-    };
 
-    clear_working_directory();
-    restore_tree(&tree_sha, PathBuf::from("."));
+    // Assume it's a commit (detached)
+    let old_head = resolve_head();
+    let (sha, tree) = refs::resolve_commit(target).unwrap_or_else(|e| fatal(&e));
+    let message = format!(
+        "checkout: moving from {} to {}",
+        refs::head_description(),
+        target
+    );
+    restore_commit(&tree, force);
+    refs::set_head_detached(&sha, &message);
+    println!("HEAD is now at {}", &sha[..7]);
+    post_checkout(old_head.as_deref());
 }
 
-/// Recursively walk a tree and restore its files and subtrees
-fn restore_tree(tree_sha: &str, base_path: PathBuf) {
-    let tree_obj = Object::read(tree_sha).expect("Failed to read tree object");
-
-    if let Object::Tree(tree) = tree_obj {
-        for entry in tree.entries {
-            let path = base_path.join(&entry.name);
-
-            match entry.mode.as_str() {
-                "100644" | "100755" => {
-                    let blob = Object::read(&entry.sha).expect("Failed to read blob");
-                    if let Object::Blob(data) = blob {
-                        if let Some(parent) = path.parent() {
-                            fs::create_dir_all(parent).expect("Failed to create directory");
-                        }
-                        fs::write(path, data).expect("Failed to write file");
-                    }
-                }
-                "40000" => {
-                    fs::create_dir_all(&path).expect("Failed to create directory");
-                    restore_tree(&entry.sha, path);
-                }
-                _ => eprintln!("Unknown mode: {}", entry.mode),
-            }
+/// Switch to an existing branch, throwing away local changes in the way if
+/// `force` is set
+pub fn switch(branch: &str, force: bool) {
+    if !refs::branch_exists(branch) {
+        fatal(&format!("invalid reference: {}", branch));
+    }
+
+    let old_head = resolve_head();
+    if refs::current_branch().as_deref() == Some(branch) {
+        if force && let Some(head) = &old_head {
+            let (_, tree) = refs::resolve_commit(head).unwrap_or_else(|e| fatal(&e));
+            restore_commit(&tree, true);
         }
-    } else {
-        panic!("{} is not a tree object", tree_sha);
+        println!("Already on '{}'", branch);
+        post_checkout(old_head.as_deref());
+        return;
     }
+    refuse_if_checked_out_elsewhere(branch);
+
+    let (_, tree) = refs::resolve_commit(&refs::branch_ref(branch)).unwrap_or_else(|e| fatal(&e));
+    let message = format!(
+        "checkout: moving from {} to {}",
+        refs::head_description(),
+        branch
+    );
+    restore_commit(&tree, force);
+    refs::set_head_symbolic(&refs::branch_ref(branch), &message);
+    println!("Switched to branch '{}'", branch);
+    post_checkout(old_head.as_deref());
 }
 
-fn clear_working_directory() {
-    // TODO: handle .hitignore
-    let entries: Vec<_> = fs::read_dir(".")
-        .expect("Failed to read directory")
-        .filter_map(Result::ok)
-        .map(|entry| entry.path())
-        .collect();
-
-    for path in entries {
-        if path.file_name().is_some_and(|name| name == ".hit") {
-            continue;
-        }
+/// Create a branch at `start` (HEAD by default) and switch to it
+///
+/// `reset` resets the branch if it already exists, like `-B` / `-C`, and
+/// `force` throws away local changes in the way.
+pub fn switch_new_branch(branch: &str, start: Option<&str>, reset: bool, force: bool) {
+    if start.is_none() && resolve_head().is_none() {
+        // nothing to branch from yet, so the new branch starts out unborn
+        switch_orphan(branch);
+        return;
+    }
 
-        if path.is_dir() {
-            fs::remove_dir_all(&path).expect("Failed to remove directory");
-        } else {
-            fs::remove_file(&path).expect("Failed to remove file");
-        }
+    let old_head = resolve_head();
+    let (sha, tree) = refs::resolve_commit(start.unwrap_or("HEAD")).unwrap_or_else(|e| fatal(&e));
+
+    if let Err(e) = refs::validate_branch_name(branch) {
+        fatal(&e);
+    }
+    if !reset && refs::branch_exists(branch) {
+        fatal(&format!("a branch named '{}' already exists", branch));
+    }
+    refuse_if_checked_out_elsewhere(branch);
+
+    let message = format!(
+        "checkout: moving from {} to {}",
+        refs::head_description(),
+        branch
+    );
+
+    // check out first, so a refused checkout leaves no stray branch behind
+    if start.is_some() || force {
+        restore_commit(&tree, force);
+    }
+
+    if let Err(e) = create_branch_at(branch, &sha, start.unwrap_or("HEAD"), reset) {
+        fatal(&e);
+    }
+    refs::set_head_symbolic(&refs::branch_ref(branch), &message);
+    println!("Switched to a new branch '{}'", branch);
+    post_checkout(old_head.as_deref());
+}
+
+/// Point HEAD at a branch with no commits, keeping the working tree and index
+pub fn switch_orphan(branch: &str) {
+    if let Err(e) = refs::validate_branch_name(branch) {
+        fatal(&e);
     }
+    if refs::branch_exists(branch) {
+        fatal(&format!("a branch named '{}' already exists", branch));
+    }
+
+    let message = format!(
+        "checkout: moving from {} to {}",
+        refs::head_description(),
+        branch
+    );
+    let old_head = resolve_head();
+    refs::set_head_symbolic(&refs::branch_ref(branch), &message);
+    println!("Switched to a new branch '{}'", branch);
+    post_checkout(old_head.as_deref());
 }
 
-/// Writes a detached HEAD (raw SHA)
-fn update_head_to_commit(sha: &str) {
-    fs::write(".hit/HEAD", format!("{}\n", sha)).expect("Failed to write detached HEAD");
+/// Restore the working directory and index to the state of a commit's tree
+fn restore_commit(tree_sha: &str, force: bool) {
+    if let Err(e) = workdir::checkout_tree(tree_sha, force) {
+        fatal(&e);
+    }
 }
 
-/// Writes a symbolic HEAD and updates branch ref
-fn update_head_to_branch(branch: &str, sha: &str) {
-    let head_contents = format!("ref: refs/heads/{}\n", branch);
-    fs::write(".hit/HEAD", head_contents).expect("Failed to write HEAD");
+/// Run the post-checkout hook for a branch checkout; a failing hook makes the
+/// checkout fail too, though nothing gets undone
+fn post_checkout(old_head: Option<&str>) {
+    if hooks::post_checkout(old_head, resolve_head().as_deref(), true).is_err() {
+        std::process::exit(1);
+    }
+}
 
-    let ref_path = format!(".hit/refs/heads/{}", branch);
-    fs::create_dir_all(".hit/refs/heads").expect("Failed to create branch ref dir");
-    fs::write(ref_path, format!("{}\n", sha)).expect("Failed to update branch ref");
+fn refuse_if_checked_out_elsewhere(branch: &str) {
+    if let Some(path) = worktree::checked_out_elsewhere(branch) {
+        fatal(&format!(
+            "'{}' is already checked out at '{}'",
+            branch,
+            path.display()
+        ));
+    }
 }
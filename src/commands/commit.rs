@@ -1,206 +1,580 @@
-use crate::utils::config::get_config_value;
-use crate::utils::hash_object::resolve_head;
+use crate::commands::status;
+use crate::utils::config;
+use crate::utils::date;
+use crate::utils::editor;
+use crate::utils::fatal::fatal;
+use crate::utils::hash_object::{hash_object, resolve_head};
+use crate::utils::hooks;
+use crate::utils::ident::{self, Ident, Role};
 use crate::utils::index::{Index, IndexEntry};
-use crate::utils::objects::{Object, TreeEntry};
-use chrono::Offset;
-use flate2::Compression;
-use flate2::write::ZlibEncoder;
-use sha1::{Digest, Sha1};
-use std::collections::{BTreeMap, HashMap};
+use crate::utils::message;
+use crate::utils::objects::{Commit, Object};
+use crate::utils::pathspec;
+use crate::utils::refs;
+use crate::utils::repo;
+use crate::utils::signature;
+use crate::utils::trailers;
+use crate::utils::workdir;
+use std::collections::{BTreeSet, HashSet};
 use std::fs;
-use std::io::Write;
-use std::path::{Path, PathBuf};
-use std::time::{SystemTime, UNIX_EPOCH};
-
-pub fn commit(message: &str) {
-    let index = Index::load();
+use std::io::Read;
+use std::path::Path;
+
+/// Options for `hit commit`
+#[derive(Default)]
+pub struct CommitOptions {
+    /// Message given with `-m` (several `-m`s become separate paragraphs)
+    pub message: Option<String>,
+    /// File to read the message from with `-F` ("-" for stdin)
+    pub message_file: Option<String>,
+    /// Replace the tip of the current branch instead of adding a commit on top
+    pub amend: bool,
+    /// With `amend`, reuse the old message without opening the editor
+    pub no_edit: bool,
+    /// With `amend`, take authorship instead of keeping the original author
+    pub reset_author: bool,
+    /// Refresh every tracked file from the working tree first (`-a`)
+    pub all: bool,
+    /// Stage `paths` into the index and commit everything staged (`-i`)
+    pub include: bool,
+    /// Commit only `paths`, leaving other staged changes alone (`-o`, the default with paths)
+    pub only: bool,
+    /// Pathspecs given on the command line
+    pub paths: Vec<String>,
+    /// Override the author: "Name <email>", or a pattern matching an earlier author
+    pub author: Option<String>,
+    /// Override the author date
+    pub date: Option<String>,
+    /// Skip the pre-commit and commit-msg hooks
+    pub no_verify: bool,
+    /// `-S` / `--no-gpg-sign`; unset means follow `commit.gpgSign`
+    pub sign: Option<bool>,
+    /// Key given with `-S<key>`, overriding `user.signingKey`
+    pub signing_key: Option<String>,
+    /// Add a "Signed-off-by" trailer for the committer (`-s`)
+    pub signoff: bool,
+    /// "key=value" trailers given with `--trailer`
+    pub trailers: Vec<String>,
+    /// Commit even if the tree is the same as the parent's
+    pub allow_empty: bool,
+    /// Commit even if the message is empty
+    pub allow_empty_message: bool,
+}
 
-    if index.entries.is_empty() {
-        eprintln!("nothing to commit");
+pub fn commit(options: &CommitOptions) {
+    if options.amend {
+        amend(options);
         return;
     }
 
     let head_sha = resolve_head();
-    if let Some(head) = &head_sha {
-        if index_matches_head(&index, head) {
-            println!("Nothing to commit — index matches HEAD.");
-            return;
-        }
+    let head_tree = head_sha
+        .as_ref()
+        .map(|_| refs::resolve_commit("HEAD").unwrap_or_else(|e| fatal(&e)).1);
+    let (mut index, mut updated) = prepare_index(options, head_tree.as_deref());
+    run_pre_commit(options, &mut index, &mut updated);
+
+    // an empty index is fine on top of a commit: it records deleting everything
+    let tree_sha = index.write_tree();
+    let unchanged = match &head_tree {
+        Some(head_tree) => *head_tree == tree_sha,
+        None => index.entries.is_empty(),
+    };
+    if unchanged && !options.allow_empty {
+        nothing_to_commit(head_sha.is_none());
     }
 
-    let tree_sha = build_tree_from_index(&index);
-    let commit_sha = write_commit(&tree_sha, message);
-    update_head(&commit_sha);
+    let (author, committer) = identities(options, None);
+    let message = commit_message(options, None, &index);
+    let subject = message.lines().next().unwrap_or("").to_string();
 
-    println!("[{}] {}", &commit_sha[..7], message);
-}
-
-fn index_matches_head(index: &Index, head_sha: &str) -> bool {
-    let head_tree_map = load_tree_map_from_commit(head_sha);
+    let parents: Vec<String> = head_sha.iter().cloned().collect();
+    let commit_sha = create_commit(options, &tree_sha, &parents, author, committer, &message);
+    let reflog_message = match head_sha {
+        Some(_) => format!("commit: {}", subject),
+        None => format!("commit (initial): {}", subject),
+    };
+    if let Err(e) = refs::update_head(&commit_sha, &reflog_message) {
+        fatal(&e);
+    }
+    updated.save();
 
-    index.entries.iter().all(|entry| {
-        let path = PathBuf::from(&entry.path);
-        head_tree_map.get(&path) == Some(&entry.sha)
-    })
+    println!("[{}] {}", &commit_sha[..7], subject);
+    let _ = hooks::run("post-commit", &[], &[]);
 }
 
-pub fn load_tree_map_from_commit(commit_sha: &str) -> HashMap<PathBuf, String> {
-    let commit_obj = Object::read(commit_sha).expect("Failed to read commit");
-    let tree_sha = match commit_obj {
-        Object::Commit(c) => c.tree,
-        _ => panic!("Not a commit object"),
+/// Rewrite the tip of the current branch from the index, keeping its parents
+/// (and unless told otherwise, its author and message)
+fn amend(options: &CommitOptions) {
+    let head_sha = resolve_head().unwrap_or_else(|| fatal("You have nothing to amend."));
+    let old = match Object::read(&head_sha) {
+        Ok(Object::Commit(commit)) => commit,
+        _ => fatal(&format!("HEAD ({}) is not a commit", head_sha)),
     };
 
-    let mut map = HashMap::new();
-    build_tree_map_recursive(&tree_sha, PathBuf::from(""), &mut map);
-    map
-}
+    let (mut index, mut updated) = prepare_index(options, Some(&old.tree));
+    run_pre_commit(options, &mut index, &mut updated);
+
+    // like git, an amended commit must still change something relative to its
+    // parent; merges are exempt
+    let tree_sha = index.write_tree();
+    if !options.allow_empty && old.parents.len() < 2 {
+        let unchanged = match old.parents.first() {
+            Some(parent) => refs::resolve_commit(parent).is_ok_and(|(_, tree)| tree == tree_sha),
+            None => index.entries.is_empty(),
+        };
+        if unchanged {
+            nothing_to_commit(old.parents.is_empty());
+        }
+    }
 
-fn build_tree_map_recursive(tree_sha: &str, base: PathBuf, map: &mut HashMap<PathBuf, String>) {
-    let obj = Object::read(tree_sha).expect("Failed to read tree object");
-    let tree = match obj {
-        Object::Tree(tree) => tree,
-        _ => panic!("Not a tree object"),
-    };
+    let (author, committer) = identities(options, Some(&old));
+    let message = commit_message(options, Some((&head_sha, &old)), &index);
+    let subject = message.lines().next().unwrap_or("").to_string();
+
+    let commit_sha = create_commit(
+        options,
+        &tree_sha,
+        &old.parents,
+        author,
+        committer,
+        &message,
+    );
+
+    if let Err(e) = refs::update_head(&commit_sha, &format!("commit (amend): {}", subject)) {
+        fatal(&e);
+    }
+    updated.save();
 
-    for entry in tree.entries {
-        let path = base.join(entry.name);
-        match entry.mode.as_str() {
-            "100644" | "100755" => {
-                map.insert(path, entry.sha);
-            }
-            "40000" => {
-                build_tree_map_recursive(&entry.sha, path, map);
+    println!("[{}] {}", &commit_sha[..7], subject);
+    let _ = hooks::run("post-commit", &[], &[]);
+}
+
+/// Run the pre-commit hook unless `--no-verify`, aborting if it fails
+///
+/// The hook may stage more changes; for anything but a partial commit
+/// (`--only`), those end up in the real index too.
+fn run_pre_commit(options: &CommitOptions, index: &mut Index, updated: &mut Index) {
+    if options.no_verify {
+        return;
+    }
+    match run_commit_hook("pre-commit", &[], index, uses_editor(options)) {
+        Ok(after) => {
+            let partial = options.only || (!options.paths.is_empty() && !options.include);
+            if !partial {
+                *updated = after.clone();
             }
-            _ => {}
+            *index = after;
         }
+        Err(_) => std::process::exit(1),
     }
 }
 
-fn build_tree_from_index(index: &Index) -> String {
-    let mut path_map: BTreeMap<PathBuf, Vec<(PathBuf, &IndexEntry)>> = BTreeMap::new();
+/// Run a commit hook with the index being committed available as
+/// HIT_INDEX_FILE, returning that index as the hook left it
+fn run_commit_hook(
+    name: &str,
+    args: &[&str],
+    index: &Index,
+    use_editor: bool,
+) -> Result<Index, String> {
+    if hooks::find(name).is_none() {
+        return Ok(index.clone());
+    }
 
-    for entry in &index.entries {
-        let path = PathBuf::from(&entry.path);
-        let parent = path.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
-        path_map.entry(parent).or_default().push((path, entry));
+    let path = repo::path("next-index");
+    index.save_to(&path);
+    let mut env = vec![(
+        "HIT_INDEX_FILE",
+        std::path::absolute(&path)
+            .unwrap_or_else(|_| path.clone())
+            .display()
+            .to_string(),
+    )];
+    if !use_editor {
+        // nothing is going to be edited, so hooks shouldn't start an editor either
+        env.push(("HIT_EDITOR", ":".to_string()));
     }
 
-    fn build_tree(
-        dir: &Path,
-        path_map: &BTreeMap<PathBuf, Vec<(PathBuf, &IndexEntry)>>,
-    ) -> (Vec<TreeEntry>, String) {
-        let mut entries = Vec::new();
-        let mut raw = Vec::new();
+    let result = hooks::run(name, args, &env);
+    let after = Index::load_from(&path);
+    let _ = fs::remove_file(&path);
+    result.map(|_| after)
+}
 
-        for (path, entry) in path_map.get(dir).cloned().unwrap_or_default() {
-            let name = path.file_name().unwrap().to_string_lossy().to_string();
+/// Whether the message will be written in the editor
+fn uses_editor(options: &CommitOptions) -> bool {
+    options.message.is_none()
+        && options.message_file.is_none()
+        && !(options.amend && options.no_edit)
+}
 
-            let tree_entry = TreeEntry {
-                mode: entry.mode.clone(),
-                name: name.clone(),
-                sha: entry.sha.clone(),
-            };
+/// Work out who the commit is by and who is committing it
+///
+/// When amending (`previous`), the original author and author date are kept
+/// unless `--reset-author`; `--author` and `--date` override either way.
+fn identities(options: &CommitOptions, previous: Option<&Commit>) -> (Ident, Ident) {
+    let mut author = match previous.filter(|_| !options.reset_author) {
+        Some(old) => Ident {
+            // older versions of hit wrote offsets as "+02:00"
+            timezone: date::parse_offset(&old.author.timezone)
+                .map_or_else(|| old.author.timezone.clone(), date::format_timezone),
+            ..old.author.clone()
+        },
+        // name and email are filled in from --author below
+        None if options.author.is_some() => {
+            ident::explicit(Role::Author, "", "").unwrap_or_else(|e| fatal(&e))
+        }
+        None => ident::author().unwrap_or_else(|e| fatal(&e)),
+    };
 
-            entries.push(tree_entry.clone());
+    if let Some(value) = &options.author {
+        let (name, email) = ident::parse_name_email(value)
+            .or_else(|| find_author(value))
+            .unwrap_or_else(|| {
+                fatal(&format!(
+                    "--author '{}' is not 'Name <email>' and matches no existing author",
+                    value
+                ))
+            });
+        author.name = name;
+        author.email = email;
+    }
+    if let Some(date) = &options.date {
+        author.set_date(date).unwrap_or_else(|e| fatal(&e));
+    }
 
-            raw.extend_from_slice(format!("{} {}\0", entry.mode, name).as_bytes());
-            let sha_bin = hex::decode(&entry.sha).unwrap();
-            raw.extend_from_slice(&sha_bin);
-        }
+    let committer = ident::committer().unwrap_or_else(|e| fatal(&e));
+    (author, committer)
+}
 
-        let mut tree_object = Vec::new();
-        tree_object.extend_from_slice(format!("tree {}\0", raw.len()).as_bytes());
-        tree_object.extend_from_slice(&raw);
-
-        let mut hasher = Sha1::new();
-        hasher.update(&tree_object);
-        let sha = hasher.finalize();
-        let sha_hex = format!("{:x}", sha);
-
-        let dir = format!(".hit/objects/{}", &sha_hex[..2]);
-        let file = format!("{}", &sha_hex[2..]);
-        let object_path = Path::new(&dir).join(file);
-
-        if !object_path.exists() {
-            fs::create_dir_all(&dir).unwrap();
-            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
-            encoder.write_all(&tree_object).unwrap();
-            let compressed = encoder.finish().unwrap();
-            fs::write(&object_path, compressed).unwrap();
+/// The most recent author in HEAD's history whose "Name <email>" contains `pattern`
+fn find_author(pattern: &str) -> Option<(String, String)> {
+    let mut pending: Vec<String> = resolve_head().into_iter().collect();
+    let mut seen = HashSet::new();
+    while let Some(sha) = pending.pop() {
+        if !seen.insert(sha.clone()) {
+            continue;
         }
+        let Ok(Object::Commit(commit)) = Object::read(&sha) else {
+            continue;
+        };
+        if commit.author.name_email().contains(pattern) {
+            return Some((commit.author.name, commit.author.email));
+        }
+        pending.extend(commit.parents.into_iter().rev());
+    }
+    None
+}
+
+/// Work out what to commit: returns the index the commit is built from and
+/// the index to save once the commit has been made
+///
+/// With `-a` or `--include` both are the real index, refreshed from the
+/// working tree. With `--only` the commit comes from a temporary index (HEAD
+/// plus just the given paths), so other staged changes stay staged.
+fn prepare_index(options: &CommitOptions, head_tree: Option<&str>) -> (Index, Index) {
+    let mut index = Index::load();
+    let head_index = match head_tree {
+        Some(tree) => Index::from_tree(tree).unwrap_or_else(|e| fatal(&e)),
+        None => Index {
+            entries: Vec::new(),
+        },
+    };
 
-        (entries, sha_hex)
+    if options.all {
+        let tracked: Vec<String> = index.entries.iter().map(|e| e.path.clone()).collect();
+        refresh_paths(&mut index, &tracked);
+        return (index.clone(), index);
+    }
+    if options.paths.is_empty() {
+        // --amend --only with no paths: just reword, ignoring anything staged
+        return if options.only {
+            (head_index, index)
+        } else {
+            (index.clone(), index)
+        };
     }
 
-    let (_, root_tree_sha) = build_tree(Path::new(""), &path_map);
-    root_tree_sha
-}
+    let mut paths = BTreeSet::new();
+    for raw in &options.paths {
+        let spec = pathspec::normalize(raw);
+        let matched: Vec<String> = index
+            .entries
+            .iter()
+            .chain(head_index.entries.iter())
+            .filter(|e| pathspec::matches(&e.path, &spec))
+            .map(|e| e.path.clone())
+            .collect();
+        if matched.is_empty() {
+            eprintln!(
+                "error: pathspec '{}' did not match any file(s) known to hit",
+                raw
+            );
+            std::process::exit(1);
+        }
+        paths.extend(matched);
+    }
+    let paths: Vec<String> = paths.into_iter().collect();
 
-fn write_commit(tree_sha: &str, message: &str) -> String {
-    let (name, email) = get_author_info();
-    let author = format!("{} <{}>", name, email);
+    refresh_paths(&mut index, &paths);
+    if options.include {
+        return (index.clone(), index);
+    }
+    let mut partial = head_index;
+    refresh_paths(&mut partial, &paths);
+    (partial, index)
+}
 
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
+/// Bring index entries for `paths` in line with the working tree,
+/// dropping the ones whose files are gone
+fn refresh_paths(index: &mut Index, paths: &[String]) {
+    for path in paths {
+        if Path::new(path).is_file() {
+            index.add(IndexEntry {
+                path: path.clone(),
+                sha: hash_object(path, true, false),
+                mode: workdir::file_mode(path),
+            });
+        } else {
+            index.remove(path);
+        }
+    }
+}
 
-    let offset = chrono::Local::now().offset().fix().local_minus_utc();
-    let offset_hours = offset / 3600; // Convert seconds to hours
-    let offset_minutes = (offset.abs() % 3600) / 60; // Get remaining minutes
-    let offset_str = format!("{:+03}:{:02}", offset_hours, offset_minutes);
+/// Get the message from -m, -F, the commit being amended or the editor,
+/// cleaned up; aborts if it ends up empty
+///
+/// Whichever it is, the message goes through COMMIT_EDITMSG so the
+/// prepare-commit-msg and commit-msg hooks get to see (and change) it.
+/// `previous` is the SHA and commit being amended.
+fn commit_message(
+    options: &CommitOptions,
+    previous: Option<(&str, &Commit)>,
+    index: &Index,
+) -> String {
+    let use_editor = uses_editor(options);
+    let template = config::get_path("commit.template")
+        .filter(|_| use_editor && previous.is_none())
+        .map(|path| {
+            fs::read_to_string(&path).unwrap_or_else(|e| {
+                fatal(&format!(
+                    "could not read commit message template '{}': {}",
+                    path.display(),
+                    e
+                ))
+            })
+        });
+
+    // the source argument git passes to prepare-commit-msg
+    let (mut content, source) = if let Some(message) = &options.message {
+        (message.clone(), Some("message"))
+    } else if let Some(file) = &options.message_file {
+        (read_message_file(file), Some("message"))
+    } else if let Some((_, old)) = previous {
+        (old.message.clone(), Some("commit"))
+    } else if let Some(template) = &template {
+        (template.clone(), Some("template"))
+    } else {
+        (String::new(), None)
+    };
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    if options.signoff || !options.trailers.is_empty() {
+        content = add_trailers(options, &content);
+    }
+    if use_editor {
+        content.push('\n');
+        content.push_str(&message::comment_lines(&status_summary()));
+    }
 
-    let parent = resolve_head();
+    let path = repo::path("COMMIT_EDITMSG");
+    fs::write(&path, content)
+        .unwrap_or_else(|e| fatal(&format!("could not write {}: {}", path.display(), e)));
+    let path_arg = path.display().to_string();
 
-    let mut content = String::new();
-    content += &format!("tree {}\n", tree_sha);
-    if let Some(p) = parent.clone() {
-        content += &format!("parent {}\n", p);
+    let mut args = vec![path_arg.as_str()];
+    args.extend(source);
+    if let (Some("commit"), Some((sha, _))) = (source, previous) {
+        args.push(sha);
+    }
+    if run_commit_hook("prepare-commit-msg", &args, index, use_editor).is_err() {
+        std::process::exit(1);
     }
-    content += &format!("author {} {} {}\n", author, timestamp, offset_str);
-    content += "\n";
-    content += message;
-    content += "\n";
 
-    let full = format!("commit {}\0{}", content.len(), content);
+    if use_editor && let Err(e) = editor::launch_editor(&path) {
+        eprintln!("error: {}", e);
+        eprintln!("Please supply the message using either -m or -F option.");
+        std::process::exit(1);
+    }
 
-    let mut hasher = Sha1::new();
-    hasher.update(full.as_bytes());
-    let sha = hasher.finalize();
-    let sha_hex = format!("{:x}", sha);
+    if !options.no_verify && run_commit_hook("commit-msg", &[&path_arg], index, use_editor).is_err()
+    {
+        std::process::exit(1);
+    }
 
-    let dir = format!(".hit/objects/{}", &sha_hex[..2]);
-    let file = format!("{}", &sha_hex[2..]);
-    let object_path = Path::new(&dir).join(file);
+    let edited = fs::read_to_string(&path).unwrap_or_default();
+    let message = message::cleanup(&edited, use_editor);
+
+    if let Some(template) = template
+        && !message.is_empty()
+        && message
+            .strip_prefix(&message::cleanup(&template, true))
+            .is_some_and(only_signoffs)
+    {
+        eprintln!("Aborting commit; you did not edit the message.");
+        std::process::exit(1);
+    }
+    if only_signoffs(&message) && !options.allow_empty_message {
+        eprintln!("Aborting commit due to empty commit message.");
+        std::process::exit(1);
+    }
+    message
+}
 
-    if !object_path.exists() {
-        fs::create_dir_all(&dir).unwrap();
-        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
-        encoder.write_all(full.as_bytes()).unwrap();
-        let compressed = encoder.finish().unwrap();
-        fs::write(&object_path, compressed).unwrap();
+/// Report that the index has nothing new in it and stop, like git's summary line
+fn nothing_to_commit(initial: bool) -> ! {
+    let changes = status::collect_changes(&status::StatusOptions::default());
+    if !changes.unstaged.is_empty() {
+        println!("no changes added to commit (use \"hit add\" and/or \"hit commit -a\")");
+    } else if !changes.untracked.is_empty() {
+        println!("nothing added to commit but untracked files present (use \"hit add\" to track)");
+    } else if initial {
+        println!("nothing to commit (create/copy files and use \"hit add\" to track)");
+    } else {
+        println!("nothing to commit, working tree clean");
     }
+    std::process::exit(1);
+}
 
-    sha_hex
+/// Add `--trailer`s and, with `-s`, the committer's sign-off to a message
+///
+/// The sign-off ignores `trailer.*` config: it always goes at the end, and
+/// is only skipped when the last trailer is already the same sign-off.
+fn add_trailers(options: &CommitOptions, content: &str) -> String {
+    let mut text = trailers::Message::parse(content);
+    for argument in &options.trailers {
+        let (key, value) = trailers::split_argument(argument);
+        if key.is_empty() {
+            fatal(&format!("empty trailer token in trailer '{}'", argument));
+        }
+        text.add(&key, &value, trailers::Placement::default());
+    }
+    if options.signoff {
+        let committer = ident::committer().unwrap_or_else(|e| fatal(&e));
+        text.add(
+            "Signed-off-by",
+            &committer.name_email(),
+            trailers::Placement {
+                where_: Some(trailers::Where::End),
+                if_exists: Some(trailers::IfExists::AddIfDifferentNeighbor),
+                if_missing: Some(trailers::IfMissing::Add),
+            },
+        );
+    }
+    text.render()
 }
 
-fn update_head(new_sha: &str) {
-    let head_path = Path::new(".hit/HEAD");
-    let head_contents = fs::read_to_string(head_path).unwrap();
+/// Whether a cleaned-up message has nothing in it but sign-offs
+fn only_signoffs(message: &str) -> bool {
+    message
+        .lines()
+        .all(|line| line.trim().is_empty() || line.starts_with("Signed-off-by:"))
+}
 
-    if head_contents.starts_with("ref: ") {
-        let ref_path = head_contents[5..].trim();
-        let full_ref_path = Path::new(".hit").join(ref_path);
-        fs::write(full_ref_path, format!("{}\n", new_sha)).unwrap();
+/// Read a message given with -F ("-" for stdin)
+fn read_message_file(file: &str) -> String {
+    if file == "-" {
+        let mut text = String::new();
+        std::io::stdin()
+            .read_to_string(&mut text)
+            .unwrap_or_else(|e| fatal(&format!("could not read log from standard input: {}", e)));
+        text
     } else {
-        fs::write(".hit/HEAD", format!("{}\n", new_sha)).unwrap(); // detached HEAD
+        fs::read_to_string(file)
+            .unwrap_or_else(|e| fatal(&format!("could not read log file '{}': {}", file, e)))
     }
 }
 
-fn get_author_info() -> (String, String) {
-    let name = get_config_value("user", "name").unwrap_or(Some("You".to_owned()));
-    let email = get_config_value("user", "email").unwrap_or(Some("you@example.com".to_owned()));
+/// The help text and status listing shown (commented out) in the editor
+fn status_summary() -> String {
+    let changes = status::collect_changes(&status::StatusOptions::default());
+    let mut text = String::from(
+        "Please enter the commit message for your changes. Lines starting\n\
+         with '#' will be ignored, and an empty message aborts the commit.\n\n",
+    );
+
+    match refs::current_branch() {
+        Some(branch) => text += &format!("On branch {}\n", branch),
+        None => text += &format!("HEAD detached at {}\n", refs::head_description()),
+    }
 
-    (name.unwrap(), email.unwrap())
+    if !changes.staged.is_empty() {
+        text += "Changes to be committed:\n";
+        for (status, path) in &changes.staged {
+            text += &format!(
+                "\t{:<12}{}\n",
+                format!("{}:", status::describe(*status)),
+                path
+            );
+        }
+        text += "\n";
+    }
+    if !changes.unstaged.is_empty() {
+        text += "Changes not staged for commit:\n";
+        for (status, path) in &changes.unstaged {
+            text += &format!(
+                "\t{:<12}{}\n",
+                format!("{}:", status::describe(*status)),
+                path
+            );
+        }
+        text += "\n";
+    }
+    if !changes.untracked.is_empty() {
+        text += "Untracked files:\n";
+        for path in &changes.untracked {
+            text += &format!("\t{}\n", path);
+        }
+        text += "\n";
+    }
+    text
+}
+
+/// Write a commit object with explicit parents, returning its SHA
+///
+/// Author and committer come from the environment and config.
+pub fn write_commit(tree_sha: &str, parents: &[String], message: &str) -> String {
+    let author = ident::author().unwrap_or_else(|e| fatal(&e));
+    let committer = ident::committer().unwrap_or_else(|e| fatal(&e));
+    Commit::new(tree_sha, parents, author, committer, message).write()
+}
+
+/// Write the commit being made, signing it if asked to (`-S` or `commit.gpgSign`)
+fn create_commit(
+    options: &CommitOptions,
+    tree_sha: &str,
+    parents: &[String],
+    author: Ident,
+    committer: Ident,
+    message: &str,
+) -> String {
+    let mut commit = Commit::new(tree_sha, parents, author, committer, message);
+    let sign = options
+        .sign
+        .unwrap_or_else(|| config::get_bool("commit.gpgSign", false));
+    if sign {
+        let signature = signature::sign(
+            &commit.serialize_unsigned(),
+            options.signing_key.as_deref(),
+            &commit.committer,
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            fatal("failed to write commit object")
+        });
+        commit.gpgsig = Some(signature);
+    }
+    commit.write()
 }
@@ -1,92 +1,117 @@
 // i couldn't come up with a witty line for this file
 
+use crate::utils::fatal::fatal;
 use std::fs;
-use std::io::Write;
-use std::path::{Path, PathBuf};
-use std::time::{SystemTime, UNIX_EPOCH};
-
-use chrono::Offset;
-use flate2::Compression;
-use flate2::write::ZlibEncoder;
-use sha1::{Digest, Sha1};
-
-pub fn commit_tree(tree_sha: &str, message: &str) -> String {
-    let author = "Your Name <you@example.com>";
-    let now = chrono::Local::now();
-    let offset = now.offset().fix().local_minus_utc(); // in seconds
-    let offset_hours = offset / 3600; // Convert seconds to hours
-    let offset_minutes = (offset.abs() % 3600) / 60; // Get remaining minutes
-    let offset_str = format!("{:+03}:{:02}", offset_hours, offset_minutes);
-
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("Time error")
-        .as_secs();
-
-    // Find HEAD ref (e.g., refs/heads/master)
-    let head_path = Path::new(".hit/HEAD");
-    let head_contents = fs::read_to_string(head_path).expect("Failed to read .hit/HEAD");
-
-    let ref_path = if head_contents.starts_with("ref: ") {
-        let rel_path = head_contents[5..].trim();
-        PathBuf::from(".hit").join(rel_path)
-    } else {
-        panic!("Invalid HEAD format");
-    };
-
-    // Try reading the parent commit SHA from the ref
-    let parent_sha = fs::read_to_string(&ref_path).ok();
+use std::io::Read;
+
+use crate::utils::config;
+use crate::utils::ident;
+use crate::utils::objects::Commit;
+use crate::utils::refs;
+use crate::utils::signature;
+
+/// Options for `hit commit-tree`
+#[derive(Default)]
+pub struct CommitTreeOptions {
+    pub tree: String,
+    /// Revisions given with `-p`, in order
+    pub parents: Vec<String>,
+    /// Pieces of the message from `-m` (as given) and `-F` (file contents), in order
+    pub message: Vec<MessagePart>,
+    /// `-S` / `--no-gpg-sign`; unset means follow `commit.gpgSign`
+    pub sign: Option<bool>,
+    /// Key given with `-S<key>`
+    pub signing_key: Option<String>,
+}
 
-    // Begin commit body
-    let mut commit_body = String::new();
-    commit_body += &format!("tree {}\n", tree_sha);
+pub enum MessagePart {
+    Text(String),
+    File(String),
+}
 
-    if let Some(parent_sha) = parent_sha {
-        let parent_sha = parent_sha.trim();
-        if !parent_sha.is_empty() {
-            commit_body += &format!("parent {}\n", parent_sha);
+/// Write a commit object for `tree` and print its SHA
+///
+/// This is plumbing: parents are exactly the `-p`s given (none makes a root
+/// commit), the message is used as-is (read from stdin without `-m`/`-F`),
+/// and no ref is touched; pair it with `hit update-ref`.
+pub fn commit_tree(options: &CommitTreeOptions) -> String {
+    if refs::resolve_object(&options.tree).is_err() {
+        fatal(&format!("not a valid object name {}", options.tree));
+    }
+    let tree = refs::peel(&options.tree, "tree")
+        .unwrap_or_else(|_| fatal(&format!("{} is not a valid 'tree' object", options.tree)));
+
+    let mut parents: Vec<String> = Vec::new();
+    for parent in &options.parents {
+        let sha = refs::resolve_revision(parent)
+            .and_then(|sha| refs::peel_to_commit(&sha))
+            .map(|(sha, _)| sha)
+            .unwrap_or_else(|_| fatal(&format!("not a valid object name {}", parent)));
+        if parents.contains(&sha) {
+            eprintln!("error: duplicate parent {} ignored", sha);
+        } else {
+            parents.push(sha);
         }
     }
 
-    commit_body += &format!("author {} {} {}\n", author, timestamp, offset_str);
-    commit_body += &format!("committer {} {} {}\n", author, timestamp, offset_str);
-    commit_body += "\n";
-    commit_body += message;
-    commit_body += "\n";
-
-    // Prepend header: "commit <len>\0"
-    let mut full_commit = Vec::new();
-    let header = format!("commit {}\0", commit_body.len());
-    full_commit.extend_from_slice(header.as_bytes());
-    full_commit.extend_from_slice(commit_body.as_bytes());
-
-    // Hash the commit
-    let mut hasher = Sha1::new();
-    hasher.update(&full_commit);
-    let hash = hasher.finalize();
-    let hash_hex = format!("{:x}", hash);
-
-    // Store in .git/objects/xx/yyyy...
-    let object_dir = format!(".hit/objects/{}", &hash_hex[..2]);
-    let object_file = hash_hex[2..].to_string();
-    let object_path = Path::new(&object_dir).join(&object_file);
-
-    if !object_path.exists() {
-        fs::create_dir_all(&object_dir).expect("Failed to create object directory");
+    let message = build_message(&options.message);
+    let author = ident::author().unwrap_or_else(|e| fatal(&e));
+    let committer = ident::committer().unwrap_or_else(|e| fatal(&e));
+    let mut commit = Commit::new(&tree, &parents, author, committer, &message);
+
+    let sign = options
+        .sign
+        .unwrap_or_else(|| config::get_bool("commit.gpgSign", false));
+    if sign {
+        let signature = signature::sign(
+            &commit.serialize_unsigned(),
+            options.signing_key.as_deref(),
+            &commit.committer,
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            fatal("failed to write commit object")
+        });
+        commit.gpgsig = Some(signature);
+    }
 
-        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
-        encoder
-            .write_all(&full_commit)
-            .expect("Failed to compress commit");
-        let compressed = encoder.finish().expect("Failed to finish compression");
+    let sha = commit.write();
+    println!("{}", sha);
+    sha
+}
 
-        fs::write(&object_path, compressed).expect("Failed to write commit object");
+/// Put the message together like git: each `-m` or `-F` is its own paragraph,
+/// and with neither (or nothing in them) the message comes from stdin verbatim
+fn build_message(parts: &[MessagePart]) -> String {
+    let mut message = String::new();
+    for part in parts {
+        if !message.is_empty() {
+            message.push('\n');
+        }
+        match part {
+            MessagePart::Text(text) => message += text,
+            MessagePart::File(file) => message += &read_file(file),
+        }
+        if !message.is_empty() && !message.ends_with('\n') {
+            message.push('\n');
+        }
     }
+    if message.is_empty() {
+        message = read_file("-");
+    }
+    message
+}
 
-    // Update the ref (e.g., refs/heads/master)
-    fs::write(&ref_path, format!("{}\n", hash_hex)).expect("Failed to update ref");
-
-    // Output the commit SHA
-    println!("{}", hash_hex);
-    hash_hex
+/// Read a message file ("-" for stdin)
+fn read_file(file: &str) -> String {
+    if file == "-" {
+        let mut text = String::new();
+        std::io::stdin()
+            .read_to_string(&mut text)
+            .unwrap_or_else(|e| fatal(&format!("could not read log from standard input: {}", e)));
+        text
+    } else {
+        fs::read_to_string(file)
+            .unwrap_or_else(|e| fatal(&format!("could not read log file '{}': {}", file, e)))
+    }
 }
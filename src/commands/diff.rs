@@ -0,0 +1,99 @@
+// diff: what did i just do
+
+use crate::utils::diff::{self, DiffOutput, PatchOptions, Snapshot};
+use crate::utils::fatal::fatal;
+use crate::utils::hash_object::resolve_head;
+use crate::utils::index::Index;
+use crate::utils::pathspec;
+use crate::utils::refs;
+use crate::utils::renames::{self, RenameOptions};
+use std::io::Write;
+
+/// Options for `hit diff`
+#[derive(Default)]
+pub struct DiffOptions {
+    /// Compare the index (or `revs[0]`) with... the index, rather than the working tree
+    pub cached: bool,
+    /// Up to two revisions; "a..b" is split into two
+    pub revs: Vec<String>,
+    pub paths: Vec<String>,
+    pub patch: PatchOptions,
+    pub renames: RenameOptions,
+    /// What to print (`--stat`, `--name-status`, ...); the patch if nothing's asked for
+    pub output: DiffOutput,
+}
+
+/// Show changes between the working tree, the index and commits
+///
+/// - no revisions: working tree against the index
+/// - `--cached [<rev>]`: the index against `<rev>` (HEAD by default)
+/// - `<rev>`: the working tree against `<rev>`
+/// - `<rev> <rev>` or `<rev>..<rev>`: one commit against another
+pub fn diff(options: &DiffOptions) {
+    let mut revs = Vec::new();
+    for rev in &options.revs {
+        match rev.split_once("..") {
+            Some((from, to)) => {
+                revs.push(if from.is_empty() { "HEAD" } else { from }.to_string());
+                revs.push(if to.is_empty() { "HEAD" } else { to }.to_string());
+            }
+            None => revs.push(rev.clone()),
+        }
+    }
+    if revs.len() > 2 || (options.cached && revs.len() > 1) {
+        fatal("too many revisions");
+    }
+
+    let index = Index::load();
+    let (old, new) = match (revs.as_slice(), options.cached) {
+        ([], false) => {
+            let old = diff::index_snapshot(&index);
+            let new = diff::worktree_snapshot(old.keys());
+            (old, new)
+        }
+        ([], true) => (head_snapshot(), diff::index_snapshot(&index)),
+        ([rev], true) => (rev_snapshot(rev), diff::index_snapshot(&index)),
+        ([rev], false) => {
+            let old = rev_snapshot(rev);
+            // files the commit or the index know about, as they are on disk
+            let tracked: Vec<String> = old
+                .keys()
+                .cloned()
+                .chain(index.entries.iter().map(|e| e.path.clone()))
+                .collect();
+            (old, diff::worktree_snapshot(&tracked))
+        }
+        ([from, to], _) => (rev_snapshot(from), rev_snapshot(to)),
+        _ => unreachable!(),
+    };
+
+    let specs: Vec<String> = options
+        .paths
+        .iter()
+        .map(|p| pathspec::normalize(p))
+        .collect();
+
+    let pairs = diff::compare(&old, &new)
+        .into_iter()
+        .filter(|pair| specs.is_empty() || pathspec::matches_any(pair.path(), &specs))
+        .collect();
+    let pairs = renames::detect(pairs, &old, &options.renames);
+    let mut output = options.output.clone();
+    if output.is_empty() {
+        output.patch = true;
+    }
+    let _ = std::io::stdout().write_all(&output.format(&pairs, &options.patch));
+}
+
+/// HEAD's files, or nothing on an unborn branch
+fn head_snapshot() -> Snapshot {
+    match resolve_head() {
+        Some(_) => rev_snapshot("HEAD"),
+        None => Snapshot::new(),
+    }
+}
+
+fn rev_snapshot(rev: &str) -> Snapshot {
+    let (_, tree) = refs::resolve_commit(rev).unwrap_or_else(|e| fatal(&e));
+    diff::tree_snapshot(&tree).unwrap_or_else(|e| fatal(&e))
+}
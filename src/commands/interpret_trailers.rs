@@ -0,0 +1,73 @@
+// interpret-trailers: paperwork about the paperwork
+
+use crate::utils::fatal::fatal;
+use crate::utils::trailers::{self, Message, Placement};
+use std::fs;
+use std::io::Read;
+
+pub struct TrailerOptions {
+    /// "key=value" arguments, each with the placement in force when it was given
+    pub trailers: Vec<(String, Placement)>,
+    pub only_trailers: bool,
+    pub only_input: bool,
+    pub unfold: bool,
+    pub trim_empty: bool,
+    pub in_place: bool,
+    pub files: Vec<String>,
+}
+
+/// Add `--trailer`s to each message (files, or stdin) and print the result
+pub fn interpret_trailers(options: &TrailerOptions) {
+    if options.in_place && options.files.is_empty() {
+        fatal("no input file given for in-place editing");
+    }
+
+    if options.files.is_empty() {
+        let mut input = String::new();
+        std::io::stdin()
+            .read_to_string(&mut input)
+            .unwrap_or_else(|e| fatal(&format!("could not read from stdin: {}", e)));
+        print!("{}", process(&input, options));
+        return;
+    }
+
+    for file in &options.files {
+        let input = fs::read_to_string(file)
+            .unwrap_or_else(|e| fatal(&format!("could not read input file '{}': {}", file, e)));
+        let output = process(&input, options);
+        if options.in_place {
+            fs::write(file, output)
+                .unwrap_or_else(|e| fatal(&format!("could not write '{}': {}", file, e)));
+        } else {
+            print!("{}", output);
+        }
+    }
+}
+
+fn process(input: &str, options: &TrailerOptions) -> String {
+    let mut message = Message::parse(input);
+    if options.trim_empty {
+        message.trim_empty();
+    }
+    if !options.only_input {
+        for (argument, placement) in &options.trailers {
+            let (key, value) = trailers::split_argument(argument);
+            if key.is_empty() {
+                fatal(&format!("empty trailer token in trailer '{}'", argument));
+            }
+            message.add(&key, &value, *placement);
+        }
+        if options.trim_empty {
+            message.trim_empty();
+        }
+    }
+
+    if options.unfold {
+        message.unfold();
+    }
+    if options.only_trailers {
+        message.render_trailers()
+    } else {
+        message.render()
+    }
+}
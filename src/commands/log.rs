@@ -1,53 +1,358 @@
+use crate::utils::config;
+use crate::utils::diff::{self, DiffOutput, FilePair, PatchOptions, Snapshot};
+use crate::utils::fatal::fatal;
+use crate::utils::graph::Graph;
 use crate::utils::objects;
-use chrono::{DateTime, Utc};
-use std::fs;
-use std::path::Path;
-
-pub fn log() {
-    let mut current = resolve_head().expect("HEAD not found");
-
-    while let Some(commit) = read_commit(&current) {
-        print_commit(&current, &commit);
-        if let Some(parent) = &commit.parent {
-            current = parent.clone();
-        } else {
-            break;
+use crate::utils::pathspec;
+use crate::utils::pretty::{Decorate, Format, Writer};
+use crate::utils::regex::Regex;
+use crate::utils::renames::{self, RenameOptions};
+use crate::utils::revwalk::{RevWalk, WalkOptions};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+/// Options for `hit log`
+pub struct LogOptions {
+    /// Where to start, and with "^<rev>" or "<a>..<b>" what to leave out; HEAD by default
+    pub revs: Vec<String>,
+    /// Start from every ref as well, and HEAD (`--all`)
+    pub all: bool,
+    /// The order to show commits in and which parents to follow (`--topo-order`,
+    /// `--reverse`, `--first-parent`, `--ancestry-path`), and how many (`-n`)
+    pub walk: WalkOptions,
+    /// How to print each commit (`--format`, `--oneline`)
+    pub format: Format,
+    /// Check and show each commit's signature (`--show-signature`, `log.showSignature`)
+    pub show_signature: bool,
+    /// Show the refs pointing at each commit (`--decorate`, `log.decorate`)
+    pub decorate: Decorate,
+    /// Draw the history down the left, children before their parents (`--graph`)
+    pub graph: bool,
+    /// What to show of each commit's changes (`-p`, `--stat`, `--name-status`, ...)
+    pub output: DiffOutput,
+    pub patch: PatchOptions,
+    /// Only show commits that change `paths[0]`, following it back across renames (`--follow`)
+    pub follow: bool,
+    /// Only show commits that change these, and only these in their changes
+    pub paths: Vec<String>,
+    pub renames: RenameOptions,
+    /// Only commits whose author matches one of these (`--author`)
+    pub authors: Vec<String>,
+    /// Only commits whose message matches one of these (`--grep`)
+    pub greps: Vec<String>,
+    /// `--author` and `--grep` take extended regular expressions (`-E`)
+    pub extended_regexp: bool,
+    /// `--author` and `--grep` ignore case (`-i`)
+    pub ignore_case: bool,
+    /// Only commits made at or after this time (`--since`), in unix seconds
+    pub since: Option<i64>,
+    /// Only commits made at or before this time (`--until`)
+    pub until: Option<i64>,
+}
+
+impl Default for LogOptions {
+    fn default() -> Self {
+        LogOptions {
+            revs: Vec::new(),
+            all: false,
+            walk: WalkOptions::default(),
+            format: Format::Medium,
+            show_signature: config::get_bool("log.showSignature", false),
+            decorate: Decorate::configured(),
+            graph: false,
+            output: DiffOutput::default(),
+            patch: PatchOptions::default(),
+            follow: false,
+            paths: Vec::new(),
+            renames: RenameOptions::default(),
+            authors: Vec::new(),
+            greps: Vec::new(),
+            extended_regexp: false,
+            ignore_case: false,
+            since: None,
+            until: None,
         }
     }
 }
 
-fn resolve_head() -> Option<String> {
-    let head = fs::read_to_string(".hit/HEAD").ok()?;
-    if head.starts_with("ref: ") {
-        let ref_path = head[5..].trim();
-        let full_path = Path::new(".hit").join(ref_path);
-        fs::read_to_string(full_path)
-            .ok()
-            .map(|s| s.trim().to_string())
-    } else {
-        Some(head.trim().to_string())
+pub fn log(options: &LogOptions) {
+    if options.follow && options.paths.len() != 1 {
+        fatal("--follow requires exactly one pathspec");
     }
-}
+    if options.graph && options.walk.reverse {
+        fatal("options '--reverse' and '--graph' cannot be used together");
+    }
+    let compile = |pattern: &String| {
+        let regex = match options.extended_regexp {
+            true => Regex::new(pattern),
+            false => Regex::basic(pattern),
+        };
+        match regex {
+            Ok(regex) if options.ignore_case => regex.ignore_case(),
+            Ok(regex) => regex,
+            Err(e) => fatal(&format!("command line, '{}': {}", pattern, e)),
+        }
+    };
+    let authors: Vec<Regex> = options.authors.iter().map(compile).collect();
+    let greps: Vec<Regex> = options.greps.iter().map(compile).collect();
+    let wanted = |commit: &objects::Commit| {
+        let matches = |regexes: &[Regex], text: &str| {
+            regexes.is_empty() || regexes.iter().any(|r| r.is_match(text.as_bytes()))
+        };
+        let time = commit.committer.timestamp;
+        matches(&authors, &commit.author.name_email())
+            && matches(&greps, &commit.message)
+            && options.since.is_none_or(|since| time >= since)
+            && options.until.is_none_or(|until| time <= until)
+    };
 
-fn read_commit(sha: &str) -> Option<objects::Commit> {
-    match objects::Object::read(sha).ok()? {
-        objects::Object::Commit(c) => Some(c),
-        _ => None,
+    let mut followed = match options.follow {
+        true => options.paths.first().map(|path| pathspec::normalize(path)),
+        false => None,
+    };
+    let specs: Vec<String> = match options.follow {
+        true => Vec::new(),
+        false => options
+            .paths
+            .iter()
+            .map(|p| pathspec::normalize(p))
+            .collect(),
+    };
+    let limited = followed.is_some() || !specs.is_empty();
+    let colors = options.patch.colors.clone().unwrap_or_default();
+    let mut writer = Writer::new(options.format.clone(), options.show_signature, colors);
+    writer.decorate = options.decorate;
+    if options.graph {
+        writer.graph = Some(Graph::new(options.patch.colors.is_some()));
+    }
+    let mut stdout = std::io::stdout();
+
+    // log counts and reverses what it shows, after its own filters, like git
+    let walk_options = WalkOptions {
+        topo_order: options.walk.topo_order || options.graph,
+        reverse: false,
+        max_count: None,
+        ..options.walk.clone()
+    };
+    let simplify = !options.walk.ancestry_path;
+    let mut walk =
+        RevWalk::new(&options.revs, options.all, walk_options).unwrap_or_else(|e| fatal(&e));
+    // like git, a merge that changed nothing under the paths came from
+    // whichever parent had them that way, so only that side is looked at
+    // (but every side with --ancestry-path); a followed file is followed
+    // along first parents
+    walk.simplify(|commit, relevant| match options.follow {
+        true => commit.parents.first().cloned(),
+        false if specs.is_empty() || !simplify => None,
+        false => relevant
+            .iter()
+            .find(|parent| same_paths(commit, parent, &specs))
+            .cloned(),
+    });
+
+    // the changes to show for a commit, and whether it touched the paths
+    let mut examine = |commit: &objects::Commit, relevant: &[String]| {
+        let mut changes = Vec::new();
+        // like git, merges don't show what they changed, unless only first
+        // parents are followed, when they're shown against that
+        let diffed = commit.parents.len() < 2 || options.walk.first_parent;
+        if (!options.output.is_empty() || limited) && diffed {
+            changes = changed_files(commit, followed.as_deref(), &specs, &options.renames);
+        }
+        if let Some(path) = &followed {
+            changes.retain(|pair| pair.path() == path);
+        }
+        // a merge changed the paths if it differs there from every parent
+        // that wasn't left out (from any, when not simplifying), or when
+        // they all were, from any parent at all
+        let same = |parent: &String| same_paths(commit, parent, &specs);
+        let compared = match options.walk.first_parent {
+            true => &commit.parents[..commit.parents.len().min(1)],
+            false => &commit.parents[..],
+        };
+        let touched = !limited
+            || match commit.parents.len() > 1 {
+                true if specs.is_empty() => false,
+                true if relevant.is_empty() => !compared.iter().all(same),
+                true if simplify => !relevant.iter().any(same),
+                true => !relevant.iter().all(same),
+                false => !changes.is_empty(),
+            };
+        // further back, the file went by its old name
+        if followed.is_some()
+            && let Some(pair) = changes.first()
+            && let Some(old) = &pair.old
+        {
+            followed = Some(old.path.clone());
+        }
+        (changes, touched)
+    };
+
+    // `parents` are those the graph joins the commit to; with no changes,
+    // the graph only moves on past the commit without it being shown
+    let mut write =
+        |sha: &str, commit: &objects::Commit, changes: Option<&[FilePair]>, parents: &[String]| {
+            if let Some(graph) = &mut writer.graph {
+                graph.update(sha, parents);
+            }
+            let Some(changes) = changes else {
+                return;
+            };
+            let diff = match options.output.is_empty() {
+                true => Vec::new(),
+                false => options.output.format(changes, &options.patch),
+            };
+            // like git, "---" between the message and a stat and patch
+            let dashes = options.output.patch && options.output.stat.is_some();
+            let mut out = Vec::new();
+            writer.write(&mut out, sha, commit, &diff, dashes);
+            let _ = stdout.write_all(&out);
+        };
+
+    let max_count = options.walk.max_count;
+    let mut shown = 0;
+    if !options.graph {
+        let mut reversed = Vec::new();
+        while let Some((sha, commit)) = walk.next() {
+            if max_count.is_some_and(|max| shown >= max) {
+                break;
+            }
+            let (changes, touched) = examine(&commit, &walk.relevant_parents(&commit));
+            if !touched || !wanted(&commit) {
+                continue;
+            }
+            match options.walk.reverse {
+                true => reversed.push((sha, commit, changes)),
+                false => write(&sha, &commit, Some(&changes), &[]),
+            }
+            shown += 1;
+        }
+        for (sha, commit, changes) in reversed.into_iter().rev() {
+            write(&sha, &commit, Some(&changes), &[]);
+        }
+        return;
+    }
+
+    // the graph needs every commit first, to put children before parents
+    let commits: Vec<_> = walk.by_ref().collect();
+    let walked: HashSet<String> = commits.iter().map(|(sha, _)| sha.clone()).collect();
+    let mut entries = Vec::new();
+    let mut untouched = HashMap::new();
+    for (sha, commit) in commits {
+        let relevant = walk.relevant_parents(&commit);
+        let (changes, mut touched) = examine(&commit, &relevant);
+        // without simplifying, a merge of two sides still shown keeps them
+        // joined, whatever it changed
+        touched |= !simplify && relevant.len() > 1;
+        // like git, following a file doesn't skip over the commits that
+        // don't touch it: the graph still moves on through them, unseen
+        if touched || options.follow {
+            entries.push((sha, commit, changes, touched));
+            continue;
+        }
+        // the graph goes on past it to its one parent, or to its one
+        // relevant parent if it's a merge; a merge of several stays put
+        let parents = walk.simplified_parents(&commit);
+        if parents.len() < 2 || options.walk.first_parent {
+            untouched.insert(sha, parents.into_iter().next());
+        } else if let [parent] = &relevant[..] {
+            untouched.insert(sha, Some(parent.clone()));
+        }
+    }
+    entries.retain(|(_, commit, ..)| wanted(commit));
+    let shown_shas: HashSet<String> = entries.iter().map(|(sha, ..)| sha.clone()).collect();
+    for (sha, mut commit, changes, touched) in entries {
+        if max_count.is_some_and(|max| shown >= max) {
+            break;
+        }
+        // like git, the graph joins a commit to the nearest commits shown
+        // before it, skipping those that didn't touch the paths, and
+        // dropping parents that have nothing shown before them at all
+        let rewrite = |mut parent: String| {
+            while let Some(next) = untouched.get(&parent) {
+                parent = next.clone()?;
+            }
+            Some(parent)
+        };
+        let mut parents: Vec<String> = Vec::new();
+        for parent in walk
+            .simplified_parents(&commit)
+            .into_iter()
+            .filter_map(rewrite)
+        {
+            if !parents.contains(&parent) {
+                parents.push(parent);
+            }
+        }
+        // which is also what "Merge:" and %P show, and what's diffed against
+        let diffed = commit.parents.len() < 2 || options.walk.first_parent;
+        let moved = commit.parents.first() != parents.first();
+        commit.parents = parents.clone();
+        let changes = match diffed && moved && !options.output.is_empty() {
+            true => changed_files(&commit, None, &specs, &options.renames),
+            false => changes,
+        };
+        if options.walk.first_parent {
+            parents.truncate(1);
+        }
+        // a parent --first-parent never walked to counts as shown, as in git
+        parents.retain(|parent| {
+            shown_shas.contains(parent) || !walked.contains(parent) && !walk.is_excluded(parent)
+        });
+        write(&sha, &commit, touched.then_some(&changes), &parents);
+        shown += usize::from(touched);
     }
 }
 
-fn print_commit(sha: &str, commit: &objects::Commit) {
-    println!("commit {}", sha);
+/// The files `commit` changed since its first parent under `specs` (all of
+/// them if empty), with renames found; when following a file, only what
+/// could matter for it
+pub fn changed_files(
+    commit: &objects::Commit,
+    followed: Option<&str>,
+    specs: &[String],
+    renames: &RenameOptions,
+) -> Vec<FilePair> {
+    let old = match commit
+        .parents
+        .first()
+        .and_then(|parent| read_commit(parent))
+    {
+        Some(parent) => diff::tree_snapshot(&parent.tree).unwrap_or_default(),
+        None => Snapshot::new(),
+    };
+    let new = diff::tree_snapshot(&commit.tree).unwrap_or_default();
+    let mut pairs = diff::compare(&old, &new);
+    pairs.retain(|pair| specs.is_empty() || pathspec::matches_any(pair.path(), specs));
 
-    // Parse and format timestamp
-    let datetime: DateTime<Utc> = DateTime::from_timestamp(commit.timestamp as i64, 0).unwrap();
+    let mut renames = *renames;
+    if let Some(path) = followed {
+        // the file itself, plus anything it could have come from: like git,
+        // following looks for copies of any file, not just changed ones
+        pairs.retain(|pair| pair.path() == path || pair.old.is_some());
+        renames.renames = true;
+        renames.copies = true;
+        renames.find_copies_harder = true;
+    }
+    renames::detect(pairs, &old, &renames)
+}
 
-    println!("Author: {}", commit.author);
-    println!(
-        "Date:   {} {}\n",
-        datetime.format("%a %b %e %T %Y"),
-        commit.timezone
-    );
+/// `commit` has the same files under `specs` as `parent`
+fn same_paths(commit: &objects::Commit, parent: &str, specs: &[String]) -> bool {
+    let Some(parent) = read_commit(parent) else {
+        return false;
+    };
+    let old = diff::tree_snapshot(&parent.tree).unwrap_or_default();
+    let new = diff::tree_snapshot(&commit.tree).unwrap_or_default();
+    let pairs = diff::compare(&old, &new);
+    !pairs
+        .iter()
+        .any(|pair| pathspec::matches_any(pair.path(), specs))
+}
 
-    println!("    {}\n", commit.message);
+fn read_commit(sha: &str) -> Option<objects::Commit> {
+    match objects::Object::read(sha).ok()? {
+        objects::Object::Commit(c) => Some(*c),
+        _ => None,
+    }
 }
@@ -1,29 +1,135 @@
-use crate::commands::commit::load_tree_map_from_commit;
+use crate::utils::fatal::fatal;
 use crate::utils::hash_object::resolve_head;
-use crate::utils::index::{Index, IndexEntry};
-use std::path::PathBuf;
+use crate::utils::index::Index;
+use crate::utils::objects::Object;
+use crate::utils::pathspec;
+use crate::utils::refs;
+use crate::utils::repo;
+use crate::utils::workdir;
+use std::fs;
 
-/// Reset index entry to match HEAD commit
-pub fn reset(path: &str) {
-    let head_sha = resolve_head().expect("No HEAD found");
-    let head_tree = load_tree_map_from_commit(&head_sha);
+pub enum ResetMode {
+    /// Only move the branch
+    Soft,
+    /// Move the branch and reset the index
+    Mixed,
+    /// Move the branch, reset the index and overwrite the working tree
+    Hard,
+}
 
-    let mut index = Index::load();
-    let file_path = PathBuf::from(path);
-
-    if let Some(sha) = head_tree.get(&file_path) {
-        // Re-stage the version from HEAD (unstage new changes)
-        let entry = IndexEntry {
-            path: path.to_string(),
-            sha: sha.clone(),
-            mode: "100644".to_string(),
-        };
-        index.add(entry);
-        println!("Unstaged changes in '{}'", path);
+/// Reset the current branch to a commit
+pub fn reset_commit(rev: &str, mode: ResetMode) {
+    let (sha, tree) = refs::resolve_commit(rev).unwrap_or_else(|e| fatal(&e));
+
+    if let Some(old_head) = resolve_head() {
+        fs::write(repo::path("ORIG_HEAD"), format!("{}\n", old_head))
+            .expect("Failed to write ORIG_HEAD");
+    }
+
+    match mode {
+        ResetMode::Soft => {}
+        ResetMode::Mixed => {
+            let index = Index::from_tree(&tree).unwrap_or_else(|e| fatal(&e));
+            index.save();
+        }
+        ResetMode::Hard => {
+            // the old side is HEAD's tree and the index together, so a file
+            // taken out of the index (`rm --cached`) is restored too
+            if let Err(e) = workdir::checkout_tree(&tree, true) {
+                fatal(&e);
+            }
+        }
+    }
+
+    if let Err(e) = refs::update_head(&sha, &format!("reset: moving to {}", rev)) {
+        fatal(&e);
+    }
+
+    match mode {
+        ResetMode::Hard => {
+            let subject = match Object::read(&sha) {
+                Ok(Object::Commit(commit)) => commit.subject().to_string(),
+                _ => String::new(),
+            };
+            println!("HEAD is now at {} {}", &sha[..7], subject);
+        }
+        ResetMode::Mixed => print_unstaged(),
+        ResetMode::Soft => {}
+    }
+}
+
+/// Reset index entries matching `paths` to their state in `rev`
+pub fn reset_paths(rev: &str, paths: &[String]) {
+    // on an unborn branch there's nothing to reset to, so paths just get unstaged
+    let source = if rev == "HEAD" && resolve_head().is_none() {
+        Index {
+            entries: Vec::new(),
+        }
     } else {
-        index.remove(path);
-        println!("Removed '{}' from staging (not in HEAD)", path);
+        let (_, tree) = refs::resolve_commit(rev).unwrap_or_else(|e| fatal(&e));
+        Index::from_tree(&tree).unwrap_or_else(|e| fatal(&e))
+    };
+
+    let mut index = Index::load();
+
+    for raw in paths {
+        let spec = pathspec::normalize(raw);
+        let from_source: Vec<_> = source
+            .entries
+            .iter()
+            .filter(|e| pathspec::matches(&e.path, &spec))
+            .cloned()
+            .collect();
+        let staged_only: Vec<String> = index
+            .entries
+            .iter()
+            .filter(|e| pathspec::matches(&e.path, &spec) && source.get(&e.path).is_none())
+            .map(|e| e.path.clone())
+            .collect();
+
+        if from_source.is_empty() && staged_only.is_empty() {
+            eprintln!(
+                "error: pathspec '{}' did not match any file(s) known to hit",
+                raw
+            );
+            std::process::exit(1);
+        }
+
+        for entry in from_source {
+            // Re-stage the version from the commit (unstage new changes)
+            if index.get(&entry.path).map(|e| (&e.sha, &e.mode)) != Some((&entry.sha, &entry.mode))
+            {
+                println!("Unstaged changes in '{}'", entry.path);
+            }
+            index.add(entry);
+        }
+        for path in staged_only {
+            index.remove(&path);
+            println!("Removed '{}' from staging (not in {})", path, rev);
+        }
     }
 
     index.save();
 }
+
+/// List working tree files that now differ from the index
+fn print_unstaged() {
+    let index = Index::load();
+    let changed: Vec<_> = index
+        .entries
+        .iter()
+        .filter(|e| workdir::is_modified(e))
+        .collect();
+
+    if !changed.is_empty() {
+        println!("Unstaged changes after reset:");
+        for entry in changed {
+            let status = if std::path::Path::new(&entry.path).exists() {
+                'M'
+            } else {
+                'D'
+            };
+            println!("{}\t{}", status, entry.path);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+// restore: for when you want your old file back and your dignity intact
+
+use crate::utils::fatal::fatal;
+use crate::utils::hash_object::resolve_head;
+use crate::utils::hooks;
+use crate::utils::index::Index;
+use crate::utils::pathspec;
+use crate::utils::refs;
+use crate::utils::workdir;
+
+/// Restore paths in the working tree and/or index from the index or a commit
+///
+/// Without `--source`, the working tree is restored from the index and the
+/// index (`--staged`) from HEAD.
+pub fn restore(paths: &[String], source: Option<&str>, staged: bool, worktree: bool) {
+    let worktree = worktree || !staged;
+    let specs: Vec<String> = paths.iter().map(|p| pathspec::normalize(p)).collect();
+
+    let mut index = Index::load();
+    let source_index = match source {
+        Some(rev) => load_commit_index(rev),
+        None if staged => match resolve_head() {
+            Some(_) => load_commit_index("HEAD"),
+            None => Index {
+                entries: Vec::new(),
+            },
+        },
+        None => Index::load(),
+    };
+
+    for (spec, raw) in specs.iter().zip(paths) {
+        let known = source_index
+            .entries
+            .iter()
+            .chain(index.entries.iter())
+            .any(|e| pathspec::matches(&e.path, spec));
+        if !known {
+            pathspec_error(raw);
+        }
+    }
+
+    // paths tracked now but missing from the source go away
+    let removed: Vec<String> = index
+        .entries
+        .iter()
+        .filter(|e| pathspec::matches_any(&e.path, &specs))
+        .filter(|e| source_index.get(&e.path).is_none())
+        .map(|e| e.path.clone())
+        .collect();
+    let restored: Vec<_> = source_index
+        .entries
+        .iter()
+        .filter(|e| pathspec::matches_any(&e.path, &specs))
+        .collect();
+
+    if worktree {
+        for entry in &restored {
+            if let Err(e) = workdir::write_entry(entry) {
+                fatal(&e);
+            }
+        }
+        for path in &removed {
+            workdir::remove_entry(path);
+        }
+    }
+
+    if staged {
+        for entry in &restored {
+            index.add((*entry).clone());
+        }
+        for path in &removed {
+            index.remove(path);
+        }
+        index.save();
+    }
+}
+
+/// `checkout [<rev>] -- <paths>`: overwrite paths from the index, or from a
+/// commit (updating the index too)
+pub fn checkout_paths(rev: Option<&str>, paths: &[String]) {
+    let mut index = Index::load();
+    let source_index = match rev {
+        Some(rev) => load_commit_index(rev),
+        None => Index::load(),
+    };
+
+    let mut restored = Vec::new();
+    for raw in paths {
+        let spec = pathspec::normalize(raw);
+        let matched: Vec<_> = source_index
+            .entries
+            .iter()
+            .filter(|e| pathspec::matches(&e.path, &spec))
+            .collect();
+        if matched.is_empty() {
+            pathspec_error(raw);
+        }
+        restored.extend(matched);
+    }
+
+    for entry in restored {
+        if let Err(e) = workdir::write_entry(entry) {
+            fatal(&e);
+        }
+        if rev.is_some() {
+            index.add(entry.clone());
+        }
+    }
+
+    if rev.is_some() {
+        index.save();
+    }
+
+    let head = resolve_head();
+    if hooks::post_checkout(head.as_deref(), head.as_deref(), false).is_err() {
+        std::process::exit(1);
+    }
+}
+
+fn load_commit_index(rev: &str) -> Index {
+    let (_, tree) = refs::resolve_commit(rev).unwrap_or_else(|e| fatal(&e));
+    Index::from_tree(&tree).unwrap_or_else(|e| fatal(&e))
+}
+
+fn pathspec_error(path: &str) -> ! {
+    eprintln!(
+        "error: pathspec '{}' did not match any file(s) known to hit",
+        path
+    );
+    std::process::exit(1);
+}
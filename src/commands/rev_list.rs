@@ -0,0 +1,160 @@
+// rev-list: log with all the personality taken out, for scripts
+
+use crate::utils::fatal::fatal;
+use crate::utils::objects::Object;
+use crate::utils::refs;
+use crate::utils::revwalk::{RevWalk, WalkOptions};
+use std::collections::HashSet;
+
+/// Options for `hit rev-list`
+#[derive(Default)]
+pub struct RevListOptions {
+    /// "<rev>", "^<rev>" and "<a>..<b>"
+    pub revs: Vec<String>,
+    /// Start from every ref and HEAD too
+    pub all: bool,
+    pub walk: WalkOptions,
+    /// Only say how many there are (`--count`)
+    pub count: bool,
+    /// List the tags, trees and blobs the commits need too (`--objects`)
+    pub objects: bool,
+}
+
+/// Print the commits reachable from the revisions, newest first, one SHA
+/// a line; with `objects`, followed by every other object they need as
+/// "<sha> <path>"
+pub fn rev_list(options: &RevListOptions) {
+    // the whole walk, since its edge is wherever it was cut off
+    let walk_options = WalkOptions {
+        reverse: false,
+        max_count: None,
+        ..options.walk.clone()
+    };
+    let mut walk =
+        RevWalk::new(&options.revs, options.all, walk_options).unwrap_or_else(|e| fatal(&e));
+    let mut commits: Vec<_> = walk.by_ref().collect();
+    // like git, whatever the left out commits at the edge of the walk
+    // already have isn't listed with the objects
+    let mut seen = HashSet::new();
+    if options.objects {
+        let edges: HashSet<&String> = commits
+            .iter()
+            .flat_map(|(_, commit)| commit.parents.iter())
+            .filter(|parent| walk.is_excluded(parent))
+            .collect();
+        for edge in edges {
+            if let Ok((_, commit)) = refs::peel_to_commit(edge) {
+                mark_tree(&commit.tree, &mut seen);
+            }
+        }
+    }
+    if let Some(max) = options.walk.max_count {
+        commits.truncate(max);
+    }
+    if options.walk.reverse {
+        commits.reverse();
+    }
+    let mut lines: Vec<String> = commits.iter().map(|(sha, _)| sha.clone()).collect();
+
+    if options.objects {
+        // tags named to be left out are skipped as well
+        for (sha, left_out) in tips(options) {
+            for (sha, name) in tags(&sha) {
+                if seen.insert(sha.clone()) && !left_out {
+                    lines.push(format!("{} {}", sha, name));
+                }
+            }
+        }
+        for (_, commit) in &commits {
+            list_tree(&commit.tree, "", &mut seen, &mut lines);
+        }
+    }
+
+    match options.count {
+        true => println!("{}", lines.len()),
+        false => lines.iter().for_each(|line| println!("{}", line)),
+    }
+}
+
+/// What the walk started from or stopped at (and so left out), before
+/// peeling tags
+fn tips(options: &RevListOptions) -> Vec<(String, bool)> {
+    let mut names = Vec::new();
+    for rev in &options.revs {
+        if let Some((from, to)) = rev.split_once("..") {
+            names.push((if from.is_empty() { "HEAD" } else { from }, true));
+            names.push((if to.is_empty() { "HEAD" } else { to }, false));
+        } else if let Some(rev) = rev.strip_prefix('^') {
+            names.push((rev, true));
+        } else {
+            names.push((rev, false));
+        }
+    }
+    if options.revs.is_empty() && !options.all {
+        names.push(("HEAD", false));
+    }
+    let mut tips: Vec<(String, bool)> = names
+        .into_iter()
+        .filter_map(|(name, left_out)| Some((refs::resolve_revision(name).ok()?, left_out)))
+        .collect();
+    if options.all {
+        tips.extend(refs::all_refs().into_iter().map(|(_, sha)| (sha, false)));
+    }
+    tips
+}
+
+/// The tags on the way from `sha` to what they point at, with their names
+fn tags(sha: &str) -> Vec<(String, String)> {
+    let mut tags = Vec::new();
+    let mut sha = sha.to_string();
+    while let Ok(Object::Tag(tag)) = Object::read(&sha) {
+        tags.push((sha, tag.name));
+        sha = tag.object;
+    }
+    tags
+}
+
+/// `tree` and everything in it not seen yet, depth first, as "<sha> <path>"
+fn list_tree(tree: &str, path: &str, seen: &mut HashSet<String>, lines: &mut Vec<String>) {
+    if !seen.insert(tree.to_string()) {
+        return;
+    }
+    lines.push(format!("{} {}", tree, path));
+    let Ok(Object::Tree(tree)) = Object::read(tree) else {
+        return;
+    };
+    for entry in tree.entries {
+        let path = match path.is_empty() {
+            true => entry.name,
+            false => format!("{}/{}", path, entry.name),
+        };
+        match entry.mode.as_str() {
+            "40000" => list_tree(&entry.sha, &path, seen, lines),
+            // submodules live in another repository
+            "160000" => {}
+            _ => {
+                if seen.insert(entry.sha.clone()) {
+                    lines.push(format!("{} {}", entry.sha, path));
+                }
+            }
+        }
+    }
+}
+
+/// Count `tree` and everything in it as seen
+fn mark_tree(tree: &str, seen: &mut HashSet<String>) {
+    if !seen.insert(tree.to_string()) {
+        return;
+    }
+    let Ok(Object::Tree(tree)) = Object::read(tree) else {
+        return;
+    };
+    for entry in tree.entries {
+        match entry.mode.as_str() {
+            "40000" => mark_tree(&entry.sha, seen),
+            _ => {
+                seen.insert(entry.sha);
+            }
+        }
+    }
+}
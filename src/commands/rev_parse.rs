@@ -0,0 +1,121 @@
+// rev-parse: what you typed, translated into what hit meant
+
+use crate::utils::fatal::fatal;
+use crate::utils::objects::Object;
+use crate::utils::refs::{self, Head};
+use crate::utils::repo;
+
+/// What `hit rev-parse` was asked for, printed in order
+pub enum Query {
+    /// A revision, "^<rev>" or "<a>..<b>"
+    Rev(String),
+    /// `--show-toplevel`
+    Toplevel,
+    /// `--hit-dir`
+    HitDir,
+}
+
+/// Options for `hit rev-parse`
+#[derive(Default)]
+pub struct RevParseOptions {
+    pub queries: Vec<Query>,
+    /// Exactly one revision, which must name an object (`--verify`)
+    pub verify: bool,
+    /// With `verify`, just fail instead of complaining (`-q`)
+    pub quiet: bool,
+    /// Abbreviate SHAs to at least this many characters (`--short`)
+    pub short: Option<usize>,
+    /// Print refs' short names instead of SHAs (`--abbrev-ref`)
+    pub abbrev_ref: bool,
+}
+
+/// Print the SHA (or name) of each revision, and the paths asked for
+pub fn rev_parse(options: &RevParseOptions) {
+    if !repo::exists() {
+        fatal(&format!(
+            "not a hit repository: {}",
+            repo::hit_dir().display()
+        ));
+    }
+    if options.verify {
+        let mut revs = options.queries.iter().filter_map(|query| match query {
+            Query::Rev(rev) => Some(rev),
+            _ => None,
+        });
+        let found = match (revs.next(), revs.next()) {
+            (Some(rev), None) => refs::resolve_object(rev)
+                .ok()
+                .filter(|sha| Object::read(sha).is_ok())
+                .map(|sha| (rev, sha)),
+            _ => None,
+        };
+        let Some((rev, sha)) = found else {
+            if options.quiet {
+                std::process::exit(1);
+            }
+            fatal("Needed a single revision");
+        };
+        println!("{}", show(options, rev, &sha));
+        return;
+    }
+
+    for query in &options.queries {
+        match query {
+            Query::Rev(rev) => {
+                if let Some((from, to)) = rev.split_once("..") {
+                    let from = if from.is_empty() { "HEAD" } else { from };
+                    let to = if to.is_empty() { "HEAD" } else { to };
+                    println!("{}", show(options, to, &resolve(to)));
+                    println!("^{}", show(options, from, &resolve(from)));
+                } else if let Some(rev) = rev.strip_prefix('^') {
+                    println!("^{}", show(options, rev, &resolve(rev)));
+                } else {
+                    let line = show(options, rev, &resolve(rev));
+                    // like git, --abbrev-ref prints nothing for what isn't a ref
+                    if !line.is_empty() {
+                        println!("{}", line);
+                    }
+                }
+            }
+            Query::Toplevel => match std::env::current_dir() {
+                Ok(dir) => println!("{}", dir.display()),
+                Err(e) => fatal(&format!("cannot find the working tree: {}", e)),
+            },
+            Query::HitDir => println!("{}", repo::hit_dir().display()),
+        }
+    }
+}
+
+/// How `rev`, which resolved to `sha`, gets printed
+fn show(options: &RevParseOptions, rev: &str, sha: &str) -> String {
+    if options.abbrev_ref {
+        return abbrev_ref(rev);
+    }
+    match options.short {
+        Some(len) => refs::abbreviate(sha, len),
+        None => sha.to_string(),
+    }
+}
+
+/// The short name of the ref `rev` names: HEAD's branch, or "HEAD" when
+/// detached; nothing if it isn't a ref
+fn abbrev_ref(rev: &str) -> String {
+    if rev == "HEAD" || rev == "@" {
+        return match refs::read_head() {
+            Some(Head::Branch(refname)) => refs::short_name(&refname).to_string(),
+            _ => "HEAD".to_string(),
+        };
+    }
+    refs::dwim_ref(rev)
+        .map(|refname| refs::short_name(&refname).to_string())
+        .unwrap_or_default()
+}
+
+fn resolve(rev: &str) -> String {
+    refs::resolve_object(rev).unwrap_or_else(|_| {
+        fatal(&format!(
+            "ambiguous argument '{}': unknown revision or path not in the working tree.",
+            rev
+        ))
+    })
+}
@@ -0,0 +1,140 @@
+// show: like cat-file -p, but it dressed up for the occasion
+
+use crate::commands::log::changed_files;
+use crate::utils::date::{self, Style};
+use crate::utils::diff::{DiffOutput, PatchOptions};
+use crate::utils::fatal::fatal;
+use crate::utils::objects::{Object, Tag};
+use crate::utils::pretty::{Format, Writer};
+use crate::utils::refs;
+use crate::utils::renames::RenameOptions;
+use std::collections::HashSet;
+use std::io::Write;
+
+/// Options for `hit show`
+pub struct ShowOptions {
+    /// What to show; HEAD if nothing's named
+    pub objects: Vec<String>,
+    /// How to print commits (`--format`, `--pretty`, `--oneline`)
+    pub format: Format,
+    /// What to show of each commit's changes; the patch if nothing's asked for
+    pub output: DiffOutput,
+    /// Show no changes at all (`-s`, `--no-patch`)
+    pub no_patch: bool,
+    pub patch: PatchOptions,
+    pub renames: RenameOptions,
+    pub show_signature: bool,
+}
+
+impl Default for ShowOptions {
+    fn default() -> Self {
+        ShowOptions {
+            objects: Vec::new(),
+            format: Format::Medium,
+            output: DiffOutput::default(),
+            no_patch: false,
+            patch: PatchOptions::default(),
+            renames: RenameOptions::default(),
+            show_signature: false,
+        }
+    }
+}
+
+/// Show objects the way git does
+///
+/// - commits: the header and message, then the changes against the first parent
+/// - annotated tags: the tagger and message, then whatever the tag points at
+/// - trees: the names inside, with a '/' after directories
+/// - blobs: the contents, as they are
+pub fn show(options: &ShowOptions) {
+    let names = match options.objects.is_empty() {
+        true => vec!["HEAD".to_string()],
+        false => options.objects.clone(),
+    };
+    let mut output = options.output.clone();
+    if output.is_empty() {
+        output.patch = true;
+    }
+    let colors = options.patch.colors.clone().unwrap_or_default();
+    let mut writer = Writer::new(options.format.clone(), options.show_signature, colors);
+
+    let mut stdout = std::io::stdout();
+    // like git, a commit named twice is only shown once
+    let mut seen = HashSet::new();
+    for name in &names {
+        let mut sha = refs::resolve_object(name).unwrap_or_else(|e| match name.contains(':') {
+            true => fatal(&e),
+            false => fatal(&format!(
+                "ambiguous argument '{}': unknown revision or path not in the working tree.",
+                name
+            )),
+        });
+        // a tag shows itself and then its target, which may be another tag
+        loop {
+            let object = Object::read(&sha).unwrap_or_else(|e| fatal(&e));
+            let mut out = Vec::new();
+            match object {
+                Object::Commit(_) if !seen.insert(sha.clone()) => {}
+                Object::Commit(commit) => {
+                    let diff = match options.no_patch {
+                        true => Vec::new(),
+                        false => {
+                            let changes = changed_files(&commit, None, &[], &options.renames);
+                            output.format(&changes, &options.patch)
+                        }
+                    };
+                    let dashes = output.patch && output.stat.is_some();
+                    writer.write(&mut out, &sha, &commit, &diff, dashes);
+                }
+                Object::Tag(tag) => {
+                    write_tag(&mut out, &tag, &mut writer);
+                    sha = tag.object.clone();
+                    let _ = stdout.write_all(&out);
+                    continue;
+                }
+                Object::Tree(tree) => {
+                    if writer.shown {
+                        out.push(b'\n');
+                    }
+                    writer.shown = true;
+                    let colors = &writer.colors;
+                    out.extend(
+                        format!("{}tree {}{}\n\n", colors.commit, name, colors.reset).bytes(),
+                    );
+                    for entry in &tree.entries {
+                        let slash = if entry.mode == "40000" { "/" } else { "" };
+                        out.extend(format!("{}{}\n", entry.name, slash).bytes());
+                    }
+                }
+                Object::Blob(content) => out = content,
+            }
+            let _ = stdout.write_all(&out);
+            break;
+        }
+    }
+}
+
+/// The tag's name, tagger and message, like git: the message as it is
+fn write_tag(out: &mut Vec<u8>, tag: &Tag, writer: &mut Writer) {
+    if writer.shown {
+        out.push(b'\n');
+    }
+    writer.shown = true;
+    let colors = &writer.colors;
+    out.extend(format!("{}tag {}{}\n", colors.commit, tag.name, colors.reset).bytes());
+    if let Some(tagger) = &tag.tagger {
+        out.extend(
+            format!(
+                "Tagger: {}\nDate:   {}\n",
+                tagger.name_email(),
+                date::format(tagger.timestamp, &tagger.timezone, Style::Default)
+            )
+            .bytes(),
+        );
+    }
+    out.push(b'\n');
+    out.extend(tag.message.bytes());
+    if let Some(signature) = &tag.signature {
+        out.extend(signature.bytes());
+    }
+}
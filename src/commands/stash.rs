@@ -0,0 +1,326 @@
+// stash: the junk drawer of version control
+
+use crate::commands::commit::write_commit;
+use crate::utils::fatal::fatal;
+use crate::utils::hash_object::{hash_object, resolve_head};
+use crate::utils::index::{Index, IndexEntry};
+use crate::utils::objects::{Commit, Object};
+use crate::utils::refs;
+use crate::utils::repo;
+use crate::utils::workdir;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+const STASH_REF: &str = "refs/stash";
+
+/// Save local changes as a stash commit and reset the working tree to HEAD
+///
+/// Like git, the stash is a commit whose tree is the working tree, with HEAD,
+/// a commit of the index, and (for `-u`) a commit of untracked files as parents.
+pub fn push(message: Option<&str>, include_untracked: bool) {
+    let head = resolve_head().unwrap_or_else(|| fatal("you do not have the initial commit yet"));
+    let head_commit = read_commit(&head);
+    let branch = refs::current_branch().unwrap_or_else(|| "(no branch)".to_string());
+    let summary = format!("{}: {} {}", branch, &head[..7], head_commit.subject());
+
+    let index = Index::load();
+    let index_tree = index.write_tree();
+
+    // snapshot tracked files as they are on disk
+    let mut worktree = Index {
+        entries: Vec::new(),
+    };
+    for entry in &index.entries {
+        if Path::new(&entry.path).is_file() {
+            worktree.entries.push(IndexEntry {
+                sha: hash_object(&entry.path, true, false),
+                ..entry.clone()
+            });
+        }
+    }
+    let worktree_tree = worktree.write_tree();
+
+    let untracked: Vec<String> = if include_untracked {
+        workdir::list_files()
+            .into_iter()
+            .filter(|path| index.get(path).is_none())
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    if index_tree == head_commit.tree && worktree_tree == head_commit.tree && untracked.is_empty() {
+        println!("No local changes to save");
+        return;
+    }
+
+    let index_commit = write_commit(
+        &index_tree,
+        std::slice::from_ref(&head),
+        &format!("index on {}", summary),
+    );
+    let mut parents = vec![head.clone(), index_commit];
+
+    if !untracked.is_empty() {
+        let untracked_index = Index {
+            entries: untracked
+                .iter()
+                .map(|path| IndexEntry {
+                    path: path.clone(),
+                    sha: hash_object(path, true, false),
+                    mode: workdir::file_mode(path),
+                })
+                .collect(),
+        };
+        let untracked_tree = untracked_index.write_tree();
+        parents.push(write_commit(
+            &untracked_tree,
+            &[],
+            &format!("untracked files on {}", summary),
+        ));
+    }
+
+    let stash_message = match message {
+        Some(msg) => format!("On {}: {}", branch, msg),
+        None => format!("WIP on {}", summary),
+    };
+    let stash_commit = write_commit(&worktree_tree, &parents, &stash_message);
+    if let Err(e) = refs::update_ref(STASH_REF, &stash_commit, &stash_message) {
+        fatal(&e);
+    }
+
+    // back to a clean HEAD
+    if let Err(e) = workdir::checkout_tree(&head_commit.tree, true) {
+        fatal(&e);
+    }
+    for path in &untracked {
+        workdir::remove_entry(path);
+    }
+
+    println!("Saved working directory and index state {}", stash_message);
+}
+
+/// Print every stash entry, newest first
+pub fn list() {
+    for (i, entry) in refs::read_reflog(STASH_REF).iter().rev().enumerate() {
+        println!("stash@{{{}}}: {}", i, entry.message);
+    }
+}
+
+/// Show which files a stash changes relative to the commit it was made on
+pub fn show(stash: Option<&str>) {
+    let (_, stash_commit) = resolve_stash(stash);
+    let base = read_commit(&stash_commit.parents[0]);
+    let before = Index::from_tree(&base.tree).unwrap_or_else(|e| fatal(&e));
+    let after = Index::from_tree(&stash_commit.tree).unwrap_or_else(|e| fatal(&e));
+
+    let paths: BTreeSet<&str> = before
+        .entries
+        .iter()
+        .chain(after.entries.iter())
+        .map(|e| e.path.as_str())
+        .collect();
+    for path in paths {
+        match (before.get(path), after.get(path)) {
+            (Some(a), Some(b)) if a.sha != b.sha || a.mode != b.mode => println!("M\t{}", path),
+            (Some(_), None) => println!("D\t{}", path),
+            (None, Some(_)) => println!("A\t{}", path),
+            _ => {}
+        }
+    }
+
+    if let Some(untracked) = stash_commit.parents.get(2) {
+        let untracked = read_commit(untracked);
+        let files = Index::from_tree(&untracked.tree).unwrap_or_else(|e| fatal(&e));
+        for entry in files.entries {
+            println!("?\t{}", entry.path);
+        }
+    }
+}
+
+/// Re-apply a stash on top of the current working tree
+///
+/// Each file changed by the stash is taken as a whole; a file that has also
+/// been changed locally (differently) is a conflict and nothing is applied.
+/// With `restore_index`, staged changes from the stash are staged again.
+pub fn apply(stash: Option<&str>, restore_index: bool) {
+    let (name, stash_commit) = resolve_stash(stash);
+    let base = read_commit(&stash_commit.parents[0]);
+    let base_index = Index::from_tree(&base.tree).unwrap_or_else(|e| fatal(&e));
+    let stashed = Index::from_tree(&stash_commit.tree).unwrap_or_else(|e| fatal(&e));
+    let stashed_index = match stash_commit.parents.get(1) {
+        Some(sha) => Index::from_tree(&read_commit(sha).tree).unwrap_or_else(|e| fatal(&e)),
+        None => Index {
+            entries: Vec::new(),
+        },
+    };
+    let untracked = match stash_commit.parents.get(2) {
+        Some(sha) => Index::from_tree(&read_commit(sha).tree).unwrap_or_else(|e| fatal(&e)),
+        None => Index {
+            entries: Vec::new(),
+        },
+    };
+
+    let paths: BTreeSet<String> = base_index
+        .entries
+        .iter()
+        .chain(stashed.entries.iter())
+        .map(|e| e.path.clone())
+        .collect();
+
+    let sha_of = |index: &Index, path: &str| index.get(path).map(|e| e.sha.clone());
+    let on_disk = |path: &str| {
+        Path::new(path)
+            .is_file()
+            .then(|| hash_object(path, false, false))
+    };
+
+    // work out what to do first so a conflict leaves everything untouched
+    let mut writes = Vec::new();
+    let mut deletes = Vec::new();
+    let mut conflicts = Vec::new();
+    for path in &paths {
+        let before = sha_of(&base_index, path);
+        let after = sha_of(&stashed, path);
+        if before == after {
+            continue;
+        }
+        let current = on_disk(path);
+        if current == after {
+            continue;
+        }
+        if current != before {
+            conflicts.push(path.clone());
+        } else if after.is_some() {
+            writes.push(stashed.get(path).unwrap().clone());
+        } else {
+            deletes.push(path.clone());
+        }
+    }
+    for entry in &untracked.entries {
+        if Path::new(&entry.path).exists() && on_disk(&entry.path) != Some(entry.sha.clone()) {
+            conflicts.push(entry.path.clone());
+        }
+    }
+
+    if !conflicts.is_empty() {
+        eprintln!(
+            "error: your local changes to the following files conflict with {}:",
+            name
+        );
+        for path in &conflicts {
+            eprintln!("\t{}", path);
+        }
+        eprintln!("please commit or stash them first");
+        std::process::exit(1);
+    }
+
+    let mut index = Index::load();
+    for entry in writes.iter().chain(untracked.entries.iter()) {
+        if let Err(e) = workdir::write_entry(entry) {
+            fatal(&e);
+        }
+    }
+    for path in &deletes {
+        workdir::remove_entry(path);
+    }
+
+    for entry in &writes {
+        // new files are staged so they don't turn into untracked strays
+        if base_index.get(&entry.path).is_none() && index.get(&entry.path).is_none() {
+            index.add(stashed_index.get(&entry.path).unwrap_or(entry).clone());
+        }
+    }
+    for path in &deletes {
+        index.remove(path);
+    }
+    if restore_index {
+        for path in &paths {
+            if sha_of(&base_index, path) == sha_of(&stashed_index, path) {
+                continue;
+            }
+            match stashed_index.get(path) {
+                Some(entry) => index.add(entry.clone()),
+                None => index.remove(path),
+            }
+        }
+    }
+    index.save();
+
+    println!("Applied {}", name);
+}
+
+/// Apply a stash and drop it if that worked
+pub fn pop(stash: Option<&str>, restore_index: bool) {
+    apply(stash, restore_index);
+    drop_stash(stash);
+}
+
+/// Remove a single stash entry
+pub fn drop_stash(stash: Option<&str>) {
+    let (name, _) = resolve_stash(stash);
+    let position = stash_position(stash);
+    let mut entries = refs::read_reflog(STASH_REF);
+    let removed = entries.remove(entries.len() - 1 - position);
+
+    if entries.is_empty() {
+        clear();
+    } else {
+        // the ref follows whatever is now on top; the reflog keeps the rest
+        let top = entries.last().unwrap().new.clone();
+        if let Err(e) =
+            refs::write_ref(STASH_REF, &top).and(refs::write_reflog(STASH_REF, &entries))
+        {
+            fatal(&e);
+        }
+    }
+
+    println!("Dropped {} ({})", name, removed.new);
+}
+
+/// Remove every stash entry
+pub fn clear() {
+    let _ = fs::remove_file(repo::path(STASH_REF));
+    let _ = fs::remove_file(repo::path(&format!("logs/{}", STASH_REF)));
+}
+
+/// Which reflog entry (0 = newest) a "stash@{n}" / "n" argument means
+fn stash_position(stash: Option<&str>) -> usize {
+    let Some(stash) = stash else {
+        return 0;
+    };
+    let n = stash
+        .strip_prefix("stash@{")
+        .and_then(|rest| rest.strip_suffix('}'))
+        .unwrap_or(stash);
+    n.parse()
+        .unwrap_or_else(|_| fatal(&format!("'{}' is not a stash reference", stash)))
+}
+
+fn resolve_stash(stash: Option<&str>) -> (String, Commit) {
+    let position = stash_position(stash);
+    let entries = refs::read_reflog(STASH_REF);
+    if entries.is_empty() {
+        fatal("no stash entries found");
+    }
+    let name = format!("stash@{{{}}}", position);
+    let entry = entries
+        .iter()
+        .rev()
+        .nth(position)
+        .unwrap_or_else(|| fatal(&format!("{} is not a valid reference", name)));
+
+    let commit = read_commit(&entry.new);
+    if commit.parents.is_empty() {
+        fatal(&format!("{} is not a stash-like commit", name));
+    }
+    (name, commit)
+}
+
+fn read_commit(sha: &str) -> Commit {
+    match Object::read(sha) {
+        Ok(Object::Commit(commit)) => *commit,
+        _ => fatal(&format!("{} is not a commit", sha)),
+    }
+}
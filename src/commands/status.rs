@@ -1,125 +1,150 @@
 // status refers to the level of being or condition of something, in this case, the state of the repository
 
-use crate::utils::hash_object::{hash_object, resolve_head};
+use crate::utils::diff::{self, FilePair, Snapshot};
+use crate::utils::hash_object::resolve_head;
+use crate::utils::index::Index;
 use crate::utils::objects::Object;
-use std::collections::{HashMap, HashSet};
-use std::fs;
-use std::path::PathBuf;
+use crate::utils::renames::{self, RenameOptions};
+use crate::utils::workdir;
+use std::path::Path;
+
+/// Options for `hit status`
+pub struct StatusOptions {
+    /// How to spot moved files (`-M`, `--no-renames`, `status.renames`)
+    pub renames: RenameOptions,
+}
+
+impl Default for StatusOptions {
+    fn default() -> Self {
+        StatusOptions {
+            renames: RenameOptions::configured("status.renames"),
+        }
+    }
+}
 
-pub fn status() {
+pub fn status(options: &StatusOptions) {
     // TODO: implement support for .hitignore
-    // TODO: add support for staged and tracked files (doesn't list ass staged or tracked)
-    let head_sha = resolve_head();
-    if head_sha.is_none() {
+    let changes = collect_changes(options);
+    if resolve_head().is_none() && changes.staged.is_empty() && changes.unstaged.is_empty() {
         println!("Empty repository, all files are untracked.");
         return;
     }
-    let head_commit = match Object::read(&(head_sha.unwrap())).expect("Failed to read HEAD object")
-    {
-        Object::Commit(commit) => commit,
-        _ => {
-            println!("HEAD does not point to a commit");
-            return;
-        }
-    };
-
-    let mut head_tree = HashMap::new();
-    build_tree_map(&head_commit.tree, PathBuf::from(""), &mut head_tree);
-
-    let mut modified = Vec::new();
-    let mut untracked = Vec::new();
-
-    let mut visited = HashSet::new();
 
-    for entry in walk_working_dir(".") {
-        let rel_path = entry.strip_prefix(".").unwrap().to_path_buf();
-        visited.insert(rel_path.clone());
-
-        if let Some(expected_sha) = head_tree.get(&rel_path) {
-            let actual_sha = hash_object(entry.to_str().unwrap(), false, false);
-            if &actual_sha != expected_sha {
-                modified.push(rel_path);
-            }
-        } else {
-            untracked.push(rel_path);
+    let mut sections = Vec::new();
+    for (title, list) in [
+        ("Changes to be committed:", &changes.staged),
+        ("Changes not staged for commit:", &changes.unstaged),
+    ] {
+        if !list.is_empty() {
+            let lines: Vec<String> = list
+                .iter()
+                .map(|(status, path)| {
+                    format!("  {:<12}{}", format!("{}:", describe(*status)), path)
+                })
+                .collect();
+            sections.push(format!("{}\n{}", title, lines.join("\n")));
         }
     }
+    if !changes.untracked.is_empty() {
+        let lines: Vec<String> = changes
+            .untracked
+            .iter()
+            .map(|path| format!("  {}", path))
+            .collect();
+        sections.push(format!("Untracked files:\n{}", lines.join("\n")));
+    }
+
+    if sections.is_empty() {
+        println!("Nothing to commit, working directory clean.");
+    } else {
+        println!("{}", sections.join("\n\n"));
+    }
+}
+
+/// Staged, unstaged and untracked changes, as (status letter, path) pairs
+pub struct Changes {
+    /// Index vs HEAD: 'A'dded, 'M'odified, 'D'eleted, or 'R'enamed/'C'opied ("old -> new")
+    pub staged: Vec<(char, String)>,
+    /// Working tree vs index: 'M'odified, 'D'eleted, or 'R'enamed/'C'opied to an untracked file
+    pub unstaged: Vec<(char, String)>,
+    pub untracked: Vec<String>,
+}
 
-    let deleted: Vec<_> = head_tree
-        .keys()
-        .filter(|path| !visited.contains(*path))
-        .cloned()
+/// Compare HEAD, the index and the working tree
+pub fn collect_changes(options: &StatusOptions) -> Changes {
+    let index = Index::load();
+    let staged_files = diff::index_snapshot(&index);
+    let head = resolve_head()
+        .and_then(|sha| match Object::read(&sha) {
+            Ok(Object::Commit(commit)) => diff::tree_snapshot(&commit.tree).ok(),
+            _ => None,
+        })
+        .unwrap_or_default();
+
+    let pairs = diff::compare(&head, &staged_files);
+    let staged = renames::detect(pairs, &head, &options.renames)
+        .iter()
+        .map(describe_pair)
         .collect();
 
-    // Print results
-    if !modified.is_empty() {
-        println!("Changes not staged for commit:");
-        for path in &modified {
-            println!("  modified:   {}", path.display());
-        }
-    }
+    let mut unstaged: Vec<(char, String)> = index
+        .entries
+        .iter()
+        .filter(|entry| workdir::is_modified(entry))
+        .map(|entry| {
+            let status = if Path::new(&entry.path).exists() {
+                'M'
+            } else {
+                'D'
+            };
+            (status, entry.path.clone())
+        })
+        .collect();
 
-    if !deleted.is_empty() {
-        println!("\nDeleted files:");
-        for path in &deleted {
-            println!("  deleted:    {}", path.display());
-        }
-    }
+    let mut untracked: Vec<String> = workdir::list_files()
+        .into_iter()
+        .filter(|path| index.get(path).is_none())
+        .collect();
 
-    if !untracked.is_empty() {
-        println!("\nUntracked files:");
-        for path in &untracked {
-            println!("  {}", path.display());
+    // a deleted file that turns up untracked elsewhere was moved
+    let gone: Snapshot = unstaged
+        .iter()
+        .filter(|(status, _)| *status == 'D')
+        .filter_map(|(_, path)| staged_files.get_key_value(path))
+        .map(|(path, side)| (path.clone(), side.clone()))
+        .collect();
+    let pairs = diff::compare(&gone, &diff::worktree_snapshot(&untracked));
+    for pair in renames::detect(pairs, &staged_files, &options.renames) {
+        if let (Some(old), Some(new), 'R' | 'C') = (&pair.old, &pair.new, pair.status()) {
+            unstaged.retain(|(_, path)| *path != old.path);
+            untracked.retain(|path| *path != new.path);
+            unstaged.push(describe_pair(&pair));
         }
     }
+    unstaged.sort_by(|a, b| a.1.cmp(&b.1));
 
-    if modified.is_empty() && untracked.is_empty() && deleted.is_empty() {
-        println!("Nothing to commit, working directory clean.");
+    Changes {
+        staged,
+        unstaged,
+        untracked,
     }
 }
 
-/// Recursively builds a map from paths to blob SHAs
-fn build_tree_map(tree_sha: &str, base: PathBuf, map: &mut HashMap<PathBuf, String>) {
-    let obj = Object::read(tree_sha).expect("Failed to read tree object");
-    let tree = match obj {
-        Object::Tree(tree) => tree,
-        _ => panic!("Not a tree object"),
-    };
-
-    for entry in tree.entries {
-        let full_path = base.join(entry.name);
-        match entry.mode.as_str() {
-            "100644" | "100755" => {
-                map.insert(full_path, entry.sha);
-            }
-            "40000" => {
-                build_tree_map(&entry.sha, full_path, map);
-            }
-            _ => {}
-        }
+/// A pair as a (status letter, path) entry, "old -> new" for renames and copies
+fn describe_pair(pair: &FilePair) -> (char, String) {
+    match (pair.status(), &pair.old) {
+        (status @ ('R' | 'C'), Some(old)) => (status, format!("{} -> {}", old.path, pair.path())),
+        (status, _) => (status, pair.path().to_string()),
     }
 }
 
-/// Recursively walks the working directory and returns file paths
-fn walk_working_dir(root: &str) -> Vec<PathBuf> {
-    let mut files = Vec::new();
-    let entries = fs::read_dir(root).expect("Failed to read working directory");
-
-    for entry in entries {
-        let entry = entry.expect("Failed to read dir entry");
-        let path = entry.path();
-
-        // Skip .hit directory
-        if path.strip_prefix(".").unwrap_or(&path).starts_with(".hit") {
-            continue;
-        }
-
-        if path.is_dir() {
-            files.extend(walk_working_dir(path.to_str().unwrap()));
-        } else {
-            files.push(path);
-        }
+/// The word git uses for a status letter ("modified", "new file", ...)
+pub fn describe(status: char) -> &'static str {
+    match status {
+        'A' => "new file",
+        'C' => "copied",
+        'D' => "deleted",
+        'R' => "renamed",
+        _ => "modified",
     }
-
-    files
 }
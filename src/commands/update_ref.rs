@@ -0,0 +1,97 @@
+// update-ref: moving sticky notes around, carefully
+
+use crate::utils::fatal::fatal;
+use crate::utils::hash_object::resolve_head;
+use crate::utils::objects::Object;
+use crate::utils::refs::{self, Head, NULL_SHA};
+
+/// Options for `hit update-ref`
+#[derive(Default)]
+pub struct UpdateRefOptions {
+    pub refname: String,
+    /// The new value; None with `-d`
+    pub new: Option<String>,
+    /// Only update if the ref currently has this value ("" or the null SHA: if it doesn't exist)
+    pub old: Option<String>,
+    /// Reflog message (`-m`)
+    pub message: String,
+    /// Update HEAD itself rather than the branch it points at
+    pub no_deref: bool,
+}
+
+/// Point a ref at a new object, or delete it, optionally checking its old value first
+pub fn update_ref(options: &UpdateRefOptions) {
+    let refname = target_ref(&options.refname, options.no_deref);
+    let current = if refname == "HEAD" {
+        resolve_head()
+    } else {
+        refs::read_ref(&refname)
+    };
+
+    if let Some(old) = &options.old {
+        let expected = match old.as_str() {
+            "" | NULL_SHA => None,
+            rev => Some(resolve(rev)),
+        };
+        if current != expected {
+            let what = match (&current, &expected) {
+                (Some(_), None) => "already exists".to_string(),
+                (Some(sha), Some(_)) => format!("is at {} but expected {}", sha, old),
+                (None, _) => format!("is missing but expected {}", old),
+            };
+            fatal(&format!(
+                "cannot lock ref '{}': reference {}",
+                options.refname, what
+            ));
+        }
+    }
+
+    match &options.new {
+        Some(new) => {
+            let sha = resolve(new);
+            if refname == "HEAD" {
+                refs::set_head_detached(&sha, &options.message);
+            } else if let Err(e) = refs::update_ref(&refname, &sha, &options.message) {
+                fatal(&e);
+            }
+        }
+        None => {
+            if current.is_none() {
+                fatal(&format!(
+                    "cannot lock ref '{}': unable to resolve reference '{}'",
+                    options.refname, refname
+                ));
+            }
+            if refname == "HEAD" {
+                fatal("refusing to delete a detached HEAD");
+            }
+            if let Err(e) = refs::delete_ref(&refname) {
+                fatal(&e);
+            }
+        }
+    }
+}
+
+/// The ref file to change: HEAD's branch unless `no_deref`, refs/... names as
+/// given, and short names only when they already exist
+fn target_ref(name: &str, no_deref: bool) -> String {
+    if name == "HEAD" {
+        return match refs::read_head() {
+            Some(Head::Branch(branch)) if !no_deref => branch,
+            _ => "HEAD".to_string(),
+        };
+    }
+    if name.starts_with("refs/") && !name.ends_with('/') && !name.contains("..") {
+        return name.to_string();
+    }
+    refs::dwim_ref(name)
+        .unwrap_or_else(|| fatal(&format!("refusing to update ref with bad name '{}'", name)))
+}
+
+/// Resolve a revision to an existing object
+fn resolve(rev: &str) -> String {
+    refs::resolve_revision(rev)
+        .ok()
+        .filter(|sha| Object::read(sha).is_ok())
+        .unwrap_or_else(|| fatal(&format!("{}: not a valid SHA1", rev)))
+}
@@ -0,0 +1,69 @@
+// verify: trust, but check the paperwork
+
+use crate::utils::fatal::fatal;
+use crate::utils::objects::{Commit, Object, Tag};
+use crate::utils::refs;
+use crate::utils::signature;
+
+/// Check the signatures on commits; exits non-zero unless all of them are good
+///
+/// `verbose` prints each commit's contents first; `raw` prints gpg's status
+/// lines instead of its human-readable output.
+pub fn verify_commit(revs: &[String], verbose: bool, raw: bool) {
+    let mut all_good = true;
+    for rev in revs {
+        let sha = refs::resolve_revision(rev).unwrap_or_else(|e| fatal(&e));
+        let (sha, commit) = refs::peel_to_commit(&sha).unwrap_or_else(|e| fatal(&e));
+        let payload = Commit::signed_payload(&read_raw(rev, &sha));
+        all_good &= check(rev, &payload, commit.gpgsig.as_deref(), verbose, raw);
+    }
+    if !all_good {
+        std::process::exit(1);
+    }
+}
+
+/// Check the signatures on annotated tags; exits non-zero unless all of them are good
+pub fn verify_tag(names: &[String], verbose: bool, raw: bool) {
+    let mut all_good = true;
+    for name in names {
+        let sha = refs::resolve_revision(name).unwrap_or_else(|e| fatal(&e));
+        let tag = match Object::read(&sha) {
+            Ok(Object::Tag(tag)) => tag,
+            Ok(_) => fatal(&format!("{}: cannot verify a non-tag object", name)),
+            Err(e) => fatal(&format!("cannot read {}: {}", name, e)),
+        };
+        let payload = Tag::signed_payload(&read_raw(name, &sha));
+        all_good &= check(name, &payload, tag.signature.as_deref(), verbose, raw);
+    }
+    if !all_good {
+        std::process::exit(1);
+    }
+}
+
+/// The object's bytes as stored, since its signature covers exactly those
+fn read_raw(name: &str, sha: &str) -> Vec<u8> {
+    match Object::read_raw(sha) {
+        Ok((_, content)) => content,
+        Err(e) => fatal(&format!("cannot read {}: {}", name, e)),
+    }
+}
+
+fn check(name: &str, payload: &[u8], signature: Option<&str>, verbose: bool, raw: bool) -> bool {
+    if verbose {
+        print!("{}", String::from_utf8_lossy(payload));
+    }
+    let Some(signature) = signature else {
+        eprintln!("error: {}: no signature found", name);
+        return false;
+    };
+    match signature::verify(payload, signature) {
+        Ok(result) => {
+            eprint!("{}", if raw { &result.raw } else { &result.output });
+            result.good
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            false
+        }
+    }
+}
@@ -0,0 +1,248 @@
+// worktrees: because one copy of your mess wasn't enough
+
+use crate::commands::branch::create_branch_at;
+use crate::utils::fatal::fatal;
+use crate::utils::hooks;
+use crate::utils::index::Index;
+use crate::utils::refs;
+use crate::utils::repo;
+use crate::utils::workdir;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A worktree known to the repository
+struct Worktree {
+    /// Working directory root
+    path: PathBuf,
+    /// Admin directory (`.hit` for the main worktree, `.hit/worktrees/<name>` otherwise)
+    admin: PathBuf,
+    /// Linked worktrees whose directory has vanished
+    prunable: bool,
+}
+
+/// Create a linked worktree at `path` and check out `target` there
+///
+/// `target` may be a branch or any commit (checked out detached). With
+/// `new_branch`, a branch is created at `target` (default HEAD). With neither,
+/// a branch named after the worktree directory is used, created if needed.
+pub fn add(path: &str, target: Option<&str>, new_branch: Option<&str>, detach: bool, force: bool) {
+    let worktree_path = Path::new(path);
+    if worktree_path.exists()
+        && fs::read_dir(worktree_path).map_or(true, |mut entries| entries.next().is_some())
+    {
+        fatal(&format!("'{}' already exists", path));
+    }
+
+    let dir_name = worktree_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_else(|| fatal(&format!("invalid worktree path '{}'", path)))
+        .to_string();
+
+    // work out which branch (if any) ends up checked out
+    let (branch, start) = match (new_branch, target) {
+        (Some(name), start) => (Some(name.to_string()), start.unwrap_or("HEAD").to_string()),
+        (None, Some(t)) if !detach && refs::branch_exists(t) => {
+            (Some(t.to_string()), t.to_string())
+        }
+        (None, Some(t)) => (None, t.to_string()),
+        (None, None) if detach => (None, "HEAD".to_string()),
+        (None, None) => (Some(dir_name.clone()), dir_name.clone()),
+    };
+    let create = match &branch {
+        Some(name) => new_branch.is_some() || !refs::branch_exists(name),
+        None => false,
+    };
+    let start = if create && target.is_none() {
+        "HEAD".to_string()
+    } else {
+        start
+    };
+
+    let (sha, tree) = refs::resolve_commit(&start).unwrap_or_else(|e| fatal(&e));
+
+    if let Some(name) = &branch {
+        if !create
+            && !force
+            && let Some(other) = checked_out_at(name, None)
+        {
+            fatal(&format!(
+                "'{}' is already checked out at '{}'",
+                name,
+                other.display()
+            ));
+        }
+        if create && let Err(e) = create_branch_at(name, &sha, &start, false) {
+            fatal(&e);
+        }
+    }
+
+    // admin directory shared with the main repository
+    let worktrees_dir = repo::path("worktrees");
+    let mut name = dir_name.clone();
+    let mut n = 1;
+    while worktrees_dir.join(&name).exists() {
+        name = format!("{}{}", dir_name, n);
+        n += 1;
+    }
+    let admin = absolute(&worktrees_dir.join(&name));
+
+    fs::create_dir_all(worktree_path).unwrap_or_else(|e| fatal(&e.to_string()));
+    fs::create_dir_all(&admin).unwrap_or_else(|e| fatal(&e.to_string()));
+    let worktree_path = absolute(worktree_path);
+    let dot_hit = worktree_path.join(".hit");
+
+    fs::write(admin.join("commondir"), "../..\n").unwrap_or_else(|e| fatal(&e.to_string()));
+    fs::write(admin.join("gitdir"), format!("{}\n", dot_hit.display()))
+        .unwrap_or_else(|e| fatal(&e.to_string()));
+    fs::write(&dot_hit, format!("hitdir: {}\n", admin.display()))
+        .unwrap_or_else(|e| fatal(&e.to_string()));
+
+    // everything below runs as if we were inside the new worktree
+    let original_dir = env::current_dir().unwrap_or_else(|e| fatal(&e.to_string()));
+    env::set_current_dir(&worktree_path).unwrap_or_else(|e| fatal(&e.to_string()));
+
+    fs::write(repo::path("HEAD"), format!("{}\n", sha)).unwrap_or_else(|e| fatal(&e.to_string()));
+    if let Err(e) = workdir::checkout_tree(&tree, true) {
+        fatal(&e);
+    }
+    match &branch {
+        Some(name) => {
+            refs::set_head_symbolic(&refs::branch_ref(name), "worktree: checkout");
+            println!("Preparing worktree (checking out '{}')", name);
+        }
+        None => {
+            refs::set_head_detached(&sha, "worktree: checkout");
+            println!("Preparing worktree (detached HEAD {})", &sha[..7]);
+        }
+    }
+    let hook = hooks::post_checkout(None, Some(&sha), true);
+
+    let _ = env::set_current_dir(original_dir);
+    if hook.is_err() {
+        std::process::exit(1);
+    }
+}
+
+/// Print every worktree with its HEAD
+pub fn list() {
+    for worktree in all_worktrees() {
+        let head = fs::read_to_string(worktree.admin.join("HEAD")).unwrap_or_default();
+        let head = head.trim();
+        let description = match head.strip_prefix("ref: ") {
+            Some(refname) => {
+                let sha = refs::read_ref(refname)
+                    .map(|s| s[..7].to_string())
+                    .unwrap_or_else(|| "0000000".to_string());
+                let branch = refname.strip_prefix("refs/heads/").unwrap_or(refname);
+                format!("{} [{}]", sha, branch)
+            }
+            None => format!("{} (detached HEAD)", head.get(..7).unwrap_or(head)),
+        };
+        let prunable = if worktree.prunable { " prunable" } else { "" };
+        println!("{}  {}{}", worktree.path.display(), description, prunable);
+    }
+}
+
+/// Delete a linked worktree and its admin directory
+///
+/// Refuses if the worktree has local changes or untracked files, unless `force`.
+pub fn remove(path: &str, force: bool) {
+    let target = absolute(Path::new(path));
+    let worktree = all_worktrees()
+        .into_iter()
+        .skip(1)
+        .find(|w| w.path == target || w.admin.file_name() == Some(path.as_ref()))
+        .unwrap_or_else(|| fatal(&format!("'{}' is not a working tree", path)));
+
+    if !force && worktree.path.exists() {
+        let original_dir = env::current_dir().unwrap_or_else(|e| fatal(&e.to_string()));
+        env::set_current_dir(&worktree.path).unwrap_or_else(|e| fatal(&e.to_string()));
+        let index = Index::load();
+        let dirty = index.entries.iter().any(workdir::is_modified)
+            || workdir::list_files()
+                .iter()
+                .any(|file| index.get(file).is_none());
+        let _ = env::set_current_dir(original_dir);
+
+        if dirty {
+            fatal(&format!(
+                "'{}' contains modified or untracked files, use --force to delete it",
+                path
+            ));
+        }
+    }
+
+    if worktree.path.exists() {
+        fs::remove_dir_all(&worktree.path).unwrap_or_else(|e| fatal(&e.to_string()));
+    }
+    fs::remove_dir_all(&worktree.admin).unwrap_or_else(|e| fatal(&e.to_string()));
+}
+
+/// Forget linked worktrees whose directories no longer exist
+pub fn prune() {
+    for worktree in all_worktrees().into_iter().filter(|w| w.prunable) {
+        let name = worktree
+            .admin
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        println!(
+            "Removing worktrees/{}: gitdir file points to non-existent location",
+            name
+        );
+        let _ = fs::remove_dir_all(&worktree.admin);
+    }
+}
+
+/// The worktree (other than this one) where `branch` is checked out, if any
+pub fn checked_out_elsewhere(branch: &str) -> Option<PathBuf> {
+    checked_out_at(branch, Some(&absolute(&repo::hit_dir())))
+}
+
+/// The worktree where `branch` is checked out, ignoring the one administered by `skip`
+fn checked_out_at(branch: &str, skip: Option<&Path>) -> Option<PathBuf> {
+    let refname = refs::branch_ref(branch);
+    all_worktrees()
+        .into_iter()
+        .filter(|w| !w.prunable && Some(absolute(&w.admin).as_path()) != skip)
+        .find(|w| {
+            fs::read_to_string(w.admin.join("HEAD"))
+                .is_ok_and(|head| head.trim().strip_prefix("ref: ") == Some(refname.as_str()))
+        })
+        .map(|w| w.path)
+}
+
+/// The main worktree followed by every linked one
+fn all_worktrees() -> Vec<Worktree> {
+    let common = absolute(&repo::common_dir());
+    let mut worktrees = vec![Worktree {
+        path: common.parent().map(Path::to_path_buf).unwrap_or_default(),
+        admin: common.clone(),
+        prunable: false,
+    }];
+
+    let mut linked: Vec<_> = fs::read_dir(common.join("worktrees"))
+        .map(|entries| entries.filter_map(Result::ok).map(|e| e.path()).collect())
+        .unwrap_or_default();
+    linked.sort();
+
+    for admin in linked {
+        let gitdir = fs::read_to_string(admin.join("gitdir")).unwrap_or_default();
+        let dot_hit = PathBuf::from(gitdir.trim());
+        worktrees.push(Worktree {
+            path: dot_hit.parent().map(Path::to_path_buf).unwrap_or_default(),
+            prunable: !dot_hit.exists(),
+            admin,
+        });
+    }
+
+    worktrees
+}
+
+/// Make a path absolute without requiring it to exist
+fn absolute(path: &Path) -> PathBuf {
+    path.canonicalize()
+        .unwrap_or_else(|_| std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()))
+}
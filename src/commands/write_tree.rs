@@ -2,13 +2,14 @@
 
 use std::fs;
 use std::io::Write;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 
 use flate2::Compression;
 use flate2::write::ZlibEncoder;
 use sha1::{Digest, Sha1};
 
 use crate::utils::hash_object;
+use crate::utils::repo;
 
 /// Entry point: write the root tree from the current directory
 pub fn write_tree() {
@@ -89,12 +90,11 @@ pub fn write_directory(path: &str, ignore: Vec<String>) -> String {
     let hash_hex = format!("{:x}", hash);
 
     // Store in .git/objects/xx/yyyy...
-    let object_dir = format!(".hit/objects/{}", &hash_hex[..2]);
-    let object_file = hash_hex[2..].to_string();
-    let object_path = Path::new(&object_dir).join(&object_file);
+    let object_path = repo::object_path(&hash_hex);
+    let object_dir = object_path.parent().unwrap();
 
     if !object_path.exists() {
-        fs::create_dir_all(&object_dir).expect("Failed to create object directory");
+        fs::create_dir_all(object_dir).expect("Failed to create object directory");
 
         let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
         encoder
@@ -6,21 +6,54 @@ mod commands {
     pub mod checkout;
     pub mod commit;
     pub mod commit_tree;
+    pub mod diff;
     pub mod init;
+    pub mod interpret_trailers;
     pub mod log;
     pub mod reset;
+    pub mod restore;
+    pub mod rev_list;
+    pub mod rev_parse;
+    pub mod show;
+    pub mod stash;
     pub mod status;
+    pub mod update_ref;
+    pub mod verify;
+    pub mod worktree;
     pub mod write_tree;
 }
 
 // the marginally smaller list of death
 pub mod utils {
+    pub mod color;
     pub mod config;
+    pub mod date;
+    pub mod diff;
+    pub mod diffstat;
+    pub mod editor;
+    pub mod fatal;
+    pub mod graph;
     pub mod hash_object;
+    pub mod hooks;
+    pub mod ident;
     pub mod index;
+    pub mod message;
     pub mod objects;
+    pub mod pathspec;
+    pub mod pretty;
+    pub mod refs;
+    pub mod regex;
+    pub mod renames;
+    pub mod repo;
+    pub mod revwalk;
+    pub mod signature;
+    pub mod trailers;
+    pub mod word_diff;
+    pub mod workdir;
 }
 
+use utils::fatal::fatal;
+
 fn main() {
     let args = std::env::args().collect::<Vec<_>>();
 
@@ -58,25 +91,280 @@ fn main() {
             commands::write_tree::write_tree();
         }
         "commit-tree" => {
-            if args.len() != 3 && args.len() != 5 {
-                eprintln!("Usage: {} commit-tree <hash> [-m <message>]", args[0]);
+            use commands::commit_tree::MessagePart;
+            let usage = || -> ! {
+                eprintln!(
+                    "Usage: {} commit-tree <tree> [-p <parent>]... [-S[<keyid>]] [(-m <message>)...] [(-F <file>)...]",
+                    args[0]
+                );
                 std::process::exit(1);
+            };
+            let mut options = commands::commit_tree::CommitTreeOptions::default();
+            let mut tree = None;
+            let mut iter = args.iter().skip(2);
+            while let Some(arg) = iter.next() {
+                match arg.as_str() {
+                    "-p" => options
+                        .parents
+                        .push(iter.next().unwrap_or_else(|| usage()).clone()),
+                    "-m" => options.message.push(MessagePart::Text(
+                        iter.next().unwrap_or_else(|| usage()).clone(),
+                    )),
+                    "-F" => options.message.push(MessagePart::File(
+                        iter.next().unwrap_or_else(|| usage()).clone(),
+                    )),
+                    "-S" | "--gpg-sign" => options.sign = Some(true),
+                    "--no-gpg-sign" => options.sign = Some(false),
+                    flag if flag.starts_with("--gpg-sign=") || flag.starts_with("-S") => {
+                        options.sign = Some(true);
+                        let key = flag
+                            .strip_prefix("--gpg-sign=")
+                            .or_else(|| flag.strip_prefix("-S"))
+                            .unwrap_or_default();
+                        options.signing_key = Some(key.to_string());
+                    }
+                    flag if flag.starts_with('-') => usage(),
+                    _ if tree.is_none() => tree = Some(arg.clone()),
+                    _ => usage(),
+                }
             }
-            let hash = &args[2];
-            let message = if args.len() > 3 && args[3] == "-m" {
-                Some(args[4].clone())
-            } else {
-                None
+            options.tree = tree.unwrap_or_else(|| usage());
+            commands::commit_tree::commit_tree(&options);
+        }
+        "update-ref" => {
+            let usage = || -> ! {
+                eprintln!(
+                    "Usage: {0} update-ref [-m <reason>] [--no-deref] <refname> <new-oid> [<old-oid>]\n       {0} update-ref [-m <reason>] [--no-deref] -d <refname> [<old-oid>]",
+                    args[0]
+                );
+                std::process::exit(1);
             };
-            commands::commit_tree::commit_tree(hash, message.as_deref().unwrap_or("commit!"));
+            let mut options = commands::update_ref::UpdateRefOptions::default();
+            let mut delete = false;
+            let mut rest = Vec::new();
+            let mut iter = args.iter().skip(2);
+            while let Some(arg) = iter.next() {
+                match arg.as_str() {
+                    "-m" => options.message = iter.next().unwrap_or_else(|| usage()).clone(),
+                    "-d" => delete = true,
+                    "--no-deref" => options.no_deref = true,
+                    flag if flag.starts_with('-') => usage(),
+                    _ => rest.push(arg.clone()),
+                }
+            }
+            let mut rest = rest.into_iter();
+            options.refname = rest.next().unwrap_or_else(|| usage());
+            if !delete {
+                options.new = Some(rest.next().unwrap_or_else(|| usage()));
+            }
+            options.old = rest.next();
+            if rest.next().is_some() {
+                usage();
+            }
+            commands::update_ref::update_ref(&options);
         }
         "checkout" => {
-            if args.len() != 3 {
-                eprintln!("Usage: {} checkout <hash>", args[0]);
+            let usage = || -> ! {
+                eprintln!(
+                    "Usage: {} checkout [-f] [-b|-B <branch> [<start>] | --orphan <branch> | <target>]\n       {} checkout [<rev>] -- <paths>...",
+                    args[0], args[0]
+                );
+                std::process::exit(1);
+            };
+            // -f can come anywhere before "--"; after it, everything is a path
+            let dashes = args.iter().position(|a| a == "--");
+            let options = &args[..dashes.unwrap_or(args.len())];
+            let force = options.iter().any(|a| a == "-f" || a == "--force");
+            let options: Vec<String> = options
+                .iter()
+                .filter(|a| *a != "-f" && *a != "--force")
+                .cloned()
+                .collect();
+            if let Some(pos) = dashes {
+                let paths = &args[pos + 1..];
+                if options.len() > 3 || paths.is_empty() {
+                    usage();
+                }
+                commands::restore::checkout_paths(options.get(2).map(String::as_str), paths);
+                return;
+            }
+            let args = options;
+            match args.get(2).map(String::as_str) {
+                Some(flag @ ("-b" | "-B")) => {
+                    if args.len() != 4 && args.len() != 5 {
+                        usage();
+                    }
+                    commands::checkout::switch_new_branch(
+                        &args[3],
+                        args.get(4).map(String::as_str),
+                        flag == "-B",
+                        force,
+                    );
+                }
+                Some("--orphan") => {
+                    if args.len() != 4 {
+                        usage();
+                    }
+                    commands::checkout::switch_orphan(&args[3]);
+                }
+                Some(target) if args.len() == 3 => commands::checkout::checkout(target, force),
+                _ => usage(),
+            }
+        }
+        "switch" => {
+            let usage = || -> ! {
+                eprintln!(
+                    "Usage: {} switch [-f] [-c|-C] <branch> [<start>] | --orphan <branch>",
+                    args[0]
+                );
                 std::process::exit(1);
+            };
+            let discard = |a: &String| a == "-f" || a == "--force" || a == "--discard-changes";
+            let force = args.iter().any(discard);
+            let args: Vec<String> = args.iter().filter(|a| !discard(a)).cloned().collect();
+            match args.get(2).map(String::as_str) {
+                Some(flag @ ("-c" | "-C" | "--create" | "--force-create")) => {
+                    if args.len() != 4 && args.len() != 5 {
+                        usage();
+                    }
+                    commands::checkout::switch_new_branch(
+                        &args[3],
+                        args.get(4).map(String::as_str),
+                        flag == "-C" || flag == "--force-create",
+                        force,
+                    );
+                }
+                Some("--orphan") => {
+                    if args.len() != 4 {
+                        usage();
+                    }
+                    commands::checkout::switch_orphan(&args[3]);
+                }
+                Some(branch) if args.len() == 3 => commands::checkout::switch(branch, force),
+                _ => usage(),
+            }
+        }
+        "restore" => {
+            let mut staged = false;
+            let mut worktree = false;
+            let mut source = None;
+            let mut paths = Vec::new();
+            let mut iter = args[2..].iter();
+            while let Some(arg) = iter.next() {
+                match arg.as_str() {
+                    "-S" | "--staged" => staged = true,
+                    "-W" | "--worktree" => worktree = true,
+                    "-SW" | "-WS" => {
+                        staged = true;
+                        worktree = true;
+                    }
+                    "-s" | "--source" => source = iter.next().cloned(),
+                    "--" => paths.extend(iter.by_ref().cloned()),
+                    _ if arg.starts_with("--source=") => {
+                        source = Some(arg["--source=".len()..].to_string())
+                    }
+                    _ => paths.push(arg.clone()),
+                }
+            }
+            if paths.is_empty() {
+                eprintln!(
+                    "Usage: {} restore [--staged] [--worktree] [--source=<rev>] <pathspec>...",
+                    args[0]
+                );
+                std::process::exit(1);
+            }
+            commands::restore::restore(&paths, source.as_deref(), staged, worktree);
+        }
+        "stash" => {
+            let usage = || -> ! {
+                eprintln!(
+                    "Usage: {} stash [push] [-m <message>] [-u] | save [-u] [<message>] | list | show [<stash>] | apply [--index] [<stash>] | pop [--index] [<stash>] | drop [<stash>] | clear",
+                    args[0]
+                );
+                std::process::exit(1);
+            };
+            // like git, no subcommand or a leading flag means push
+            let (subcommand, rest) = match args.get(2) {
+                Some(arg) if !arg.starts_with('-') => (arg.as_str(), &args[3..]),
+                _ => ("push", &args[2..]),
+            };
+            let mut message = None;
+            let mut include_untracked = false;
+            let mut restore_index = false;
+            let mut words = Vec::new();
+            let mut iter = rest.iter();
+            while let Some(arg) = iter.next() {
+                match arg.as_str() {
+                    "-m" | "--message" => {
+                        message = Some(iter.next().cloned().unwrap_or_else(|| usage()))
+                    }
+                    "-u" | "--include-untracked" => include_untracked = true,
+                    "--index" => restore_index = true,
+                    _ if !arg.starts_with('-') => words.push(arg.clone()),
+                    _ => usage(),
+                }
+            }
+            if subcommand == "save" {
+                // everything after save is the message
+                let message = (!words.is_empty()).then(|| words.join(" "));
+                commands::stash::push(message.as_deref(), include_untracked);
+                return;
+            }
+            if words.len() > 1 {
+                usage();
+            }
+            let stash = words.first().map(String::as_str);
+            match subcommand {
+                "push" if stash.is_none() => {
+                    commands::stash::push(message.as_deref(), include_untracked)
+                }
+                "list" => commands::stash::list(),
+                "show" => commands::stash::show(stash),
+                "apply" => commands::stash::apply(stash, restore_index),
+                "pop" => commands::stash::pop(stash, restore_index),
+                "drop" => commands::stash::drop_stash(stash),
+                "clear" => commands::stash::clear(),
+                _ => usage(),
+            }
+        }
+        "worktree" => {
+            let usage = || -> ! {
+                eprintln!(
+                    "Usage: {} worktree add [-b <branch>] [--detach] [-f] <path> [<commit-ish>] | list | remove [-f] <path> | prune",
+                    args[0]
+                );
+                std::process::exit(1);
+            };
+            let mut new_branch = None;
+            let mut detach = false;
+            let mut force = false;
+            let mut positional = Vec::new();
+            let mut iter = args.iter().skip(3);
+            while let Some(arg) = iter.next() {
+                match arg.as_str() {
+                    "-b" => new_branch = Some(iter.next().cloned().unwrap_or_else(|| usage())),
+                    "-d" | "--detach" => detach = true,
+                    "-f" | "--force" => force = true,
+                    _ if arg.starts_with('-') => usage(),
+                    _ => positional.push(arg.as_str()),
+                }
+            }
+            match (args.get(2).map(String::as_str), positional.as_slice()) {
+                (Some("add"), [path]) => {
+                    commands::worktree::add(path, None, new_branch.as_deref(), detach, force)
+                }
+                (Some("add"), [path, target]) => commands::worktree::add(
+                    path,
+                    Some(target),
+                    new_branch.as_deref(),
+                    detach,
+                    force,
+                ),
+                (Some("list"), []) => commands::worktree::list(),
+                (Some("remove"), [path]) => commands::worktree::remove(path, force),
+                (Some("prune"), []) => commands::worktree::prune(),
+                _ => usage(),
             }
-            let hash = &args[2];
-            commands::checkout::checkout(hash);
         }
         "branch" => {
             let branch_name = if args.len() > 2 {
@@ -90,11 +378,14 @@ fn main() {
             }
         }
         "status" => {
-            if args.len() != 2 {
-                eprintln!("Usage: {} status", args[0]);
-                std::process::exit(1);
+            let mut options = commands::status::StatusOptions::default();
+            for arg in &args[2..] {
+                if !options.renames.parse_flag(arg) {
+                    eprintln!("Usage: {} status [-M[<n>] | --no-renames]", args[0]);
+                    std::process::exit(1);
+                }
             }
-            commands::status::status();
+            commands::status::status(&options);
         }
         "add" => {
             if args.len() < 3 {
@@ -122,41 +413,510 @@ fn main() {
             commands::add::rm(&args[2 + cached as usize], cached);
         }
         "commit" => {
-            // if no message, return error
-            if args.len() < 3 {
-                eprintln!("Usage: {} commit -m <message>", args[0]);
+            let usage = || -> ! {
+                eprintln!(
+                    "Usage: {} commit [-a | -i | -o] [-n] [-s] [--allow-empty] [--allow-empty-message] [-S[<key>]] [--trailer <token>[(=|:)<value>]]... [-m <message>]... [-F <file>] [--amend [--no-edit] [--reset-author]] [--author=<author>] [--date=<date>] [--] [<paths>...]",
+                    args[0]
+                );
                 std::process::exit(1);
+            };
+            let mut options = commands::commit::CommitOptions::default();
+            let mut iter = args.iter().skip(2);
+            while let Some(arg) = iter.next() {
+                match arg.as_str() {
+                    "-m" | "--message" => {
+                        let message = iter.next().unwrap_or_else(|| usage());
+                        // several -m options become separate paragraphs
+                        options.message = Some(match options.message.take() {
+                            Some(previous) => format!("{}\n\n{}", previous, message),
+                            None => message.clone(),
+                        });
+                    }
+                    "-F" | "--file" => {
+                        options.message_file = Some(iter.next().unwrap_or_else(|| usage()).clone())
+                    }
+                    "--amend" => options.amend = true,
+                    "--no-edit" => options.no_edit = true,
+                    "--reset-author" => options.reset_author = true,
+                    "--author" => {
+                        options.author = Some(iter.next().unwrap_or_else(|| usage()).clone())
+                    }
+                    "--date" => options.date = Some(iter.next().unwrap_or_else(|| usage()).clone()),
+                    flag if flag.starts_with("--author=") => {
+                        options.author = Some(flag["--author=".len()..].to_string())
+                    }
+                    flag if flag.starts_with("--date=") => {
+                        options.date = Some(flag["--date=".len()..].to_string())
+                    }
+                    "-a" | "--all" => options.all = true,
+                    "-n" | "--no-verify" => options.no_verify = true,
+                    "-S" | "--gpg-sign" => options.sign = Some(true),
+                    "--no-gpg-sign" => options.sign = Some(false),
+                    flag if flag.starts_with("--gpg-sign=") || flag.starts_with("-S") => {
+                        options.sign = Some(true);
+                        let key = flag
+                            .strip_prefix("--gpg-sign=")
+                            .or_else(|| flag.strip_prefix("-S"))
+                            .unwrap_or_default();
+                        options.signing_key = Some(key.to_string());
+                    }
+                    "--allow-empty" => options.allow_empty = true,
+                    "--allow-empty-message" => options.allow_empty_message = true,
+                    "-s" | "--signoff" => options.signoff = true,
+                    "--no-signoff" => options.signoff = false,
+                    "--trailer" => options
+                        .trailers
+                        .push(iter.next().unwrap_or_else(|| usage()).clone()),
+                    flag if flag.starts_with("--trailer=") => options
+                        .trailers
+                        .push(flag["--trailer=".len()..].to_string()),
+                    "-i" | "--include" => options.include = true,
+                    "-o" | "--only" => options.only = true,
+                    "--" => options.paths.extend(iter.by_ref().cloned()),
+                    flag if flag.starts_with('-') => usage(),
+                    path => options.paths.push(path.to_string()),
+                }
             }
-            let message = if args[2] == "-m" {
-                if args.len() < 4 {
-                    eprintln!("Usage: {} commit -m <message>", args[0]);
+            if options.message.is_some() && options.message_file.is_some() {
+                fatal("options '-m' and '-F' cannot be used together");
+            }
+            if [options.all, options.include, options.only]
+                .iter()
+                .filter(|&&set| set)
+                .count()
+                > 1
+            {
+                fatal("only one of --include/--only/--all can be used");
+            }
+            if options.all && !options.paths.is_empty() {
+                fatal(&format!(
+                    "paths '{}' with -a does not make sense",
+                    options.paths.join(" ")
+                ));
+            }
+            if options.paths.is_empty() && (options.include || (options.only && !options.amend)) {
+                fatal("no paths with --include/--only does not make sense");
+            }
+            commands::commit::commit(&options);
+        }
+        "reset" => {
+            let mut mode = None;
+            let mut rest = Vec::new();
+            let mut separator = None;
+            for arg in &args[2..] {
+                match arg.as_str() {
+                    "--soft" => mode = Some(commands::reset::ResetMode::Soft),
+                    "--mixed" => mode = Some(commands::reset::ResetMode::Mixed),
+                    "--hard" => mode = Some(commands::reset::ResetMode::Hard),
+                    "--" if separator.is_none() => separator = Some(rest.len()),
+                    _ => rest.push(arg.clone()),
+                }
+            }
+
+            // "reset <rev> -- <paths>", or guess whether the first argument is a revision
+            let (rev, paths) = match separator {
+                Some(pos) if pos <= 1 => (
+                    rest.first().filter(|_| pos == 1).cloned(),
+                    rest[pos..].to_vec(),
+                ),
+                Some(_) => {
+                    eprintln!("Usage: {} reset [<rev>] -- <paths>...", args[0]);
                     std::process::exit(1);
                 }
-                Some(args[3].clone())
-            } else {
-                None
+                None => match rest.first() {
+                    Some(first)
+                        if mode.is_some()
+                            || (!std::path::Path::new(first).exists()
+                                && utils::refs::resolve_commit(first).is_ok()) =>
+                    {
+                        (Some(first.clone()), rest[1..].to_vec())
+                    }
+                    _ => (None, rest),
+                },
             };
-            if let Some(msg) = message {
-                commands::commit::commit(&msg);
+            let rev = rev.as_deref().unwrap_or("HEAD");
+
+            if paths.is_empty() {
+                commands::reset::reset_commit(
+                    rev,
+                    mode.unwrap_or(commands::reset::ResetMode::Mixed),
+                );
+            } else if mode.is_some() {
+                fatal("cannot do a --soft/--mixed/--hard reset with paths");
             } else {
-                eprintln!("Usage: {} commit -m <message>", args[0]);
-                std::process::exit(1);
+                commands::reset::reset_paths(rev, &paths);
             }
         }
-        "reset" => {
-            if args.len() < 3 {
-                eprintln!("Usage: {} reset <file>", args[0]);
+        "diff" => {
+            let usage = || -> ! {
+                eprintln!(
+                    "Usage: {} diff [--cached] [-U<n>] [--diff-algorithm=<algorithm>] [-M[<n>]] [-C[<n>]] [--stat | --numstat | --shortstat | --dirstat | --name-status | --name-only] [--word-diff[=<mode>]] [--word-diff-regex=<regex>] [--color[=<when>]] [<rev> [<rev>]] [--] [<path>...]",
+                    args[0]
+                );
                 std::process::exit(1);
+            };
+            let algorithm = |name: &str| {
+                utils::diff::Algorithm::parse(name).unwrap_or_else(|| {
+                    eprintln!(
+                        "error: option diff-algorithm accepts \"myers\", \"minimal\", \"patience\" and \"histogram\""
+                    );
+                    std::process::exit(1);
+                })
+            };
+            let mut options = commands::diff::DiffOptions::default();
+            let mut iter = args.iter().skip(2);
+            while let Some(arg) = iter.next() {
+                match arg.as_str() {
+                    "--cached" | "--staged" => options.cached = true,
+                    "-U" | "--unified" => {
+                        let n = iter.next().unwrap_or_else(|| usage());
+                        options.patch.context = n.parse().unwrap_or_else(|_| usage());
+                    }
+                    flag if flag.starts_with("--unified=") || flag.starts_with("-U") => {
+                        let n = flag
+                            .strip_prefix("--unified=")
+                            .or_else(|| flag.strip_prefix("-U"))
+                            .unwrap_or_default();
+                        options.patch.context = n.parse().unwrap_or_else(|_| usage());
+                    }
+                    "--minimal" | "--patience" | "--histogram" => {
+                        options.patch.algorithm = algorithm(&arg[2..])
+                    }
+                    "--diff-algorithm" => {
+                        options.patch.algorithm = algorithm(iter.next().unwrap_or_else(|| usage()))
+                    }
+                    flag if flag.starts_with("--diff-algorithm=") => {
+                        options.patch.algorithm = algorithm(&flag["--diff-algorithm=".len()..])
+                    }
+                    flag if options.output.parse_flag(flag) => {}
+                    flag if options.patch.parse_flag(flag) => {}
+                    flag if options.renames.parse_flag(flag) => {}
+                    "--" => options.paths.extend(iter.by_ref().cloned()),
+                    flag if flag.starts_with('-') => usage(),
+                    // like git, anything before "--" is a revision if it resolves to one
+                    _ if options.paths.is_empty()
+                        && utils::refs::resolve_commit(arg.split("..").next().unwrap_or(arg))
+                            .is_ok() =>
+                    {
+                        options.revs.push(arg.clone())
+                    }
+                    _ if std::path::Path::new(arg).exists() => options.paths.push(arg.clone()),
+                    _ => {
+                        fatal(&format!(
+                            "ambiguous argument '{}': unknown revision or path not in the working tree.",
+                            arg
+                        ));
+                    }
+                }
             }
-            let path = &args[2];
-            commands::reset::reset(path);
+            commands::diff::diff(&options);
         }
         "log" => {
-            if args.len() != 2 {
-                eprintln!("Usage: {} log", args[0]);
+            let usage = || -> ! {
+                eprintln!(
+                    "Usage: {} log [-n <n>] [--oneline | --format=<format>] [--graph] [--all] [--topo-order] [--reverse] [--first-parent] [--ancestry-path] [--decorate[=short|full|no] | --no-decorate] [--author=<pattern>] [--grep=<pattern>] [-E] [-i] [--since=<date>] [--until=<date>] [--show-signature] [-p] [--stat | --numstat | --shortstat | --dirstat | --name-status | --name-only] [--word-diff[=<mode>]] [--color[=<when>]] [-M[<n>]] [--follow] [<rev> | <a>..<b>] [--] [<path>...]",
+                    args[0]
+                );
+                std::process::exit(1);
+            };
+            let count = |n: &str| n.parse::<usize>().unwrap_or_else(|_| usage());
+            let date =
+                |value: &str| Some(utils::date::parse_approx(value).unwrap_or_else(|e| fatal(&e)));
+            let mut options = commands::log::LogOptions::default();
+            let mut iter = args.iter().skip(2);
+            while let Some(arg) = iter.next() {
+                // "--author=<pattern>" or "--author <pattern>"
+                let (flag, inline) = match arg.split_once('=') {
+                    Some((flag, value)) if flag.starts_with("--") => (flag, Some(value)),
+                    _ => (arg.as_str(), None),
+                };
+                let mut value = || {
+                    inline
+                        .map(str::to_string)
+                        .or_else(|| iter.next().cloned())
+                        .unwrap_or_else(|| usage())
+                };
+                match flag {
+                    "-n" | "--max-count" => options.walk.max_count = Some(count(&value())),
+                    "--author" => options.authors.push(value()),
+                    "--grep" => options.greps.push(value()),
+                    "--since" | "--after" => options.since = date(&value()),
+                    "--until" | "--before" => options.until = date(&value()),
+                    "--format" | "--pretty" if inline.is_some() => {
+                        let spec = value();
+                        options.format = utils::pretty::Format::parse(&spec)
+                            .unwrap_or_else(|| fatal(&format!("invalid --pretty format: {}", spec)))
+                    }
+                    "--pretty" => options.format = utils::pretty::Format::Medium,
+                    "--oneline" => options.format = utils::pretty::Format::Oneline,
+                    "--graph" => options.graph = true,
+                    "--all" => options.all = true,
+                    "--topo-order" => options.walk.topo_order = true,
+                    "--reverse" => options.walk.reverse = true,
+                    "--first-parent" => options.walk.first_parent = true,
+                    "--ancestry-path" => options.walk.ancestry_path = true,
+                    "--decorate" => {
+                        options.decorate = match inline {
+                            Some(value) => {
+                                utils::pretty::Decorate::parse(value).unwrap_or_else(|| {
+                                    fatal(&format!("invalid --decorate option: {}", value))
+                                })
+                            }
+                            None => utils::pretty::Decorate::Short,
+                        }
+                    }
+                    "--no-decorate" => options.decorate = utils::pretty::Decorate::No,
+                    "-E" | "--extended-regexp" => options.extended_regexp = true,
+                    "-i" | "--regexp-ignore-case" => options.ignore_case = true,
+                    "--show-signature" => options.show_signature = true,
+                    "--no-show-signature" => options.show_signature = false,
+                    "--follow" => options.follow = true,
+                    _ if arg.starts_with("-n") && arg.len() > 2 => {
+                        options.walk.max_count = Some(count(&arg[2..]))
+                    }
+                    _ if arg.starts_with('-')
+                        && arg.len() > 1
+                        && arg[1..].bytes().all(|b| b.is_ascii_digit()) =>
+                    {
+                        options.walk.max_count = Some(count(&arg[1..]))
+                    }
+                    _ if options.output.parse_flag(arg) => {}
+                    _ if options.patch.parse_flag(arg) => {}
+                    _ if options.renames.parse_flag(arg) => {}
+                    "--" => options.paths.extend(iter.by_ref().cloned()),
+                    _ if arg.starts_with('-') => usage(),
+                    // like git, anything before "--" is a revision if it resolves to one
+                    _ if options.paths.is_empty()
+                        && arg.trim_start_matches('^').split("..").all(|rev| {
+                            rev.is_empty() || utils::refs::resolve_commit(rev).is_ok()
+                        }) =>
+                    {
+                        options.revs.push(arg.clone())
+                    }
+                    _ if std::path::Path::new(arg).exists() => options.paths.push(arg.clone()),
+                    _ => {
+                        fatal(&format!(
+                            "ambiguous argument '{}': unknown revision or path not in the working tree.",
+                            arg
+                        ));
+                    }
+                }
+            }
+            commands::log::log(&options);
+        }
+        "rev-list" => {
+            let usage = || -> ! {
+                eprintln!(
+                    "Usage: {} rev-list [--count] [--objects] [-n <n>] [--all] [--topo-order] [--reverse] [--first-parent] [--ancestry-path] <rev>... (or ^<rev>, <a>..<b>)",
+                    args[0]
+                );
+                std::process::exit(1);
+            };
+            let count = |n: &str| n.parse::<usize>().unwrap_or_else(|_| usage());
+            let mut options = commands::rev_list::RevListOptions::default();
+            let mut iter = args.iter().skip(2);
+            while let Some(arg) = iter.next() {
+                match arg.as_str() {
+                    "--count" => options.count = true,
+                    "--objects" => options.objects = true,
+                    "--all" => options.all = true,
+                    "--topo-order" => options.walk.topo_order = true,
+                    "--reverse" => options.walk.reverse = true,
+                    "--first-parent" => options.walk.first_parent = true,
+                    "--ancestry-path" => options.walk.ancestry_path = true,
+                    "-n" | "--max-count" => {
+                        options.walk.max_count = Some(count(iter.next().unwrap_or_else(|| usage())))
+                    }
+                    _ if arg.starts_with("--max-count=") => {
+                        options.walk.max_count = Some(count(&arg["--max-count=".len()..]))
+                    }
+                    _ if arg.starts_with("-n") && arg.len() > 2 => {
+                        options.walk.max_count = Some(count(&arg[2..]))
+                    }
+                    _ if arg.starts_with('-')
+                        && arg.len() > 1
+                        && arg[1..].bytes().all(|b| b.is_ascii_digit()) =>
+                    {
+                        options.walk.max_count = Some(count(&arg[1..]))
+                    }
+                    _ if arg.starts_with('-') => usage(),
+                    _ => options.revs.push(arg.clone()),
+                }
+            }
+            if options.revs.is_empty() && !options.all {
+                usage();
+            }
+            commands::rev_list::rev_list(&options);
+        }
+        "rev-parse" => {
+            use commands::rev_parse::Query;
+            let usage = || -> ! {
+                eprintln!(
+                    "Usage: {} rev-parse [--verify [-q]] [--short[=<n>] | --abbrev-ref] [--show-toplevel] [--hit-dir] <rev>...",
+                    args[0]
+                );
                 std::process::exit(1);
+            };
+            let mut options = commands::rev_parse::RevParseOptions::default();
+            for arg in &args[2..] {
+                match arg.as_str() {
+                    "--verify" => options.verify = true,
+                    "-q" | "--quiet" => options.quiet = true,
+                    // like git, --short implies --verify
+                    "--short" => {
+                        options.verify = true;
+                        options.short = Some(7);
+                    }
+                    "--abbrev-ref" => options.abbrev_ref = true,
+                    "--show-toplevel" => options.queries.push(Query::Toplevel),
+                    "--hit-dir" => options.queries.push(Query::HitDir),
+                    _ if arg.starts_with("--short=") => {
+                        let len = arg["--short=".len()..].parse().unwrap_or_else(|_| usage());
+                        options.verify = true;
+                        options.short = Some(len);
+                    }
+                    _ if arg.starts_with('-') => usage(),
+                    _ => options.queries.push(Query::Rev(arg.clone())),
+                }
+            }
+            commands::rev_parse::rev_parse(&options);
+        }
+        "show" => {
+            let usage = || -> ! {
+                eprintln!(
+                    "Usage: {} show [--format=<format> | --oneline] [-s] [--stat | --name-only | --name-status] [--word-diff[=<mode>]] [--color[=<when>]] [<object>...]",
+                    args[0]
+                );
+                std::process::exit(1);
+            };
+            let format = |spec: &str| {
+                utils::pretty::Format::parse(spec)
+                    .unwrap_or_else(|| fatal(&format!("invalid --pretty format: {}", spec)))
+            };
+            let mut options = commands::show::ShowOptions::default();
+            for arg in &args[2..] {
+                match arg.as_str() {
+                    "--oneline" => options.format = utils::pretty::Format::Oneline,
+                    "--pretty" => options.format = utils::pretty::Format::Medium,
+                    flag if flag.starts_with("--format=") || flag.starts_with("--pretty=") => {
+                        options.format = format(flag.split_once('=').unwrap_or_default().1)
+                    }
+                    "-s" | "--no-patch" => options.no_patch = true,
+                    "--show-signature" => options.show_signature = true,
+                    "--no-show-signature" => options.show_signature = false,
+                    flag if options.output.parse_flag(flag) => {}
+                    flag if options.patch.parse_flag(flag) => {}
+                    flag if options.renames.parse_flag(flag) => {}
+                    flag if flag.starts_with('-') => usage(),
+                    object => options.objects.push(object.to_string()),
+                }
+            }
+            commands::show::show(&options);
+        }
+        "interpret-trailers" => {
+            use utils::trailers::{IfExists, IfMissing, Placement, Where};
+            let usage = || -> ! {
+                eprintln!(
+                    "Usage: {} interpret-trailers [--in-place] [--trim-empty] [(--trailer <token>[(=|:)<value>])...] [--parse] [<file>...]",
+                    args[0]
+                );
+                std::process::exit(1);
+            };
+            let bad_value = |what: &str, value: &str| -> ! {
+                fatal(&format!("unknown value '{}' for {}", value, what));
+            };
+
+            let mut options = commands::interpret_trailers::TrailerOptions {
+                trailers: Vec::new(),
+                only_trailers: false,
+                only_input: false,
+                unfold: false,
+                trim_empty: false,
+                in_place: false,
+                files: Vec::new(),
+            };
+            // --where and friends apply to the --trailer arguments after them
+            let mut placement = Placement::default();
+            let mut i = 2;
+            while i < args.len() {
+                let arg = args[i].as_str();
+                let (flag, inline) = match arg.split_once('=') {
+                    Some((flag, value)) if flag.starts_with("--") => {
+                        (flag, Some(value.to_string()))
+                    }
+                    _ => (arg, None),
+                };
+                let mut value = || {
+                    inline.clone().unwrap_or_else(|| {
+                        i += 1;
+                        args.get(i).cloned().unwrap_or_else(|| usage())
+                    })
+                };
+                match flag {
+                    "--trailer" => {
+                        let trailer = value();
+                        options.trailers.push((trailer, placement));
+                    }
+                    "--where" => {
+                        let v = value();
+                        placement.where_ =
+                            Some(Where::parse(&v).unwrap_or_else(|| bad_value("--where", &v)));
+                    }
+                    "--if-exists" => {
+                        let v = value();
+                        placement.if_exists = Some(
+                            IfExists::parse(&v).unwrap_or_else(|| bad_value("--if-exists", &v)),
+                        );
+                    }
+                    "--if-missing" => {
+                        let v = value();
+                        placement.if_missing = Some(
+                            IfMissing::parse(&v).unwrap_or_else(|| bad_value("--if-missing", &v)),
+                        );
+                    }
+                    "--no-where" => placement.where_ = None,
+                    "--no-if-exists" => placement.if_exists = None,
+                    "--no-if-missing" => placement.if_missing = None,
+                    "--only-trailers" => options.only_trailers = true,
+                    "--only-input" => options.only_input = true,
+                    "--unfold" => options.unfold = true,
+                    "--parse" => {
+                        options.only_trailers = true;
+                        options.only_input = true;
+                        options.unfold = true;
+                    }
+                    "--trim-empty" => options.trim_empty = true,
+                    "--in-place" => options.in_place = true,
+                    _ if arg.starts_with('-') => usage(),
+                    _ => options.files.push(arg.to_string()),
+                }
+                i += 1;
+            }
+            if options.only_input && !options.trailers.is_empty() {
+                fatal("--trailer with --only-input does not make sense");
+            }
+            commands::interpret_trailers::interpret_trailers(&options);
+        }
+        "verify-commit" | "verify-tag" => {
+            let mut verbose = false;
+            let mut raw = false;
+            let mut names = Vec::new();
+            for arg in &args[2..] {
+                match arg.as_str() {
+                    "-v" | "--verbose" => verbose = true,
+                    "--raw" => raw = true,
+                    _ => names.push(arg.clone()),
+                }
+            }
+            if names.is_empty() {
+                eprintln!("Usage: {} {} [-v] [--raw] <name>...", args[0], args[1]);
+                std::process::exit(1);
+            }
+            if args[1] == "verify-commit" {
+                commands::verify::verify_commit(&names, verbose, raw);
+            } else {
+                commands::verify::verify_tag(&names, verbose, raw);
             }
-            commands::log::log();
         }
         "config" => {
             if args.len() < 3 {
@@ -195,12 +955,32 @@ fn main() {
             } else {
                 ""
             };
-            let key = if scope.is_empty() { &args[2] } else { &args[3] };
-            let value = if scope.is_empty() { &args[3] } else { &args[4] };
-            match utils::config::set_config_value(scope, "user", key, value) {
-                Ok(_) => println!("Set config: {} = {}", key, value),
-                Err(e) => {
-                    eprintln!("Error setting config: {:?}", e);
+            let rest = if scope.is_empty() {
+                &args[2..]
+            } else {
+                &args[3..]
+            };
+            match rest {
+                // a single key just reads the value back
+                [key] => match utils::config::get(key) {
+                    Some(value) => println!("{}", value),
+                    None => std::process::exit(1),
+                },
+                [key, value] => {
+                    let (section, name) = utils::config::split_key(key);
+                    match utils::config::set_config_value(scope, &section, &name, value) {
+                        Ok(_) => println!("Set config: {} = {}", key, value),
+                        Err(e) => {
+                            eprintln!("Error setting config: {:?}", e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                _ => {
+                    eprintln!(
+                        "Usage: {} config [--global or --list] <key> [<value>]",
+                        args[0]
+                    );
                     std::process::exit(1);
                 }
             }
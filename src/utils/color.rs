@@ -0,0 +1,138 @@
+// color: red means bad, green means good, and bold means someone wrote a header
+
+use crate::utils::config;
+use std::io::IsTerminal;
+
+/// Turns every color and attribute back off
+pub const RESET: &str = "\x1b[m";
+
+/// When to color output (`--color=<when>`, `color.ui`)
+#[derive(Clone, Copy, PartialEq)]
+pub enum When {
+    Always,
+    Never,
+    /// Only when stdout is a terminal
+    Auto,
+}
+
+impl When {
+    /// "always", "never", "auto", or a boolean (true meaning auto), like git
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "always" => Some(When::Always),
+            "never" | "false" | "no" | "off" | "0" => Some(When::Never),
+            "auto" | "true" | "yes" | "on" | "1" | "" => Some(When::Auto),
+            _ => None,
+        }
+    }
+
+    /// `key` (e.g. `color.diff`), falling back to `color.ui`, then auto
+    pub fn configured(key: &str) -> Self {
+        config::get(key)
+            .and_then(|value| When::parse(&value))
+            .or_else(|| config::get("color.ui").and_then(|value| When::parse(&value)))
+            .unwrap_or(When::Auto)
+    }
+
+    pub fn enabled(self) -> bool {
+        match self {
+            When::Always => true,
+            When::Never => false,
+            When::Auto => std::io::stdout().is_terminal(),
+        }
+    }
+}
+
+/// Turn a git color like "red", "bold blue reverse", "#ff8000" or "214 black"
+/// into its escape sequence: attributes first, then foreground and background
+pub fn parse(spec: &str) -> Option<String> {
+    let mut attributes = Vec::new();
+    let mut colors: Vec<String> = Vec::new();
+    for word in spec.split_whitespace() {
+        let word = word.to_lowercase();
+        if let Some(code) = attribute(&word) {
+            attributes.push(code.to_string());
+            continue;
+        }
+        // the first color is the foreground, the second the background
+        let background = match colors.len() {
+            0 => false,
+            1 => true,
+            _ => return None,
+        };
+        colors.push(color_code(&word, background)?);
+    }
+    // "normal" means no change at all
+    let codes: Vec<String> = attributes
+        .into_iter()
+        .chain(colors.into_iter().filter(|code| !code.is_empty()))
+        .collect();
+    Some(match codes.is_empty() {
+        true => String::new(),
+        false => format!("\x1b[{}m", codes.join(";")),
+    })
+}
+
+fn attribute(word: &str) -> Option<&'static str> {
+    let (negate, name) = match word.strip_prefix("no") {
+        Some(name) => (true, name.strip_prefix('-').unwrap_or(name)),
+        None => (false, word),
+    };
+    let codes = match name {
+        "bold" => ("1", "22"),
+        "dim" => ("2", "22"),
+        "italic" => ("3", "23"),
+        "ul" => ("4", "24"),
+        "blink" => ("5", "25"),
+        "reverse" => ("7", "27"),
+        "strike" => ("9", "29"),
+        _ => return None,
+    };
+    Some(if negate { codes.1 } else { codes.0 })
+}
+
+fn color_code(word: &str, background: bool) -> Option<String> {
+    const NAMES: [&str; 8] = [
+        "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+    ];
+    let base = if background { 40 } else { 30 };
+    if word == "normal" {
+        return Some(String::new());
+    }
+    if word == "default" {
+        return Some((base + 9).to_string());
+    }
+    if let Some(i) = NAMES.iter().position(|&name| name == word) {
+        return Some((base + i).to_string());
+    }
+    if let Some(i) = word
+        .strip_prefix("bright")
+        .and_then(|name| NAMES.iter().position(|&n| n == name))
+    {
+        return Some((base + 60 + i).to_string());
+    }
+    if let Some(hex) = word.strip_prefix('#')
+        && hex.len() == 6
+    {
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        return Some(format!(
+            "{};2;{};{};{}",
+            base + 8,
+            channel(0)?,
+            channel(2)?,
+            channel(4)?
+        ));
+    }
+    let n: u8 = word.parse().ok()?;
+    Some(match n {
+        0..=7 => (base + n as usize).to_string(),
+        _ => format!("{};5;{}", base + 8, n),
+    })
+}
+
+/// The escape sequence configured for `key` (e.g. `color.diff.old`), or `default`
+pub fn slot(key: &str, default: &str) -> String {
+    config::get(key)
+        .and_then(|spec| parse(&spec))
+        .unwrap_or_else(|| default.to_string())
+}
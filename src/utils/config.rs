@@ -1,3 +1,4 @@
+use crate::utils::repo;
 use configparser::ini::Ini;
 use std::fs;
 use std::io;
@@ -19,7 +20,7 @@ impl From<io::Error> for ConfigError {
 /// Load config (local first, then global)
 pub fn get_config_value(section: &str, key: &str) -> Result<Option<String>, ConfigError> {
     // 1. Try local config
-    let local_path = PathBuf::from(".hit").join("config");
+    let local_path = repo::path("config");
     if Path::exists(&local_path) {
         let mut conf = Ini::new();
         conf.load(local_path.to_str().unwrap())
@@ -60,7 +61,7 @@ pub fn set_config_value(
         "--global" => home::home_dir()
             .ok_or(ConfigError::HomeDirNotFound)?
             .join(".hitconfig"),
-        _ => PathBuf::from(".hit").join("config"),
+        _ => repo::path("config"),
     };
 
     let mut conf = if path.exists() {
@@ -83,3 +84,75 @@ pub fn set_config_value(
 
     Ok(())
 }
+
+/// Split a dotted name into (section, key) the way git does:
+/// "core.editor" -> ("core", "editor"), "trailer.sign.key" -> ("trailer \"sign\"", "key").
+/// A bare key like "name" lives under [user], as it always has.
+pub fn split_key(name: &str) -> (String, String) {
+    match (name.find('.'), name.rfind('.')) {
+        (Some(first), Some(last)) if first == last => {
+            (name[..first].to_string(), name[first + 1..].to_string())
+        }
+        (Some(first), Some(last)) => (
+            format!("{} \"{}\"", &name[..first], &name[first + 1..last]),
+            name[last + 1..].to_string(),
+        ),
+        _ => ("user".to_string(), name.to_string()),
+    }
+}
+
+/// Names of the subsections of `section` in local and global config,
+/// e.g. ["sign", "review"] for `[trailer "sign"]` and `[trailer "review"]`
+pub fn subsections(section: &str) -> Vec<String> {
+    let mut paths = vec![repo::path("config")];
+    if let Some(home) = home::home_dir() {
+        paths.push(home.join(".hitconfig"));
+    }
+
+    let prefix = format!("{} \"", section.to_lowercase());
+    let mut names = Vec::new();
+    for path in paths.iter().filter(|p| p.exists()) {
+        let mut conf = Ini::new();
+        if conf.load(path.to_str().unwrap()).is_err() {
+            continue;
+        }
+        for name in conf.sections() {
+            if let Some(sub) = name.strip_prefix(&prefix).and_then(|s| s.strip_suffix('"'))
+                && !names.iter().any(|n| n == sub)
+            {
+                names.push(sub.to_string());
+            }
+        }
+    }
+    names
+}
+
+/// Look up a dotted config name like "core.editor", treating errors as unset
+///
+/// A value wrapped in double quotes (to keep trailing spaces) is unquoted.
+pub fn get(name: &str) -> Option<String> {
+    let (section, key) = split_key(name);
+    let value = get_config_value(&section, &key).ok().flatten()?;
+    match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(unquoted) => Some(unquoted.to_string()),
+        None => Some(value),
+    }
+}
+
+/// Look up a boolean config name, accepting git's spellings of true and false
+pub fn get_bool(name: &str, default: bool) -> bool {
+    match get(name).map(|v| v.to_lowercase()) {
+        Some(v) if ["true", "yes", "on", "1", ""].contains(&v.as_str()) => true,
+        Some(v) if ["false", "no", "off", "0"].contains(&v.as_str()) => false,
+        _ => default,
+    }
+}
+
+/// Look up a path config value, expanding a leading "~/"
+pub fn get_path(name: &str) -> Option<PathBuf> {
+    let value = get(name)?;
+    match value.strip_prefix("~/") {
+        Some(rest) => home::home_dir().map(|home| home.join(rest)),
+        None => Some(PathBuf::from(value)),
+    }
+}
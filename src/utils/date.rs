@@ -0,0 +1,259 @@
+// dates: the only thing harder than naming things
+
+use chrono::{
+    DateTime, FixedOffset, Local, Months, NaiveDate, NaiveDateTime, Offset, TimeDelta, TimeZone,
+};
+
+/// Formats accepted with an explicit UTC offset
+const ZONED_FORMATS: [&str; 5] = [
+    "%Y-%m-%d %H:%M:%S %z",
+    "%Y-%m-%dT%H:%M:%S%z",
+    "%Y-%m-%d %H:%M:%S%z",
+    "%Y-%m-%d %H:%M %z",
+    "%a %b %e %H:%M:%S %Y %z",
+];
+
+/// Formats taken to be in local time
+const LOCAL_FORMATS: [&str; 3] = [
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+    "%a %b %e %H:%M:%S %Y",
+];
+
+/// Dates taken to be in local time, at midnight unless a time follows
+const DATE_FORMATS: [&str; 4] = ["%Y-%m-%d", "%Y/%m/%d", "%B %d %Y", "%d %B %Y"];
+
+/// Times of day that can follow one of `DATE_FORMATS`
+const TIME_FORMATS: [&str; 2] = ["%H:%M:%S", "%H:%M"];
+
+/// Parse a date given to `--date` or HIT_*_DATE into (unix seconds, UTC offset in seconds)
+///
+/// Accepts git's internal "<seconds> <+hhmm>" (optionally with a leading "@"),
+/// RFC 2822, ISO 8601, "now", and local dates like "2020-01-04 12:00" or
+/// "Jan 4, 2020" (with or without a time).
+pub fn parse(value: &str) -> Result<(i64, i32), String> {
+    let value = value.trim();
+    let invalid = || format!("invalid date format: {}", value);
+
+    if value == "now" {
+        return Ok(now());
+    }
+
+    // "<seconds> [<+hhmm>]", "@<seconds> [<+hhmm>]"
+    let raw = value.strip_prefix('@').unwrap_or(value);
+    let mut parts = raw.split_whitespace();
+    if let Some(seconds) = parts.next().and_then(|s| s.parse::<i64>().ok())
+        && (raw.len() != value.len() || seconds >= 100_000_000)
+    {
+        let offset = match parts.next() {
+            Some(tz) => parse_offset(tz).ok_or_else(invalid)?,
+            None if raw.len() != value.len() => 0,
+            None => return Err(invalid()),
+        };
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        return Ok((seconds, offset));
+    }
+
+    if let Ok(date) =
+        DateTime::parse_from_rfc2822(value).or_else(|_| DateTime::parse_from_rfc3339(value))
+    {
+        return Ok((date.timestamp(), date.offset().local_minus_utc()));
+    }
+    for format in ZONED_FORMATS {
+        if let Ok(date) = DateTime::parse_from_str(value, format) {
+            return Ok((date.timestamp(), date.offset().local_minus_utc()));
+        }
+    }
+
+    // "January 4, 2020" reads the same as "Jan 4 2020"
+    let words = value.replace(',', " ");
+    let words = words.split_whitespace().collect::<Vec<_>>().join(" ");
+    let naive = LOCAL_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| {
+            DATE_FORMATS.iter().find_map(|date| {
+                TIME_FORMATS
+                    .iter()
+                    .find_map(|time| {
+                        let format = format!("{} {}", date, time);
+                        NaiveDateTime::parse_from_str(&words, &format).ok()
+                    })
+                    .or_else(|| {
+                        NaiveDate::parse_from_str(&words, date)
+                            .ok()
+                            .and_then(|d| d.and_hms_opt(0, 0, 0))
+                    })
+            })
+        })
+        .ok_or_else(invalid)?;
+    let local = Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .ok_or_else(invalid)?;
+    Ok((local.timestamp(), local.offset().local_minus_utc()))
+}
+
+/// Parse a date given to `--since` or `--until` into unix seconds: anything
+/// `parse` takes, or one relative to now like "2 weeks ago", "3.days",
+/// "yesterday" or "1 year 2 months ago"
+pub fn parse_approx(value: &str) -> Result<i64, String> {
+    if let Ok((timestamp, _)) = parse(value) {
+        return Ok(timestamp);
+    }
+    let invalid = || format!("invalid date format: {}", value);
+    let mut when = Local::now();
+    let mut count = None;
+    let words = value
+        .split(|c: char| c.is_whitespace() || c == '.' || c == '_' || c == ',')
+        .filter(|word| !word.is_empty());
+    for word in words {
+        let word = word.to_lowercase();
+        if let Ok(n) = word.parse::<u32>() {
+            count = Some(n);
+            continue;
+        }
+        let n = match word.as_str() {
+            "a" | "an" => {
+                count = Some(1);
+                continue;
+            }
+            "ago" | "now" | "today" => continue,
+            "yesterday" => 1,
+            _ => count.take().unwrap_or(1),
+        };
+        let seconds = |unit: i64| when - TimeDelta::seconds(unit * n as i64);
+        when = match word.strip_suffix('s').unwrap_or(&word) {
+            "second" | "sec" => seconds(1),
+            "minute" | "min" => seconds(60),
+            "hour" => seconds(60 * 60),
+            "day" | "yesterday" => seconds(24 * 60 * 60),
+            "week" => seconds(7 * 24 * 60 * 60),
+            "month" => when
+                .checked_sub_months(Months::new(n))
+                .ok_or_else(invalid)?,
+            "year" => when
+                .checked_sub_months(Months::new(n * 12))
+                .ok_or_else(invalid)?,
+            _ => return Err(invalid()),
+        };
+    }
+    if count.is_some() {
+        return Err(invalid());
+    }
+    Ok(when.timestamp())
+}
+
+/// The current time as (unix seconds, local UTC offset in seconds)
+pub fn now() -> (i64, i32) {
+    let now = Local::now();
+    (now.timestamp(), now.offset().fix().local_minus_utc())
+}
+
+/// Parse "+0200", "-05:30" or "Z" into an offset in seconds
+pub fn parse_offset(tz: &str) -> Option<i32> {
+    if tz == "Z" {
+        return Some(0);
+    }
+    let sign = match tz.as_bytes().first()? {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return None,
+    };
+    let digits = tz[1..].replace(':', "");
+    if digits.len() != 4 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i32 = digits[..2].parse().ok()?;
+    let minutes: i32 = digits[2..].parse().ok()?;
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Format a UTC offset in seconds the way git does: "+0200", "-0530"
+pub fn format_timezone(offset_seconds: i32) -> String {
+    let sign = if offset_seconds < 0 { '-' } else { '+' };
+    let minutes = offset_seconds.abs() / 60;
+    format!("{}{:02}{:02}", sign, minutes / 60, minutes % 60)
+}
+
+/// The ways a date can be shown (`%ad`, `%ar`, `%ai`, ...)
+#[derive(Clone, Copy, PartialEq)]
+pub enum Style {
+    /// "Sun Jan 5 00:00:00 2020 +0000", in the date's own timezone
+    Default,
+    /// "7 years ago"
+    Relative,
+    /// "2020-01-05 00:00:00 +0000"
+    Iso,
+    /// "2020-01-05T00:00:00+00:00"
+    IsoStrict,
+    /// "2020-01-05"
+    Short,
+    /// Seconds since the epoch
+    Unix,
+}
+
+/// Show a timestamp and its "+hhmm" timezone in `style`, like git
+pub fn format(timestamp: i64, timezone: &str, style: Style) -> String {
+    let offset = FixedOffset::east_opt(parse_offset(timezone).unwrap_or(0))
+        .unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
+    let Some(datetime) = DateTime::from_timestamp(timestamp, 0) else {
+        return timestamp.to_string();
+    };
+    let datetime = datetime.with_timezone(&offset);
+    match style {
+        Style::Default => datetime.format("%a %b %-d %T %Y %z").to_string(),
+        Style::Relative => relative(now().0 - timestamp),
+        Style::Iso => datetime.format("%Y-%m-%d %T %z").to_string(),
+        Style::IsoStrict => datetime.to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+        Style::Short => datetime.format("%Y-%m-%d").to_string(),
+        Style::Unix => timestamp.to_string(),
+    }
+}
+
+/// "3 hours ago" for something `seconds` old, rounded the way git rounds
+fn relative(seconds: i64) -> String {
+    if seconds < 0 {
+        return "in the future".to_string();
+    }
+    let ago = |n: i64, unit: &str| match n {
+        1 => format!("1 {} ago", unit),
+        _ => format!("{} {}s ago", n, unit),
+    };
+    if seconds < 90 {
+        return ago(seconds, "second");
+    }
+    let minutes = (seconds + 30) / 60;
+    if minutes < 90 {
+        return ago(minutes, "minute");
+    }
+    let hours = (minutes + 30) / 60;
+    if hours < 36 {
+        return ago(hours, "hour");
+    }
+    let days = (hours + 12) / 24;
+    if days < 14 {
+        return ago(days, "day");
+    }
+    if days < 70 {
+        return ago((days + 3) / 7, "week");
+    }
+    if days < 365 {
+        return ago((days + 15) / 30, "month");
+    }
+    if days < 1825 {
+        let months = (days * 12 * 2 + 365) / (365 * 2);
+        let years = match months / 12 {
+            1 => "1 year".to_string(),
+            n => format!("{} years", n),
+        };
+        return match months % 12 {
+            0 => format!("{} ago", years),
+            1 => format!("{}, 1 month ago", years),
+            n => format!("{}, {} months ago", years, n),
+        };
+    }
+    ago((days + 183) / 365, "year")
+}
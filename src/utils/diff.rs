@@ -0,0 +1,1574 @@
+// diff: spot the difference, but for computers
+
+use crate::utils::color::{self, When};
+use crate::utils::config;
+use crate::utils::diffstat::{self, DirstatBy, DirstatOptions, StatOptions};
+use crate::utils::hash_object::hash_object;
+use crate::utils::index::{Index, IndexEntry};
+use crate::utils::objects::Object;
+use crate::utils::regex::Regex;
+use crate::utils::renames;
+use crate::utils::word_diff::{self, WordDiff};
+use crate::utils::workdir;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+/// One step of an edit script, holding 0-based line numbers
+#[derive(Clone, Copy, PartialEq)]
+pub enum Edit {
+    /// Line `.0` of the old file is line `.1` of the new one
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Split file contents into lines, each keeping its '\n' (the last may lack one)
+pub fn split_lines(data: &[u8]) -> Vec<&[u8]> {
+    data.split_inclusive(|&b| b == b'\n').collect()
+}
+
+/// Like git: a NUL in the first 8000 bytes means binary
+pub fn is_binary(data: &[u8]) -> bool {
+    data.iter().take(8000).any(|&b| b == 0)
+}
+
+/// Ways of choosing which lines to show as changed
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum Algorithm {
+    /// git's default: Myers, giving up on a perfect result for huge, very different files
+    #[default]
+    Myers,
+    /// Myers, always finding the smallest diff however long it takes
+    Minimal,
+    /// Anchor on lines that appear exactly once in each file, then diff between them
+    Patience,
+    /// Like patience, but anchoring on the rarest lines even if they repeat
+    Histogram,
+}
+
+impl Algorithm {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "myers" | "default" => Some(Algorithm::Myers),
+            "minimal" => Some(Algorithm::Minimal),
+            "patience" => Some(Algorithm::Patience),
+            "histogram" => Some(Algorithm::Histogram),
+            _ => None,
+        }
+    }
+
+    /// `diff.algorithm`, or Myers
+    pub fn configured() -> Self {
+        let name = config::get("diff.algorithm");
+        name.as_deref().map_or(Algorithm::Myers, |name| {
+            Algorithm::parse(name).unwrap_or_else(|| {
+                eprintln!(
+                    "warning: unknown value for config 'diff.algorithm': {}",
+                    name
+                );
+                Algorithm::Myers
+            })
+        })
+    }
+}
+
+/// How to lay out a patch
+#[derive(Clone)]
+pub struct PatchOptions {
+    /// Unchanged lines around each change (`-U<n>`, `diff.context`)
+    pub context: usize,
+    /// `--diff-algorithm`, `diff.algorithm`
+    pub algorithm: Algorithm,
+    /// Mark changed words within lines rather than whole lines (`--word-diff`)
+    pub word_diff: Option<WordDiff>,
+    /// What counts as a word (`--word-diff-regex`, `diff.wordRegex`); runs of
+    /// non-whitespace if unset
+    pub word_regex: Option<Regex>,
+    /// Escape codes to color the output with (`--color`, `color.diff`), if any
+    pub colors: Option<DiffColors>,
+}
+
+impl Default for PatchOptions {
+    fn default() -> Self {
+        let word_regex = config::get("diff.wordRegex").and_then(|pattern| {
+            Regex::new(&pattern)
+                .map_err(|e| eprintln!("warning: invalid regular expression: {}: {}", pattern, e))
+                .ok()
+        });
+        PatchOptions {
+            context: config::get("diff.context")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3),
+            algorithm: Algorithm::configured(),
+            word_diff: None,
+            word_regex,
+            colors: When::configured("color.diff")
+                .enabled()
+                .then(DiffColors::configured),
+        }
+    }
+}
+
+impl PatchOptions {
+    /// Apply a command-line flag if it's one of ours: `--word-diff[=<mode>]`,
+    /// `--word-diff-regex=<regex>`, `--color-words[=<regex>]`, `--color[=<when>]`
+    /// or `--no-color`
+    ///
+    /// Returns false for anything else, including malformed values.
+    pub fn parse_flag(&mut self, flag: &str) -> bool {
+        let (name, value) = match flag.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (flag, None),
+        };
+        match (name, value) {
+            ("--word-diff", None) => self.word_diff = Some(WordDiff::Plain),
+            ("--word-diff", Some("none")) => self.word_diff = None,
+            ("--word-diff", Some(mode)) => match WordDiff::parse(mode) {
+                Some(mode) => self.set_word_diff(mode),
+                None => return false,
+            },
+            ("--word-diff-regex", Some(pattern)) => match Regex::new(pattern) {
+                // like git, a word regex alone means plain word diff
+                Ok(regex) => {
+                    self.word_regex = Some(regex);
+                    self.word_diff.get_or_insert(WordDiff::Plain);
+                }
+                Err(_) => return false,
+            },
+            ("--color-words", _) => {
+                if let Some(pattern) = value {
+                    match Regex::new(pattern) {
+                        Ok(regex) => self.word_regex = Some(regex),
+                        Err(_) => return false,
+                    }
+                }
+                self.set_word_diff(WordDiff::Color);
+            }
+            ("--color", _) => match When::parse(value.unwrap_or("always")) {
+                Some(when) => self.colors = when.enabled().then(DiffColors::configured),
+                None => return false,
+            },
+            ("--no-color", None) => self.colors = None,
+            _ => return false,
+        }
+        true
+    }
+
+    /// Word diff in `mode`; coloring the words implies `--color`
+    fn set_word_diff(&mut self, mode: WordDiff) {
+        if mode == WordDiff::Color && self.colors.is_none() {
+            self.colors = Some(DiffColors::configured());
+        }
+        self.word_diff = Some(mode);
+    }
+}
+
+/// Escape codes for each part of the output, from `color.diff.<slot>`, and
+/// all empty when not coloring
+#[derive(Clone, Default)]
+pub struct DiffColors {
+    /// Headers: "diff --git", "index", "---" and so on
+    pub meta: String,
+    /// The "@@ -a,b +c,d @@" of a hunk
+    pub frag: String,
+    /// The function name after it
+    pub func: String,
+    pub context: String,
+    pub old: String,
+    pub new: String,
+    /// Whitespace errors in added lines
+    pub whitespace: String,
+    /// The "commit <sha>" line in `hit log`
+    pub commit: String,
+    pub reset: String,
+}
+
+impl DiffColors {
+    /// git's colors, unless `color.diff.<slot>` says otherwise
+    pub fn configured() -> Self {
+        let slot =
+            |name: &str, default: &str| color::slot(&format!("color.diff.{}", name), default);
+        DiffColors {
+            meta: slot("meta", "\x1b[1m"),
+            frag: slot("frag", "\x1b[36m"),
+            func: slot("func", ""),
+            // "plain" is the old name for context
+            context: color::slot("color.diff.context", &slot("plain", "")),
+            old: slot("old", "\x1b[31m"),
+            new: slot("new", "\x1b[32m"),
+            whitespace: slot("whitespace", "\x1b[41m"),
+            commit: slot("commit", "\x1b[33m"),
+            reset: color::RESET.to_string(),
+        }
+    }
+}
+
+/// Which views of a set of changes to print
+#[derive(Clone, Default)]
+pub struct DiffOutput {
+    /// The patch itself (`-p`)
+    pub patch: bool,
+    /// Status letters and paths only (`--name-status`), which overrides the rest
+    pub name_status: bool,
+    /// Just the paths (`--name-only`), which overrides the rest too
+    pub name_only: bool,
+    pub numstat: bool,
+    pub stat: Option<StatOptions>,
+    pub shortstat: bool,
+    pub dirstat: Option<DirstatOptions>,
+}
+
+impl DiffOutput {
+    /// Nothing asked for, so the command's default applies
+    pub fn is_empty(&self) -> bool {
+        !(self.patch
+            || self.name_status
+            || self.name_only
+            || self.numstat
+            || self.stat.is_some()
+            || self.shortstat
+            || self.dirstat.is_some())
+    }
+
+    /// Apply a command-line flag if it's one of ours: `-p`, `--name-status`,
+    /// `--name-only`, `--stat[=...]`, `--numstat`, `--shortstat` or `--dirstat[=...]`
+    ///
+    /// Returns false for anything else, including malformed values.
+    pub fn parse_flag(&mut self, flag: &str) -> bool {
+        match flag {
+            "-p" | "-u" | "--patch" => self.patch = true,
+            "--name-status" => self.name_status = true,
+            "--name-only" => self.name_only = true,
+            "--numstat" => self.numstat = true,
+            "--shortstat" => self.shortstat = true,
+            "--stat" => self.stat = Some(StatOptions::default()),
+            "--dirstat" => self.dirstat = Some(DirstatOptions::default()),
+            _ => {
+                if let Some(spec) = flag.strip_prefix("--stat=") {
+                    self.stat = StatOptions::parse(spec);
+                    return self.stat.is_some();
+                }
+                if let Some(params) = flag.strip_prefix("--dirstat=") {
+                    self.dirstat = DirstatOptions::default().with(params);
+                    return self.dirstat.is_some();
+                }
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Everything asked for, in git's order: statistics, then the patch
+    pub fn format(&self, pairs: &[FilePair], options: &PatchOptions) -> Vec<u8> {
+        if self.name_status {
+            return pairs
+                .iter()
+                .map(|pair| format_name_status(pair) + "\n")
+                .collect::<String>()
+                .into_bytes();
+        }
+        if self.name_only {
+            return pairs
+                .iter()
+                .map(|pair| pair.path().to_string() + "\n")
+                .collect::<String>()
+                .into_bytes();
+        }
+
+        let mut summary = String::new();
+        let by_lines = self.dirstat.is_some_and(|d| d.by == DirstatBy::Lines);
+        let stats = match self.numstat || self.stat.is_some() || self.shortstat || by_lines {
+            true => diffstat::file_stats(pairs, options.algorithm),
+            false => Vec::new(),
+        };
+        if self.numstat {
+            summary += &diffstat::format_numstat(&stats);
+        }
+        if let Some(stat) = &self.stat {
+            summary +=
+                &diffstat::format_stat(&stats, stat, &options.colors.clone().unwrap_or_default());
+        }
+        if self.shortstat {
+            summary += &diffstat::format_shortstat(&stats);
+        }
+        if let Some(dirstat) = &self.dirstat {
+            summary += &diffstat::format_dirstat(pairs, &stats, dirstat);
+        }
+
+        let mut out = summary.into_bytes();
+        if self.patch {
+            if !out.is_empty() {
+                out.push(b'\n');
+            }
+            for pair in pairs {
+                out.extend(format_patch(pair, options));
+            }
+        }
+        out
+    }
+}
+
+/// Compute an edit script turning `old` into `new`
+pub fn diff_lines(old: &[&[u8]], new: &[&[u8]], algorithm: Algorithm) -> Vec<Edit> {
+    // compare numbers rather than byte strings
+    let mut ids = HashMap::new();
+    let a = intern(&mut ids, old);
+    let b = intern(&mut ids, new);
+
+    let mut changed_a = vec![false; a.len()];
+    let mut changed_b = vec![false; b.len()];
+    let mut marks = Marks {
+        a: &mut changed_a,
+        b: &mut changed_b,
+    };
+    match algorithm {
+        Algorithm::Myers => without_unmatched(&a, &b, &mut marks, false),
+        Algorithm::Minimal => without_unmatched(&a, &b, &mut marks, true),
+        Algorithm::Patience => patience(&a, &b, 0, 0, &mut marks),
+        Algorithm::Histogram => histogram(&a, &b, 0, 0, &mut marks),
+    }
+    compact(&a, &b, &mut changed_a, &mut changed_b);
+    edit_script(&changed_a, &changed_b)
+}
+
+/// Number each distinct line, sharing numbers between files
+fn intern<'a>(ids: &mut HashMap<&'a [u8], usize>, lines: &[&'a [u8]]) -> Vec<usize> {
+    lines
+        .iter()
+        .map(|line| {
+            let next = ids.len();
+            *ids.entry(*line).or_insert(next)
+        })
+        .collect()
+}
+
+/// Which lines of each file are changed; algorithms fill this in
+struct Marks<'m> {
+    a: &'m mut [bool],
+    b: &'m mut [bool],
+}
+
+impl Marks<'_> {
+    /// Mark `a_len` lines of the old file and `b_len` of the new one as changed
+    fn set(&mut self, a_off: usize, a_len: usize, b_off: usize, b_len: usize) {
+        self.a[a_off..a_off + a_len].fill(true);
+        self.b[b_off..b_off + b_len].fill(true);
+    }
+}
+
+/// Lengths of the common prefix and suffix of two regions (not overlapping)
+fn common_ends(a: &[usize], b: &[usize]) -> (usize, usize) {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    (prefix, suffix)
+}
+
+/// Run Myers on the lines that appear in both files
+///
+/// Lines that appear nowhere in the other file can't be part of a common
+/// subsequence, so like git they're marked up front and left out of the
+/// (much slower) search. Unless `minimal`, so are lines that appear many
+/// times in the other file when they sit among such unmatched lines.
+fn without_unmatched(a: &[usize], b: &[usize], marks: &mut Marks, minimal: bool) {
+    let ids = a.iter().chain(b).max().map_or(0, |&id| id + 1);
+    let count = |lines: &[usize]| {
+        let mut counts = vec![0; ids];
+        for &line in lines {
+            counts[line] += 1;
+        }
+        counts
+    };
+    let (in_a, in_b) = (count(a), count(b));
+
+    // like git, only lines between the common start and end are candidates
+    let (prefix, suffix) = common_ends(a, b);
+    let keep = |lines: &[usize], in_other: &[usize]| -> Vec<usize> {
+        let limit = bogosqrt(lines.len()).min(1024);
+        let middle = &lines[prefix..lines.len() - suffix];
+        let discard: Vec<u8> = middle
+            .iter()
+            .map(|&line| match in_other[line] {
+                0 => 0,
+                n if n >= limit && !minimal => 2,
+                _ => 1,
+            })
+            .collect();
+        (0..middle.len())
+            .filter(|&i| discard[i] == 1 || (discard[i] == 2 && !among_unmatched(&discard, i)))
+            .map(|i| prefix + i)
+            .collect()
+    };
+    let keep_a = keep(a, &in_b);
+    let keep_b = keep(b, &in_a);
+    let short_a: Vec<usize> = keep_a.iter().map(|&i| a[i]).collect();
+    let short_b: Vec<usize> = keep_b.iter().map(|&j| b[j]).collect();
+
+    let mut short_changed_a = vec![false; short_a.len()];
+    let mut short_changed_b = vec![false; short_b.len()];
+    let mut short_marks = Marks {
+        a: &mut short_changed_a,
+        b: &mut short_changed_b,
+    };
+    // like git, give up on perfection once the search gets this expensive
+    let max_cost = bogosqrt(short_a.len() + short_b.len() + 3).max(256);
+    let bounds = [0, short_a.len() as isize, 0, short_b.len() as isize];
+    Myers::new(&short_a, &short_b, max_cost).compare(&mut short_marks, bounds, minimal);
+
+    for (changed, len) in [(&mut *marks.a, a.len()), (&mut *marks.b, b.len())] {
+        changed[..prefix].fill(false);
+        changed[prefix..len - suffix].fill(true);
+        changed[len - suffix..].fill(false);
+    }
+    for (k, &i) in keep_a.iter().enumerate() {
+        marks.a[i] = short_changed_a[k];
+    }
+    for (k, &j) in keep_b.iter().enumerate() {
+        marks.b[j] = short_changed_b[k];
+    }
+}
+
+/// git's cheap square root: a power of two, at least 1
+fn bogosqrt(mut n: usize) -> usize {
+    let mut root = 1;
+    while n > 0 {
+        root <<= 1;
+        n >>= 2;
+    }
+    root
+}
+
+/// Whether the often-repeated line `i` is in the middle of a run of unmatched
+/// (0) and other often-repeated (2) lines that's mostly unmatched, so it's
+/// better left out of the search (git's xdl_clean_mmatch)
+fn among_unmatched(discard: &[u8], i: usize) -> bool {
+    // how far to look either way
+    const WINDOW: usize = 100;
+
+    let run = |range: &mut dyn Iterator<Item = usize>| {
+        let (mut unmatched, mut repeated) = (0, 1);
+        for j in range {
+            match discard[j] {
+                0 => unmatched += 1,
+                2 => repeated += 1,
+                _ => break,
+            }
+        }
+        (unmatched, repeated)
+    };
+    let (before, repeated_before) = run(&mut (i.saturating_sub(WINDOW)..i).rev());
+    if before == 0 {
+        return false;
+    }
+    let (after, repeated_after) = run(&mut (i + 1..discard.len().min(i + WINDOW + 1)));
+    if after == 0 {
+        return false;
+    }
+    let (unmatched, repeated) = (before + after, repeated_before + repeated_after);
+    repeated * 4 < repeated + unmatched
+}
+
+/// Fall back to (non-minimal) Myers for one region, as git's patience and
+/// histogram diffs do when they find nothing to anchor on
+fn classic(a: &[usize], b: &[usize], a_off: usize, b_off: usize, marks: &mut Marks) {
+    let mut region = Marks {
+        a: &mut marks.a[a_off..a_off + a.len()],
+        b: &mut marks.b[b_off..b_off + b.len()],
+    };
+    without_unmatched(a, b, &mut region, false);
+}
+
+/// Linear-space Myers, as git does it: mark the lines of `a` and `b` that
+/// aren't part of a common subsequence, splitting the problem in two each time
+///
+/// Unless `minimal`, a split may settle for a long-enough common run (or, past
+/// `max_cost`, the furthest point reached) rather than an optimal one.
+struct Myers<'a> {
+    a: &'a [usize],
+    b: &'a [usize],
+    /// Furthest `a` position reached on each diagonal (`i - j`, shifted by
+    /// `base`) searching forwards, and backwards
+    forward: Vec<isize>,
+    backward: Vec<isize>,
+    base: isize,
+    max_cost: isize,
+}
+
+/// How long a common run has to be to count as a good place to split
+const SNAKE_COUNT: isize = 20;
+/// How expensive a search gets before good runs are used to cut it short
+const HEURISTIC_MIN_COST: isize = 256;
+
+impl<'a> Myers<'a> {
+    fn new(a: &'a [usize], b: &'a [usize], max_cost: usize) -> Self {
+        let size = a.len() + b.len() + 3;
+        Myers {
+            a,
+            b,
+            forward: vec![0; size],
+            backward: vec![0; size],
+            base: b.len() as isize + 1,
+            max_cost: max_cost.min(isize::MAX as usize) as isize,
+        }
+    }
+
+    /// git's xdl_recs_cmp, for `a[off1..lim1]` against `b[off2..lim2]`
+    fn compare(&mut self, marks: &mut Marks, bounds: [isize; 4], minimal: bool) {
+        let [mut off1, mut lim1, mut off2, mut lim2] = bounds;
+        let (a, b) = (self.a, self.b);
+        while off1 < lim1 && off2 < lim2 && a[off1 as usize] == b[off2 as usize] {
+            off1 += 1;
+            off2 += 1;
+        }
+        while off1 < lim1 && off2 < lim2 && a[lim1 as usize - 1] == b[lim2 as usize - 1] {
+            lim1 -= 1;
+            lim2 -= 1;
+        }
+        if off1 == lim1 || off2 == lim2 {
+            marks.set(
+                off1 as usize,
+                (lim1 - off1) as usize,
+                off2 as usize,
+                (lim2 - off2) as usize,
+            );
+            return;
+        }
+        let (i1, i2, minimal_low, minimal_high) = self.split([off1, lim1, off2, lim2], minimal);
+        self.compare(marks, [off1, i1, off2, i2], minimal_low);
+        self.compare(marks, [i1, lim1, i2, lim2], minimal_high);
+    }
+
+    /// git's xdl_split: search forwards from the start and backwards from the
+    /// end until the paths meet, and return where, plus whether each half
+    /// still has to be diffed minimally
+    fn split(&mut self, bounds: [isize; 4], minimal: bool) -> (isize, isize, bool, bool) {
+        let [off1, lim1, off2, lim2] = bounds;
+        let (a, b) = (self.a, self.b);
+        let base = self.base;
+        let (dmin, dmax) = (off1 - lim2, lim1 - off2);
+        let (fmid, bmid) = (off1 - off2, lim1 - lim2);
+        let odd = (fmid - bmid) & 1 != 0;
+        let (mut fmin, mut fmax, mut bmin, mut bmax) = (fmid, fmid, bmid, bmid);
+        let kf = |d: isize| (d + base) as usize;
+        self.forward[kf(fmid)] = off1;
+        self.backward[kf(bmid)] = lim1;
+
+        let mut cost = 1;
+        loop {
+            let mut got_snake = false;
+
+            // widen the range of diagonals by one each way, if they fit
+            if fmin > dmin {
+                fmin -= 1;
+                self.forward[kf(fmin - 1)] = -1;
+            } else {
+                fmin += 1;
+            }
+            if fmax < dmax {
+                fmax += 1;
+                self.forward[kf(fmax + 1)] = -1;
+            } else {
+                fmax -= 1;
+            }
+            let mut d = fmax;
+            while d >= fmin {
+                let mut i1 = if self.forward[kf(d - 1)] >= self.forward[kf(d + 1)] {
+                    self.forward[kf(d - 1)] + 1
+                } else {
+                    self.forward[kf(d + 1)]
+                };
+                let start = i1;
+                let mut i2 = i1 - d;
+                while i1 < lim1 && i2 < lim2 && a[i1 as usize] == b[i2 as usize] {
+                    i1 += 1;
+                    i2 += 1;
+                }
+                got_snake |= i1 - start > SNAKE_COUNT;
+                self.forward[kf(d)] = i1;
+                if odd && bmin <= d && d <= bmax && self.backward[kf(d)] <= i1 {
+                    return (i1, i2, true, true);
+                }
+                d -= 2;
+            }
+
+            if bmin > dmin {
+                bmin -= 1;
+                self.backward[kf(bmin - 1)] = isize::MAX;
+            } else {
+                bmin += 1;
+            }
+            if bmax < dmax {
+                bmax += 1;
+                self.backward[kf(bmax + 1)] = isize::MAX;
+            } else {
+                bmax -= 1;
+            }
+            let mut d = bmax;
+            while d >= bmin {
+                let mut i1 = if self.backward[kf(d - 1)] < self.backward[kf(d + 1)] {
+                    self.backward[kf(d - 1)]
+                } else {
+                    self.backward[kf(d + 1)] - 1
+                };
+                let start = i1;
+                let mut i2 = i1 - d;
+                while i1 > off1 && i2 > off2 && a[i1 as usize - 1] == b[i2 as usize - 1] {
+                    i1 -= 1;
+                    i2 -= 1;
+                }
+                got_snake |= start - i1 > SNAKE_COUNT;
+                self.backward[kf(d)] = i1;
+                if !odd && fmin <= d && d <= fmax && i1 <= self.forward[kf(d)] {
+                    return (i1, i2, true, true);
+                }
+                d -= 2;
+            }
+
+            if minimal {
+                cost += 1;
+                continue;
+            }
+
+            // expensive already: settle for a point well along the way that
+            // ends (or starts) a long common run
+            if got_snake && cost > HEURISTIC_MIN_COST {
+                let mut best = None;
+                let mut d = fmax;
+                while d >= fmin {
+                    let i1 = self.forward[kf(d)];
+                    let i2 = i1 - d;
+                    let v = (i1 - off1) + (i2 - off2) - (d - fmid).abs();
+                    if v > 4 * cost
+                        && best.is_none_or(|(best, _, _)| v > best)
+                        && off1 + SNAKE_COUNT <= i1
+                        && i1 < lim1
+                        && off2 + SNAKE_COUNT <= i2
+                        && i2 < lim2
+                        && (1..=SNAKE_COUNT).all(|k| a[(i1 - k) as usize] == b[(i2 - k) as usize])
+                    {
+                        best = Some((v, i1, i2));
+                    }
+                    d -= 2;
+                }
+                if let Some((_, i1, i2)) = best {
+                    return (i1, i2, true, false);
+                }
+
+                let mut d = bmax;
+                while d >= bmin {
+                    let i1 = self.backward[kf(d)];
+                    let i2 = i1 - d;
+                    let v = (lim1 - i1) + (lim2 - i2) - (d - bmid).abs();
+                    if v > 4 * cost
+                        && best.is_none_or(|(best, _, _)| v > best)
+                        && off1 < i1
+                        && i1 <= lim1 - SNAKE_COUNT
+                        && off2 < i2
+                        && i2 <= lim2 - SNAKE_COUNT
+                        && (0..SNAKE_COUNT).all(|k| a[(i1 + k) as usize] == b[(i2 + k) as usize])
+                    {
+                        best = Some((v, i1, i2));
+                    }
+                    d -= 2;
+                }
+                if let Some((_, i1, i2)) = best {
+                    return (i1, i2, false, true);
+                }
+            }
+
+            // too expensive: split wherever either search got furthest
+            if cost >= self.max_cost {
+                let (mut fbest, mut fbest1) = (-1, -1);
+                let mut d = fmax;
+                while d >= fmin {
+                    let mut i1 = self.forward[kf(d)].min(lim1);
+                    let mut i2 = i1 - d;
+                    if lim2 < i2 {
+                        i1 = lim2 + d;
+                        i2 = lim2;
+                    }
+                    if fbest < i1 + i2 {
+                        fbest = i1 + i2;
+                        fbest1 = i1;
+                    }
+                    d -= 2;
+                }
+                let (mut bbest, mut bbest1) = (isize::MAX, isize::MAX);
+                let mut d = bmax;
+                while d >= bmin {
+                    let mut i1 = self.backward[kf(d)].max(off1);
+                    let mut i2 = i1 - d;
+                    if i2 < off2 {
+                        i1 = off2 + d;
+                        i2 = off2;
+                    }
+                    if i1 + i2 < bbest {
+                        bbest = i1 + i2;
+                        bbest1 = i1;
+                    }
+                    d -= 2;
+                }
+                return if (lim1 + lim2) - bbest < fbest - (off1 + off2) {
+                    (fbest1, fbest - fbest1, true, false)
+                } else {
+                    (bbest1, bbest - bbest1, false, true)
+                };
+            }
+            cost += 1;
+        }
+    }
+}
+
+/// Patience diff: match up lines that occur exactly once in both files, keep
+/// the longest run of them that's in order, and diff the gaps between them
+///
+/// Like git, unique lines are picked before trimming the common ends, which
+/// are only skipped within each gap.
+fn patience(a: &[usize], b: &[usize], a_off: usize, b_off: usize, marks: &mut Marks) {
+    if a.is_empty() || b.is_empty() {
+        marks.set(a_off, a.len(), b_off, b.len());
+        return;
+    }
+
+    // line -> (count in a, position in a, count in b, position in b)
+    let mut seen: HashMap<usize, (usize, usize, usize, usize)> = HashMap::new();
+    for (i, &line) in a.iter().enumerate() {
+        let entry = seen.entry(line).or_default();
+        entry.0 += 1;
+        entry.1 = i;
+    }
+    for (j, &line) in b.iter().enumerate() {
+        if let Some(entry) = seen.get_mut(&line) {
+            entry.2 += 1;
+            entry.3 = j;
+        }
+    }
+    let mut unique: Vec<(usize, usize)> = seen
+        .values()
+        .filter(|&&(in_a, _, in_b, _)| in_a == 1 && in_b == 1)
+        .map(|&(_, i, _, j)| (i, j))
+        .collect();
+    if unique.is_empty() {
+        classic(a, b, a_off, b_off, marks);
+        return;
+    }
+    unique.sort_unstable();
+
+    let mut gap = |a: &[usize], b: &[usize], a_off: usize, b_off: usize| {
+        let (prefix, suffix) = common_ends(a, b);
+        patience(
+            &a[prefix..a.len() - suffix],
+            &b[prefix..b.len() - suffix],
+            a_off + prefix,
+            b_off + prefix,
+            marks,
+        );
+    };
+    let mut start = (0, 0);
+    for (i, j) in longest_increasing(&unique) {
+        gap(
+            &a[start.0..i],
+            &b[start.1..j],
+            a_off + start.0,
+            b_off + start.1,
+        );
+        start = (i + 1, j + 1);
+    }
+    gap(
+        &a[start.0..],
+        &b[start.1..],
+        a_off + start.0,
+        b_off + start.1,
+    );
+}
+
+/// The longest subsequence of `pairs` (sorted by .0) whose .1 also increases,
+/// found by patience sorting
+fn longest_increasing(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    // tops[p]: index into pairs of the top card of pile p; back[i]: card under i's pile to the left
+    let mut tops: Vec<usize> = Vec::new();
+    let mut back: Vec<Option<usize>> = vec![None; pairs.len()];
+    for (i, &(_, j)) in pairs.iter().enumerate() {
+        let pile = tops.partition_point(|&t| pairs[t].1 < j);
+        back[i] = pile.checked_sub(1).map(|p| tops[p]);
+        if pile == tops.len() {
+            tops.push(i);
+        } else {
+            tops[pile] = i;
+        }
+    }
+
+    let mut result = Vec::new();
+    let mut card = tops.last().copied();
+    while let Some(i) = card {
+        result.push(pairs[i]);
+        card = back[i];
+    }
+    result.reverse();
+    result
+}
+
+/// Histogram diff: split around the longest common run among the rarest
+/// lines, then diff each side of it the same way
+///
+/// This follows git's choices closely: a run is only considered if its first
+/// line is no more common than the best run's rarest line, and replaces it if
+/// it's longer or rarer.
+fn histogram(a: &[usize], b: &[usize], a_off: usize, b_off: usize, marks: &mut Marks) {
+    /// Lines more common than this are never used as anchors
+    const MAX_CHAIN: usize = 64;
+
+    if a.is_empty() || b.is_empty() {
+        marks.set(a_off, a.len(), b_off, b.len());
+        return;
+    }
+
+    let mut positions: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, &line) in a.iter().enumerate() {
+        positions.entry(line).or_default().push(i);
+    }
+    let count = |line: usize| positions[&line].len();
+
+    // (a start, b start, length) of the best common run so far, and how
+    // often its rarest line appears in `a`
+    let mut best: Option<(usize, usize, usize)> = None;
+    let mut best_rarity = MAX_CHAIN + 1;
+    let mut has_common = false;
+    let mut j = 0;
+    while j < b.len() {
+        let mut next = j + 1;
+        if let Some(found) = positions.get(&b[j]) {
+            has_common = true;
+            let mut k = if found.len() <= best_rarity {
+                0
+            } else {
+                found.len()
+            };
+            while k < found.len() {
+                let (mut a_start, mut b_start) = (found[k], j);
+                let (mut a_end, mut b_end) = (found[k], j);
+                let mut rarity = found.len();
+                while a_start > 0 && b_start > 0 && a[a_start - 1] == b[b_start - 1] {
+                    a_start -= 1;
+                    b_start -= 1;
+                    rarity = rarity.min(count(a[a_start]));
+                }
+                while a_end + 1 < a.len() && b_end + 1 < b.len() && a[a_end + 1] == b[b_end + 1] {
+                    a_end += 1;
+                    b_end += 1;
+                    rarity = rarity.min(count(a[a_end]));
+                }
+                next = next.max(b_end + 1);
+                let len = a_end - a_start + 1;
+                if best.is_none_or(|(_, _, best_len)| best_len < len) || rarity < best_rarity {
+                    best = Some((a_start, b_start, len));
+                    best_rarity = rarity;
+                }
+                // later occurrences inside this run would only find it again
+                while k < found.len() && found[k] <= a_end {
+                    k += 1;
+                }
+            }
+        }
+        j = next;
+    }
+
+    let Some((i, j, len)) = best else {
+        if has_common {
+            classic(a, b, a_off, b_off, marks);
+        } else {
+            marks.set(a_off, a.len(), b_off, b.len());
+        }
+        return;
+    };
+    histogram(&a[..i], &b[..j], a_off, b_off, marks);
+    histogram(
+        &a[i + len..],
+        &b[j + len..],
+        a_off + i + len,
+        b_off + j + len,
+        marks,
+    );
+}
+
+/// Slide runs of changed lines to where git would show them: as far down as
+/// they go, so that e.g. an added function is shown whole rather than
+/// straddling a brace, unless they can line up with a change in the other file
+fn compact(a: &[usize], b: &[usize], changed_a: &mut [bool], changed_b: &mut [bool]) {
+    compact_against(a, changed_a, changed_b);
+    compact_against(b, changed_b, changed_a);
+}
+
+/// A run of changed lines, `start..end`, possibly empty
+#[derive(Clone, Copy)]
+struct Group {
+    start: usize,
+    end: usize,
+}
+
+/// git's xdl_change_compact: slide each group in `changed` (merging any it
+/// runs into), walking the other file's groups in step
+fn compact_against(lines: &[usize], changed: &mut [bool], other: &mut [bool]) {
+    let mut g = first_group(changed);
+    let mut go = first_group(other);
+    loop {
+        if g.end != g.start {
+            let mut earliest_end;
+            // the last end at which the group lines up with a change in the other file
+            let mut end_matching_other;
+            loop {
+                let size = g.end - g.start;
+                end_matching_other = None;
+                while slide_up(lines, changed, &mut g) {
+                    previous_group(other, &mut go);
+                }
+                earliest_end = g.end;
+                if go.end > go.start {
+                    end_matching_other = Some(g.end);
+                }
+                while slide_down(lines, changed, &mut g) {
+                    next_group(other, &mut go);
+                    if go.end > go.start {
+                        end_matching_other = Some(g.end);
+                    }
+                }
+                if size == g.end - g.start {
+                    break;
+                }
+            }
+            // it's as far down as it goes; back up to meet the other file's change
+            if g.end != earliest_end && end_matching_other.is_some() {
+                while go.end == go.start {
+                    slide_up(lines, changed, &mut g);
+                    previous_group(other, &mut go);
+                }
+            }
+        }
+        if !next_group(changed, &mut g) {
+            break;
+        }
+        next_group(other, &mut go);
+    }
+}
+
+fn first_group(changed: &[bool]) -> Group {
+    let end = changed.iter().take_while(|&&c| c).count();
+    Group { start: 0, end }
+}
+
+/// Move to the next (possibly empty) group, unless already at the end
+fn next_group(changed: &[bool], g: &mut Group) -> bool {
+    if g.end == changed.len() {
+        return false;
+    }
+    g.start = g.end + 1;
+    g.end = g.start;
+    while g.end < changed.len() && changed[g.end] {
+        g.end += 1;
+    }
+    true
+}
+
+/// Move to the previous (possibly empty) group, unless already at the start
+fn previous_group(changed: &[bool], g: &mut Group) -> bool {
+    if g.start == 0 {
+        return false;
+    }
+    g.end = g.start - 1;
+    g.start = g.end;
+    while g.start > 0 && changed[g.start - 1] {
+        g.start -= 1;
+    }
+    true
+}
+
+/// Move the group down a line if the line after it matches its first line,
+/// swallowing any group it bumps into
+fn slide_down(lines: &[usize], changed: &mut [bool], g: &mut Group) -> bool {
+    if g.end >= lines.len() || lines[g.start] != lines[g.end] {
+        return false;
+    }
+    changed[g.start] = false;
+    changed[g.end] = true;
+    g.start += 1;
+    g.end += 1;
+    while g.end < changed.len() && changed[g.end] {
+        g.end += 1;
+    }
+    true
+}
+
+/// Move the group up a line if the line before it matches its last line,
+/// swallowing any group it bumps into
+fn slide_up(lines: &[usize], changed: &mut [bool], g: &mut Group) -> bool {
+    if g.start == 0 || lines[g.start - 1] != lines[g.end - 1] {
+        return false;
+    }
+    g.start -= 1;
+    g.end -= 1;
+    changed[g.start] = true;
+    changed[g.end] = false;
+    while g.start > 0 && changed[g.start - 1] {
+        g.start -= 1;
+    }
+    true
+}
+
+/// Turn the changed-line marks back into an edit script, deletions first
+fn edit_script(changed_a: &[bool], changed_b: &[bool]) -> Vec<Edit> {
+    let (mut i, mut j) = (0, 0);
+    let mut script = Vec::new();
+    while i < changed_a.len() || j < changed_b.len() {
+        if i < changed_a.len() && changed_a[i] {
+            script.push(Edit::Delete(i));
+            i += 1;
+        } else if j < changed_b.len() && changed_b[j] {
+            script.push(Edit::Insert(j));
+            j += 1;
+        } else {
+            script.push(Edit::Equal(i, j));
+            i += 1;
+            j += 1;
+        }
+    }
+    script
+}
+
+/// A block of changes with its surrounding context
+pub struct Hunk {
+    /// 1-based first line in the old file (the line before, for an empty range)
+    pub old_start: usize,
+    pub old_count: usize,
+    pub new_start: usize,
+    pub new_count: usize,
+    pub edits: Vec<Edit>,
+}
+
+/// Group an edit script into hunks with `context` unchanged lines around each
+/// change, merging hunks whose context would overlap or touch
+pub fn hunks(script: &[Edit], context: usize) -> Vec<Hunk> {
+    let changes: Vec<usize> = (0..script.len())
+        .filter(|&i| !matches!(script[i], Edit::Equal(..)))
+        .collect();
+    let Some(&first) = changes.first() else {
+        return Vec::new();
+    };
+
+    let mut ranges = Vec::new();
+    let (mut start, mut end) = (first, first);
+    for &i in &changes[1..] {
+        if i - end - 1 > 2 * context {
+            ranges.push((start, end));
+            start = i;
+        }
+        end = i;
+    }
+    ranges.push((start, end));
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            let from = start.saturating_sub(context);
+            let to = (end + context + 1).min(script.len());
+            let edits = script[from..to].to_vec();
+
+            // the position in each file where the hunk begins
+            let (mut old_line, mut new_line) = (0, 0);
+            for edit in &script[..from] {
+                match edit {
+                    Edit::Equal(..) => {
+                        old_line += 1;
+                        new_line += 1;
+                    }
+                    Edit::Delete(_) => old_line += 1,
+                    Edit::Insert(_) => new_line += 1,
+                }
+            }
+            let old_count = edits
+                .iter()
+                .filter(|e| !matches!(e, Edit::Insert(_)))
+                .count();
+            let new_count = edits
+                .iter()
+                .filter(|e| !matches!(e, Edit::Delete(_)))
+                .count();
+            Hunk {
+                old_start: if old_count == 0 {
+                    old_line
+                } else {
+                    old_line + 1
+                },
+                old_count,
+                new_start: if new_count == 0 {
+                    new_line
+                } else {
+                    new_line + 1
+                },
+                new_count,
+                edits,
+            }
+        })
+        .collect()
+}
+
+/// Write a hunk's "@@ -a,b +c,d @@ <function>" line
+pub fn format_hunk_header(out: &mut Vec<u8>, hunk: &Hunk, old: &[&[u8]], colors: &DiffColors) {
+    let range = |start: usize, count: usize| {
+        if count == 1 {
+            start.to_string()
+        } else {
+            format!("{},{}", start, count)
+        }
+    };
+    out.extend_from_slice(
+        format!(
+            "{}@@ -{} +{} @@{}",
+            colors.frag,
+            range(hunk.old_start, hunk.old_count),
+            range(hunk.new_start, hunk.new_count),
+            colors.reset
+        )
+        .as_bytes(),
+    );
+    let first_old = hunk
+        .edits
+        .iter()
+        .find_map(|edit| match edit {
+            Edit::Equal(i, _) | Edit::Delete(i) => Some(*i),
+            Edit::Insert(_) => None,
+        })
+        .unwrap_or(hunk.old_start);
+    if let Some(function) = function_line(&old[..first_old.min(old.len())]) {
+        let space = format!("{} {}{}", colors.context, colors.reset, colors.func);
+        out.extend_from_slice(space.as_bytes());
+        out.extend_from_slice(function);
+        out.extend_from_slice(colors.reset.as_bytes());
+    }
+    out.push(b'\n');
+}
+
+/// Write one hunk in unified format
+///
+/// With colors, whitespace errors in added lines are highlighted like git's
+/// default `core.whitespace`: trailing whitespace, spaces before a tab in the
+/// indent, and blank lines added at the end of the file. `blank_at_eof` holds
+/// where those start in each file, if the new file has more of them.
+pub fn format_hunk(
+    out: &mut Vec<u8>,
+    hunk: &Hunk,
+    old: &[&[u8]],
+    new: &[&[u8]],
+    colors: &DiffColors,
+    blank_at_eof: Option<(usize, usize)>,
+) {
+    format_hunk_header(out, hunk, old, colors);
+
+    // counted like git: one past each line by the time it's written
+    let (mut old_line, mut new_line) = (hunk.old_start, hunk.new_start);
+    for edit in &hunk.edits {
+        let line = match *edit {
+            Edit::Equal(i, _) => {
+                old_line += 1;
+                new_line += 1;
+                paint_line(out, &colors.context, b' ', old[i], &colors.reset);
+                old[i]
+            }
+            Edit::Delete(i) => {
+                old_line += 1;
+                paint_line(out, &colors.old, b'-', old[i], &colors.reset);
+                old[i]
+            }
+            Edit::Insert(j) => {
+                new_line += 1;
+                let at_eof = blank_at_eof.is_some_and(|(old_eof, new_eof)| {
+                    old_eof <= old_line
+                        && new_eof <= new_line
+                        && new[j].iter().all(|&b| is_space(b))
+                });
+                if colors.reset.is_empty() {
+                    paint_line(out, "", b'+', new[j], "");
+                } else if at_eof {
+                    paint_line(out, &colors.whitespace, b'+', new[j], &colors.reset);
+                } else {
+                    paint_added(out, new[j], colors);
+                }
+                new[j]
+            }
+        };
+        if !line.ends_with(b"\n") {
+            paint_line(
+                out,
+                &colors.context,
+                b'\\',
+                b" No newline at end of file\n",
+                &colors.reset,
+            );
+        }
+    }
+}
+
+/// git's whitespace, which leaves out vertical tabs and form feeds
+pub fn is_space(byte: u8) -> bool {
+    matches!(byte, b' ' | b'\t' | b'\n' | b'\r')
+}
+
+/// Write `sign` and `line` in `color`, keeping a "\r\n" ending outside it
+fn paint_line(out: &mut Vec<u8>, color: &str, sign: u8, line: &[u8], reset: &str) {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    let (line, cr) = match line.strip_suffix(b"\r") {
+        Some(line) => (line, true),
+        None => (line, false),
+    };
+    out.extend_from_slice(color.as_bytes());
+    out.push(sign);
+    out.extend_from_slice(line);
+    out.extend_from_slice(reset.as_bytes());
+    if cr {
+        out.push(b'\r');
+    }
+    out.push(b'\n');
+}
+
+/// Write an added line with its whitespace errors highlighted, as git's
+/// ws_check_emit does
+fn paint_added(out: &mut Vec<u8>, line: &[u8], colors: &DiffColors) {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    let trailing = line.len() - line.iter().rev().take_while(|&&b| is_space(b)).count();
+    let mut painted = format!("{}+{}", colors.new, colors.reset).into_bytes();
+
+    // the indent goes out as is, up to its last tab, flagging spaces before a tab
+    let mut written = 0;
+    for i in 0..trailing {
+        match line[i] {
+            b' ' => continue,
+            b'\t' if written < i => {
+                painted.extend_from_slice(colors.whitespace.as_bytes());
+                painted.extend_from_slice(&line[written..i]);
+                painted.extend_from_slice(colors.reset.as_bytes());
+                painted.push(b'\t');
+            }
+            b'\t' => painted.push(b'\t'),
+            _ => break,
+        }
+        written = i + 1;
+    }
+    for (part, color) in [
+        (&line[written..trailing], &colors.new),
+        (&line[trailing..], &colors.whitespace),
+    ] {
+        if !part.is_empty() {
+            painted.extend_from_slice(color.as_bytes());
+            painted.extend_from_slice(part);
+            painted.extend_from_slice(colors.reset.as_bytes());
+        }
+    }
+    out.extend(painted);
+    out.push(b'\n');
+}
+
+/// Where blank lines added at the end of `new` begin in each file (1-based,
+/// counted like git), if `new` ends in more blank lines than `old`
+fn blank_at_eof(old: &[u8], new: &[u8]) -> Option<(usize, usize)> {
+    let (old_blank, new_blank) = (trailing_blank_lines(old), trailing_blank_lines(new));
+    if new_blank <= old_blank {
+        return None;
+    }
+    let lines = |data: &[u8]| split_lines(data).len();
+    Some((lines(old) - old_blank + 1, lines(new) - new_blank + 1))
+}
+
+/// How many whitespace-only lines `data` ends with, quirks and all: like git,
+/// a first line of fewer than two bytes is never counted
+fn trailing_blank_lines(data: &[u8]) -> usize {
+    if data.is_empty() {
+        return 0;
+    }
+    // the last byte of the last line, not counting its newline
+    let mut end = data.len() as isize - 1;
+    if data[end as usize] == b'\n' {
+        end -= 1;
+    }
+    let mut count = 0;
+    while end > 0 {
+        let mut start = end;
+        while start >= 0 && data[start as usize] != b'\n' {
+            start -= 1;
+        }
+        if !data[(start + 1) as usize..=end as usize]
+            .iter()
+            .all(|&b| is_space(b))
+        {
+            break;
+        }
+        count += 1;
+        end = start - 1;
+    }
+    count
+}
+
+/// The nearest line above a hunk that looks like the start of a function:
+/// git's default rule is any line starting with a letter, '_' or '$'
+fn function_line<'a>(before: &[&'a [u8]]) -> Option<&'a [u8]> {
+    before.iter().rev().find_map(|line| {
+        let first = *line.first()?;
+        if !(first.is_ascii_alphabetic() || first == b'_' || first == b'$') {
+            return None;
+        }
+        let mut end = line.len();
+        while end > 0 && line[end - 1].is_ascii_whitespace() {
+            end -= 1;
+        }
+        Some(&line[..end.min(80)])
+    })
+}
+
+/// A file as it appears on one side of a comparison
+#[derive(Clone)]
+pub struct Side {
+    pub path: String,
+    pub mode: String,
+    pub sha: String,
+    /// Read the contents from the working tree rather than the object store
+    pub worktree: bool,
+}
+
+impl Side {
+    fn from_entry(entry: &IndexEntry) -> Self {
+        Side {
+            path: entry.path.clone(),
+            mode: entry.mode.clone(),
+            sha: entry.sha.clone(),
+            worktree: false,
+        }
+    }
+
+    pub fn content(&self) -> Vec<u8> {
+        if self.worktree {
+            fs::read(&self.path).unwrap_or_default()
+        } else {
+            match Object::read(&self.sha) {
+                Ok(Object::Blob(data)) => data,
+                _ => Vec::new(),
+            }
+        }
+    }
+}
+
+/// Every file on one side of a comparison, by path
+pub type Snapshot = BTreeMap<String, Side>;
+
+/// The files recorded in a tree
+pub fn tree_snapshot(tree_sha: &str) -> Result<Snapshot, String> {
+    Ok(index_snapshot(&Index::from_tree(tree_sha)?))
+}
+
+/// The files staged in an index
+pub fn index_snapshot(index: &Index) -> Snapshot {
+    index
+        .entries
+        .iter()
+        .map(|entry| (entry.path.clone(), Side::from_entry(entry)))
+        .collect()
+}
+
+/// The working tree copies of `paths` (files that are missing are left out)
+pub fn worktree_snapshot<'a>(paths: impl IntoIterator<Item = &'a String>) -> Snapshot {
+    paths
+        .into_iter()
+        .filter(|path| Path::new(path.as_str()).is_file())
+        .map(|path| {
+            let side = Side {
+                path: path.clone(),
+                mode: workdir::file_mode(path),
+                sha: hash_object(path, false, false),
+                worktree: true,
+            };
+            (path.clone(), side)
+        })
+        .collect()
+}
+
+/// A path that differs between two snapshots; a missing side means added or
+/// deleted, and different paths on each side a rename or copy
+#[derive(Default)]
+pub struct FilePair {
+    pub old: Option<Side>,
+    pub new: Option<Side>,
+    /// How similar a renamed or copied file is to where it came from, out of `renames::MAX_SCORE`
+    pub score: u64,
+    /// The old file is still around, so this is a copy rather than a rename
+    pub copy: bool,
+}
+
+impl FilePair {
+    /// The path on the new side, or the old one for a deleted file
+    pub fn path(&self) -> &str {
+        match (&self.old, &self.new) {
+            (_, Some(side)) | (Some(side), None) => &side.path,
+            (None, None) => "",
+        }
+    }
+
+    /// git's status letter: 'A'dded, 'D'eleted, 'M'odified, 'R'enamed or 'C'opied
+    pub fn status(&self) -> char {
+        match (&self.old, &self.new) {
+            (None, _) => 'A',
+            (_, None) => 'D',
+            (Some(old), Some(new)) if old.path != new.path => {
+                if self.copy {
+                    'C'
+                } else {
+                    'R'
+                }
+            }
+            _ => 'M',
+        }
+    }
+
+    /// The similarity as a whole percentage
+    pub fn similarity(&self) -> u64 {
+        self.score * 100 / renames::MAX_SCORE
+    }
+}
+
+/// The paths whose content or mode differ between `old` and `new`, sorted by path
+pub fn compare(old: &Snapshot, new: &Snapshot) -> Vec<FilePair> {
+    let mut pairs = Vec::new();
+    for (path, before) in old {
+        match new.get(path) {
+            Some(after) if after.sha == before.sha && after.mode == before.mode => {}
+            after => pairs.push(FilePair {
+                old: Some(before.clone()),
+                new: after.cloned(),
+                ..FilePair::default()
+            }),
+        }
+    }
+    for (path, after) in new {
+        if !old.contains_key(path) {
+            pairs.push(FilePair {
+                new: Some(after.clone()),
+                ..FilePair::default()
+            });
+        }
+    }
+    pairs.sort_by(|a, b| a.path().cmp(b.path()));
+    pairs
+}
+
+/// One `--name-status` line: the status letter (with the similarity for a
+/// rename or copy) and the path(s), tab-separated
+pub fn format_name_status(pair: &FilePair) -> String {
+    match (pair.status(), &pair.old, &pair.new) {
+        (status @ ('R' | 'C'), Some(old), Some(new)) => format!(
+            "{}{:03}\t{}\t{}",
+            status,
+            pair.similarity(),
+            old.path,
+            new.path
+        ),
+        (status, _, _) => format!("{}\t{}", status, pair.path()),
+    }
+}
+
+/// The full "diff --git" section for one changed file
+pub fn format_patch(pair: &FilePair, options: &PatchOptions) -> Vec<u8> {
+    let old_name = pair.old.as_ref().map(|side| format!("a/{}", side.path));
+    let new_name = pair.new.as_ref().map(|side| format!("b/{}", side.path));
+    let old_path = old_name
+        .clone()
+        .unwrap_or_else(|| format!("a/{}", pair.path()));
+    let new_path = new_name
+        .clone()
+        .unwrap_or_else(|| format!("b/{}", pair.path()));
+    let abbrev = |side: &Option<Side>| match side {
+        Some(side) => side.sha[..7].to_string(),
+        None => "0000000".to_string(),
+    };
+    let colors = options.colors.clone().unwrap_or_default();
+
+    let mut header = vec![format!("diff --git {} {}", old_path, new_path)];
+    match (&pair.old, &pair.new) {
+        (None, Some(new)) => header.push(format!("new file mode {}", new.mode)),
+        (Some(old), None) => header.push(format!("deleted file mode {}", old.mode)),
+        (Some(old), Some(new)) if old.mode != new.mode => {
+            header.push(format!("old mode {}", old.mode));
+            header.push(format!("new mode {}", new.mode));
+        }
+        _ => {}
+    }
+    if let (Some(old), Some(new)) = (&pair.old, &pair.new)
+        && old.path != new.path
+    {
+        let how = if pair.copy { "copy" } else { "rename" };
+        header.push(format!("similarity index {}%", pair.similarity()));
+        header.push(format!("{} from {}", how, old.path));
+        header.push(format!("{} to {}", how, new.path));
+    }
+    let same_content =
+        matches!((&pair.old, &pair.new), (Some(old), Some(new)) if old.sha == new.sha);
+    if !same_content {
+        let mut index = format!("index {}..{}", abbrev(&pair.old), abbrev(&pair.new));
+        if let (Some(old), Some(new)) = (&pair.old, &pair.new)
+            && old.mode == new.mode
+        {
+            index += &format!(" {}", old.mode);
+        }
+        header.push(index);
+    }
+
+    let old_data = pair.old.as_ref().map(Side::content).unwrap_or_default();
+    let new_data = pair.new.as_ref().map(Side::content).unwrap_or_default();
+    let old_label = old_name.unwrap_or_else(|| "/dev/null".to_string());
+    let new_label = new_name.unwrap_or_else(|| "/dev/null".to_string());
+    let binary = is_binary(&old_data) || is_binary(&new_data);
+    let old_lines = split_lines(&old_data);
+    let new_lines = split_lines(&new_data);
+    let hunks = match same_content || binary {
+        true => Vec::new(),
+        false => hunks(
+            &diff_lines(&old_lines, &new_lines, options.algorithm),
+            options.context,
+        ),
+    };
+    // like git, no "---"/"+++" without a hunk to follow, as for an empty new file
+    if !hunks.is_empty() {
+        header.push(format!("--- {}", old_label));
+        header.push(format!("+++ {}", new_label));
+    }
+
+    let mut out = String::new();
+    for line in header {
+        out += &format!("{}{}{}\n", colors.meta, line, colors.reset);
+    }
+    if same_content {
+        return out.into_bytes();
+    }
+    if binary {
+        out += &format!("Binary files {} and {} differ\n", old_label, new_label);
+        return out.into_bytes();
+    }
+
+    let mut bytes = out.into_bytes();
+    let eof = blank_at_eof(&old_data, &new_data);
+    for hunk in hunks {
+        match options.word_diff {
+            Some(mode) => word_diff::format_hunk(
+                &mut bytes,
+                &hunk,
+                &old_lines,
+                &new_lines,
+                mode,
+                options.word_regex.as_ref(),
+                &colors,
+            ),
+            None => format_hunk(&mut bytes, &hunk, &old_lines, &new_lines, &colors, eof),
+        }
+    }
+    bytes
+}
@@ -0,0 +1,470 @@
+// diffstat: how bad is it, in one screenful
+
+use crate::utils::config;
+use crate::utils::diff::{self, Algorithm, DiffColors, Edit, FilePair, Side};
+use crate::utils::renames;
+use terminal_size::{Width, terminal_size};
+
+/// How much one file changed
+pub struct FileStat {
+    /// The path, or "dir/{old => new}" for a rename or copy
+    pub name: String,
+    /// Lines added and removed; for binary files, the new and old sizes in bytes
+    pub added: usize,
+    pub deleted: usize,
+    pub binary: bool,
+}
+
+/// Count the changes in each pair (in the same order)
+pub fn file_stats(pairs: &[FilePair], algorithm: Algorithm) -> Vec<FileStat> {
+    pairs
+        .iter()
+        .map(|pair| file_stat(pair, algorithm))
+        .collect()
+}
+
+fn file_stat(pair: &FilePair, algorithm: Algorithm) -> FileStat {
+    let name = match (&pair.old, &pair.new) {
+        (Some(old), Some(new)) if old.path != new.path => rename_name(&old.path, &new.path),
+        _ => pair.path().to_string(),
+    };
+    let old = pair.old.as_ref().map(Side::content).unwrap_or_default();
+    let new = pair.new.as_ref().map(Side::content).unwrap_or_default();
+    let binary = diff::is_binary(&old) || diff::is_binary(&new);
+    let same = matches!((&pair.old, &pair.new), (Some(a), Some(b)) if a.sha == b.sha);
+
+    let (added, deleted) = if same {
+        (0, 0)
+    } else if binary {
+        (new.len(), old.len())
+    } else {
+        let script = diff::diff_lines(
+            &diff::split_lines(&old),
+            &diff::split_lines(&new),
+            algorithm,
+        );
+        let added = script
+            .iter()
+            .filter(|e| matches!(e, Edit::Insert(_)))
+            .count();
+        let deleted = script
+            .iter()
+            .filter(|e| matches!(e, Edit::Delete(_)))
+            .count();
+        (added, deleted)
+    };
+    FileStat {
+        name,
+        added,
+        deleted,
+        binary,
+    }
+}
+
+/// "old => new", with the directories and trailing path both share pulled
+/// out like git does: "src/{a => b}/main.rs"
+pub fn rename_name(old: &str, new: &str) -> String {
+    let (a, b) = (old.as_bytes(), new.as_bytes());
+
+    // common leading directories
+    let mut prefix = 0;
+    let mut k = 0;
+    while k < a.len() && k < b.len() && a[k] == b[k] {
+        if a[k] == b'/' {
+            prefix = k + 1;
+        }
+        k += 1;
+    }
+
+    // common trailing path, starting at a slash; this may look one byte back
+    // into the prefix to find the slash it ends with
+    let byte = |s: &[u8], i: isize| s.get(i as usize).copied().unwrap_or(0);
+    let stop = prefix as isize - if prefix > 0 { 1 } else { 0 };
+    let (mut i, mut j) = (a.len() as isize, b.len() as isize);
+    let mut suffix = 0;
+    while stop <= i && stop <= j && byte(a, i) == byte(b, j) {
+        if byte(a, i) == b'/' {
+            suffix = a.len() - i as usize;
+        }
+        i -= 1;
+        j -= 1;
+    }
+
+    if prefix + suffix == 0 {
+        return format!("{} => {}", old, new);
+    }
+    let a_mid = a.len().saturating_sub(prefix + suffix);
+    let b_mid = b.len().saturating_sub(prefix + suffix);
+    format!(
+        "{}{{{} => {}}}{}",
+        &old[..prefix],
+        &old[prefix..prefix + a_mid],
+        &new[prefix..prefix + b_mid],
+        &old[old.len() - suffix..]
+    )
+}
+
+/// Layout for `--stat[=<width>[,<name-width>[,<count>]]]`
+#[derive(Clone, Copy, Default)]
+pub struct StatOptions {
+    /// Total width; the terminal's ($COLUMNS), or 80
+    pub width: Option<usize>,
+    /// Most columns to give to file names
+    pub name_width: Option<usize>,
+    /// Most files to list
+    pub count: Option<usize>,
+}
+
+impl StatOptions {
+    /// Read the part after `--stat=`; empty fields keep their default
+    pub fn parse(spec: &str) -> Option<Self> {
+        let fields: Vec<Option<usize>> = spec
+            .split(',')
+            .map(|field| match field {
+                "" => Some(None),
+                n => n.parse().ok().map(Some),
+            })
+            .collect::<Option<_>>()?;
+        if fields.len() > 3 {
+            return None;
+        }
+        Some(StatOptions {
+            width: fields.first().copied().flatten(),
+            name_width: fields.get(1).copied().flatten(),
+            count: fields.get(2).copied().flatten(),
+        })
+    }
+}
+
+/// $COLUMNS, or 80
+/// How wide stdout is: the terminal's own width if it is one, else `$COLUMNS`,
+/// else 80
+fn terminal_width() -> usize {
+    if let Some((Width(width), _)) = terminal_size()
+        && width > 0
+    {
+        return width as usize;
+    }
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .filter(|&columns| columns > 0)
+        .unwrap_or(80)
+}
+
+fn digits(n: usize) -> usize {
+    n.to_string().len()
+}
+
+/// git's `--stat`: a line per file with a +/- bar scaled to fit the width,
+/// then the summary line
+pub fn format_stat(stats: &[FileStat], options: &StatOptions, colors: &DiffColors) -> String {
+    if stats.is_empty() {
+        return String::new();
+    }
+    let shown = &stats[..options.count.unwrap_or(stats.len()).min(stats.len())];
+
+    let (mut max_len, mut max_change, mut number_width, mut bin_width) = (0, 0, 0, 0);
+    for stat in shown {
+        max_len = max_len.max(stat.name.chars().count());
+        if stat.binary {
+            // "Bin XXX -> YYY bytes", with the count column lined up with "Bin"
+            bin_width = bin_width.max(14 + digits(stat.added) + digits(stat.deleted));
+            number_width = 3;
+        } else {
+            max_change = max_change.max(stat.added + stat.deleted);
+        }
+    }
+    number_width = number_width.max(digits(max_change));
+
+    // signed, as the sums below can go negative on narrow terminals
+    let number = number_width as isize;
+    let width = (options.width.unwrap_or_else(terminal_width) as isize).max(16 + 6 + number);
+    let mut graph_width = if max_change + 4 > bin_width {
+        max_change
+    } else {
+        bin_width - 4
+    } as isize;
+    let mut name_width = options
+        .name_width
+        .filter(|&w| w > 0 && w < max_len)
+        .unwrap_or(max_len) as isize;
+    // too wide: give the graph up to 3/8 of the width and the name the rest
+    if name_width + number + 6 + graph_width > width {
+        if graph_width > width * 3 / 8 - number - 6 {
+            graph_width = (width * 3 / 8 - number - 6).max(6);
+        }
+        if name_width > width - number - 6 - graph_width {
+            name_width = width - number - 6 - graph_width;
+        } else {
+            graph_width = width - number - 6 - name_width;
+        }
+    }
+    let (name_width, graph_width) = (name_width.max(0) as usize, graph_width.max(0) as usize);
+    let scale = |n: usize| match n {
+        0 => 0,
+        n => 1 + n * graph_width.saturating_sub(1) / max_change,
+    };
+
+    let mut out = String::new();
+    for stat in shown {
+        // names that don't fit lose their start, back to a slash if there is one
+        let chars: Vec<char> = stat.name.chars().collect();
+        let (prefix, name, len) = if chars.len() > name_width {
+            let len = name_width.saturating_sub(3);
+            let tail: String = chars[chars.len() - len..].iter().collect();
+            let name = match tail.find('/') {
+                Some(slash) => tail[slash..].to_string(),
+                None => tail,
+            };
+            ("...", name, len)
+        } else {
+            ("", stat.name.clone(), name_width)
+        };
+        let padding = " ".repeat(len.saturating_sub(name.chars().count()));
+
+        if stat.binary {
+            out += &format!(
+                " {}{}{} | {:>width$}",
+                prefix,
+                name,
+                padding,
+                "Bin",
+                width = number_width
+            );
+            if stat.added + stat.deleted > 0 {
+                out += &format!(
+                    " {}{}{} -> {}{}{} bytes",
+                    colors.old, stat.deleted, colors.reset, colors.new, stat.added, colors.reset
+                );
+            }
+            out.push('\n');
+            continue;
+        }
+
+        let (mut add, mut del) = (stat.added, stat.deleted);
+        if graph_width <= max_change {
+            let mut total = scale(add + del);
+            if total < 2 && add > 0 && del > 0 {
+                total = 2;
+            }
+            if add < del {
+                add = scale(add);
+                del = total - add;
+            } else {
+                del = scale(del);
+                add = total - del;
+            }
+        }
+        let changes = stat.added + stat.deleted;
+        let graph = |sign: &str, count: usize, color: &str| match count {
+            0 => String::new(),
+            _ => format!("{}{}{}", color, sign.repeat(count), colors.reset),
+        };
+        out += &format!(
+            " {}{}{} | {:>width$}{}{}{}\n",
+            prefix,
+            name,
+            padding,
+            changes,
+            if changes > 0 { " " } else { "" },
+            graph("+", add, &colors.new),
+            graph("-", del, &colors.old),
+            width = number_width
+        );
+    }
+    if shown.len() < stats.len() {
+        out += " ...\n";
+    }
+    out + &format_shortstat(stats)
+}
+
+/// The "N files changed, X insertions(+), Y deletions(-)" line
+pub fn format_shortstat(stats: &[FileStat]) -> String {
+    if stats.is_empty() {
+        return String::new();
+    }
+    let lines = |count: fn(&FileStat) -> usize| -> usize {
+        stats.iter().filter(|stat| !stat.binary).map(count).sum()
+    };
+    let insertions = lines(|stat| stat.added);
+    let deletions = lines(|stat| stat.deleted);
+    let plural = |n: usize| if n == 1 { "" } else { "s" };
+
+    let mut out = format!(" {} file{} changed", stats.len(), plural(stats.len()));
+    if insertions > 0 || deletions == 0 {
+        out += &format!(", {} insertion{}(+)", insertions, plural(insertions));
+    }
+    if deletions > 0 || insertions == 0 {
+        out += &format!(", {} deletion{}(-)", deletions, plural(deletions));
+    }
+    out + "\n"
+}
+
+/// git's `--numstat`: added and deleted lines and the name, tab-separated
+/// ("-" for binary files)
+pub fn format_numstat(stats: &[FileStat]) -> String {
+    stats
+        .iter()
+        .map(|stat| match stat.binary {
+            true => format!("-\t-\t{}\n", stat.name),
+            false => format!("{}\t{}\t{}\n", stat.added, stat.deleted, stat.name),
+        })
+        .collect()
+}
+
+/// What `--dirstat` measures
+#[derive(Clone, Copy, PartialEq)]
+pub enum DirstatBy {
+    /// Bytes removed from or added to each file, going by lines
+    Changes,
+    /// Lines added plus lines removed (64-byte chunks for binary files)
+    Lines,
+    /// Just the number of files changed
+    Files,
+}
+
+/// Settings for `--dirstat[=<param>,...]`
+#[derive(Clone, Copy)]
+pub struct DirstatOptions {
+    pub by: DirstatBy,
+    /// Count subdirectories' changes towards their parents even when they're listed themselves
+    pub cumulative: bool,
+    /// The share of all changes a directory needs to be listed, in tenths of a percent
+    pub permille: usize,
+}
+
+impl Default for DirstatOptions {
+    /// `diff.dirstat` on top of "changes,noncumulative,3"
+    fn default() -> Self {
+        let defaults = DirstatOptions {
+            by: DirstatBy::Changes,
+            cumulative: false,
+            permille: 30,
+        };
+        match config::get("diff.dirstat") {
+            Some(params) => defaults.with(&params).unwrap_or_else(|| {
+                eprintln!(
+                    "warning: unknown value for config 'diff.dirstat': {}",
+                    params
+                );
+                defaults
+            }),
+            None => defaults,
+        }
+    }
+}
+
+impl DirstatOptions {
+    /// Apply comma-separated parameters: changes, lines, files, cumulative,
+    /// noncumulative, or a limit in percent ("10", "2.5")
+    pub fn with(mut self, params: &str) -> Option<Self> {
+        for param in params.split(',').filter(|param| !param.is_empty()) {
+            match param {
+                "changes" => self.by = DirstatBy::Changes,
+                "lines" => self.by = DirstatBy::Lines,
+                "files" => self.by = DirstatBy::Files,
+                "cumulative" => self.cumulative = true,
+                "noncumulative" => self.cumulative = false,
+                limit => {
+                    // like git, only the first decimal counts
+                    let (whole, fraction) = limit.split_once('.').unwrap_or((limit, ""));
+                    if !fraction.chars().all(|c| c.is_ascii_digit()) {
+                        return None;
+                    }
+                    let tenths = fraction
+                        .chars()
+                        .next()
+                        .map_or(0, |c| c as usize - '0' as usize);
+                    self.permille = whole.parse::<usize>().ok()? * 10 + tenths;
+                }
+            }
+        }
+        Some(self)
+    }
+}
+
+/// git's `--dirstat`: the share of the changes in each directory, for those
+/// above the limit (`stats` must line up with `pairs`, and is only needed
+/// when counting lines)
+pub fn format_dirstat(pairs: &[FilePair], stats: &[FileStat], options: &DirstatOptions) -> String {
+    let mut files: Vec<(&str, usize)> = Vec::new();
+    for (k, pair) in pairs.iter().enumerate() {
+        let damage = match options.by {
+            DirstatBy::Lines => {
+                let stat = &stats[k];
+                match stat.binary {
+                    true => (stat.added + stat.deleted).div_ceil(64),
+                    false => stat.added + stat.deleted,
+                }
+            }
+            _ => match (&pair.old, &pair.new) {
+                (Some(old), Some(new)) if old.sha == new.sha => continue,
+                _ if options.by == DirstatBy::Files => 1,
+                (Some(old), Some(new)) => {
+                    let (old, new) = (old.content(), new.content());
+                    let (copied, added) = renames::count_changes(&old, &new);
+                    // it did change, even if not by whole lines
+                    (old.len().saturating_sub(copied) + added).max(1)
+                }
+                (Some(side), None) | (None, Some(side)) => side.content().len().max(1),
+                (None, None) => continue,
+            },
+        };
+        files.push((pair.path(), damage));
+    }
+    let total: usize = files.iter().map(|(_, damage)| damage).sum();
+    if total == 0 {
+        return String::new();
+    }
+    files.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut out = String::new();
+    let mut next = 0;
+    gather_dirstat(&files, &mut next, "", total, options, &mut out);
+    out
+}
+
+/// Add up the changes under `base`, listing each directory (deepest first)
+/// that has enough of them, and return what the parent should count
+fn gather_dirstat(
+    files: &[(&str, usize)],
+    next: &mut usize,
+    base: &str,
+    total: usize,
+    options: &DirstatOptions,
+    out: &mut String,
+) -> usize {
+    let mut sum = 0;
+    // like git: a subdirectory counts once, a file twice, so that a directory
+    // whose changes are all in one subdirectory isn't listed twice
+    let mut sources = 0;
+    while let Some(&(name, damage)) = files.get(*next) {
+        if !name.starts_with(base) {
+            break;
+        }
+        match name[base.len()..].find('/') {
+            Some(slash) => {
+                let dir = &name[..base.len() + slash + 1];
+                sum += gather_dirstat(files, next, dir, total, options, out);
+                sources += 1;
+            }
+            None => {
+                sum += damage;
+                *next += 1;
+                sources += 2;
+            }
+        }
+    }
+
+    if !base.is_empty() && sources != 1 && sum > 0 {
+        let permille = sum * 1000 / total;
+        if permille >= options.permille {
+            *out += &format!("{:4}.{}% {}\n", permille / 10, permille % 10, base);
+            if !options.cumulative {
+                return 0;
+            }
+        }
+    }
+    sum
+}
@@ -0,0 +1,44 @@
+// editors: the one holy war hit refuses to take a side in
+
+use crate::utils::config;
+use std::path::Path;
+use std::process::Command;
+
+/// Pick the editor the same way git does, with HIT_EDITOR in place of GIT_EDITOR
+pub fn editor_command() -> String {
+    std::env::var("HIT_EDITOR")
+        .ok()
+        .or_else(|| config::get("core.editor"))
+        .or_else(|| std::env::var("VISUAL").ok())
+        .or_else(|| std::env::var("EDITOR").ok())
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string())
+}
+
+/// Open `path` in the user's editor and wait for it to exit
+pub fn launch_editor(path: &Path) -> Result<(), String> {
+    let editor = editor_command();
+    // ":" is the conventional "don't actually edit anything" editor
+    if editor == ":" {
+        return Ok(());
+    }
+
+    // run through the shell so editors with arguments ("code --wait") work
+    #[cfg(windows)]
+    let status = Command::new("cmd")
+        .arg("/C")
+        .arg(format!("{} \"{}\"", editor, path.display()))
+        .status();
+    #[cfg(not(windows))]
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$@\"", editor))
+        .arg(&editor)
+        .arg(path)
+        .status();
+
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        _ => Err(format!("there was a problem with the editor '{}'", editor)),
+    }
+}
@@ -0,0 +1,7 @@
+// fatal: the last words of a command that has given up
+
+/// Print "fatal: <message>" and exit with 128, as git does
+pub fn fatal(message: &str) -> ! {
+    eprintln!("fatal: {}", message);
+    std::process::exit(128);
+}
@@ -0,0 +1,615 @@
+// graph: ascii art for people who think in branches
+
+/// The colors lanes cycle through, like git's default `log.graphColors`
+const COLUMN_COLORS: [&str; 12] = [
+    "\x1b[31m",
+    "\x1b[32m",
+    "\x1b[33m",
+    "\x1b[34m",
+    "\x1b[35m",
+    "\x1b[36m",
+    "\x1b[1;31m",
+    "\x1b[1;32m",
+    "\x1b[1;33m",
+    "\x1b[1;34m",
+    "\x1b[1;35m",
+    "\x1b[1;36m",
+];
+
+/// Which kind of line comes next
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    /// Every lane carries on straight down
+    Padding,
+    /// "...", when a commit starts before the last one finished
+    Skip,
+    /// Room made for an octopus merge's lanes
+    PreCommit,
+    /// The line with the commit's '*'
+    Commit,
+    /// A merge's lanes branching off
+    PostMerge,
+    /// Lanes moving left to where they belong
+    Collapsing,
+}
+
+/// A lane, waiting for the commit it leads to
+#[derive(Clone)]
+struct Column {
+    commit: String,
+    /// Index into COLUMN_COLORS, or None when uncolored
+    color: Option<usize>,
+}
+
+/// One line of the graph, with its width on screen (colors take up none)
+#[derive(Default)]
+struct Line {
+    text: String,
+    width: usize,
+}
+
+impl Line {
+    fn add(&mut self, c: char) {
+        self.text.push(c);
+        self.width += 1;
+    }
+
+    fn add_spaces(&mut self, n: usize) {
+        self.text.extend(std::iter::repeat_n(' ', n));
+        self.width += n;
+    }
+
+    fn write_column(&mut self, column: &Column, c: char) {
+        match column.color {
+            Some(color) => {
+                self.text.push_str(COLUMN_COLORS[color]);
+                self.add(c);
+                self.text.push_str(crate::utils::color::RESET);
+            }
+            None => self.add(c),
+        }
+    }
+}
+
+/// The lanes drawn down the left of `log --graph`, a port of git's graph.c:
+/// call `update` for each commit shown, then take lines off until the
+/// commit is finished
+pub struct Graph {
+    colored: bool,
+    commit: String,
+    /// The parents of `commit` that are shown too
+    parents: Vec<String>,
+    /// Screen columns the lanes take up on this commit's lines
+    width: usize,
+    /// How many pre-commit lines have been written
+    expansion_row: usize,
+    state: State,
+    prev_state: State,
+    /// Which lane the commit is in
+    commit_index: usize,
+    prev_commit_index: usize,
+    /// How a merge's first parent sits relative to it: 0 to the left, 1 below
+    merge_layout: usize,
+    /// Lanes added to the right by this commit (-1 when a merge's edges join
+    /// straight away)
+    edges_added: isize,
+    prev_edges_added: isize,
+    /// Lanes before and after this commit
+    columns: Vec<Column>,
+    new_columns: Vec<Column>,
+    /// For each screen column (two per lane), which new lane goes through it
+    mapping: Vec<Option<usize>>,
+    old_mapping: Vec<Option<usize>>,
+    /// The color the next new lane gets
+    default_color: usize,
+}
+
+impl Graph {
+    pub fn new(colored: bool) -> Self {
+        Graph {
+            colored,
+            commit: String::new(),
+            parents: Vec::new(),
+            width: 0,
+            expansion_row: 0,
+            state: State::Padding,
+            prev_state: State::Padding,
+            commit_index: 0,
+            prev_commit_index: 0,
+            merge_layout: 0,
+            edges_added: 0,
+            prev_edges_added: 0,
+            columns: Vec::new(),
+            new_columns: Vec::new(),
+            mapping: Vec::new(),
+            old_mapping: Vec::new(),
+            default_color: COLUMN_COLORS.len() - 1,
+        }
+    }
+
+    /// Move on to the next commit shown, with those of its parents that are shown too
+    pub fn update(&mut self, commit: &str, parents: &[String]) {
+        self.commit = commit.to_string();
+        self.parents.clear();
+        for parent in parents {
+            if !self.parents.contains(parent) {
+                self.parents.push(parent.clone());
+            }
+        }
+        self.prev_commit_index = self.commit_index;
+        self.update_columns();
+        self.expansion_row = 0;
+        self.state = if self.state != State::Padding {
+            State::Skip
+        } else if self.needs_pre_commit_line() {
+            State::PreCommit
+        } else {
+            State::Commit
+        };
+    }
+
+    /// Everything about the commit has been drawn
+    pub fn is_finished(&self) -> bool {
+        self.state == State::Padding
+    }
+
+    /// The next line, and whether it is the one with the commit on it
+    pub fn next_line(&mut self) -> (String, bool) {
+        let mut line = Line::default();
+        let mut commit_line = false;
+        match self.state {
+            State::Padding => {
+                for column in &self.new_columns {
+                    line.write_column(column, '|');
+                    line.add(' ');
+                }
+            }
+            State::Skip => {
+                line.text.push_str("...");
+                line.width += 3;
+                let state = match self.needs_pre_commit_line() {
+                    true => State::PreCommit,
+                    false => State::Commit,
+                };
+                self.set_state(state);
+            }
+            State::PreCommit => self.pre_commit_line(&mut line),
+            State::Commit => {
+                self.commit_line(&mut line);
+                commit_line = true;
+            }
+            State::PostMerge => self.post_merge_line(&mut line),
+            State::Collapsing => self.collapsing_line(&mut line),
+        }
+        self.pad(&mut line);
+        (line.text, commit_line)
+    }
+
+    /// A line that carries the lanes on without moving the graph along, for
+    /// blank lines and changes under a commit
+    pub fn padding_line(&mut self) -> String {
+        if self.state != State::Commit {
+            return self.next_line().0;
+        }
+        let mut line = Line::default();
+        for column in &self.columns {
+            line.write_column(column, '|');
+            if column.commit == self.commit && self.parents.len() > 2 {
+                line.add_spaces((self.parents.len() - 2) * 2);
+            } else {
+                line.add(' ');
+            }
+        }
+        self.pad(&mut line);
+        self.prev_state = State::Padding;
+        line.text
+    }
+
+    /// The lines up to and including the commit's own, the last without its newline
+    pub fn show_commit(&mut self, out: &mut String) {
+        if self.is_finished() {
+            out.push_str(&self.padding_line());
+            return;
+        }
+        let mut commit_line = false;
+        while !commit_line && !self.is_finished() {
+            let line;
+            (line, commit_line) = self.next_line();
+            out.push_str(&line);
+            if !commit_line {
+                out.push('\n');
+            }
+        }
+    }
+
+    /// Whatever is left of the commit's lines, the last without its newline
+    pub fn show_remainder(&mut self, out: &mut String) {
+        while !self.is_finished() {
+            out.push_str(&self.next_line().0);
+            if !self.is_finished() {
+                out.push('\n');
+            }
+        }
+    }
+
+    /// `text` with the graph down its left, carrying on from the commit line:
+    /// the first line goes straight after what's already there
+    pub fn show_text(&mut self, out: &mut String, text: &str) {
+        let mut lines = text.split_inclusive('\n').peekable();
+        while let Some(line) = lines.next() {
+            out.push_str(line);
+            if lines.peek().is_some() {
+                out.push_str(&self.next_line().0);
+            }
+        }
+        if !self.is_finished() {
+            let terminated = text.ends_with('\n');
+            if !terminated {
+                out.push('\n');
+            }
+            self.show_remainder(out);
+            if terminated {
+                out.push('\n');
+            }
+        }
+    }
+
+    fn set_state(&mut self, state: State) {
+        self.prev_state = self.state;
+        self.state = state;
+    }
+
+    fn pad(&self, line: &mut Line) {
+        if line.width < self.width {
+            line.add_spaces(self.width - line.width);
+        }
+    }
+
+    fn current_color(&self) -> Option<usize> {
+        self.colored.then_some(self.default_color)
+    }
+
+    fn increment_color(&mut self) {
+        self.default_color = (self.default_color + 1) % COLUMN_COLORS.len();
+    }
+
+    fn find_new_column(&self, commit: &str) -> Option<usize> {
+        self.new_columns.iter().position(|c| c.commit == commit)
+    }
+
+    fn num_dashed_parents(&self) -> usize {
+        (self.parents.len() + self.merge_layout).saturating_sub(3)
+    }
+
+    fn needs_pre_commit_line(&self) -> bool {
+        self.parents.len() >= 3
+            && self.commit_index + 1 < self.columns.len()
+            && self.expansion_row < self.num_dashed_parents() * 2
+    }
+
+    /// Every lane is where it belongs, so nothing needs collapsing
+    fn is_mapping_correct(&self) -> bool {
+        self.mapping
+            .iter()
+            .enumerate()
+            .all(|(i, target)| target.is_none_or(|target| target == i / 2))
+    }
+
+    /// Work out the lanes below this commit: its parents take its place
+    fn update_columns(&mut self) {
+        std::mem::swap(&mut self.columns, &mut self.new_columns);
+        self.new_columns.clear();
+
+        let max_new_columns = self.columns.len() + self.parents.len();
+        self.mapping = vec![None; 2 * max_new_columns];
+        self.width = 0;
+        self.prev_edges_added = self.edges_added;
+        self.edges_added = 0;
+
+        let mut seen_this = false;
+        let mut in_columns = true;
+        for i in 0..=self.columns.len() {
+            let commit = if i == self.columns.len() {
+                if seen_this {
+                    break;
+                }
+                in_columns = false;
+                self.commit.clone()
+            } else {
+                self.columns[i].commit.clone()
+            };
+
+            if commit == self.commit {
+                seen_this = true;
+                self.commit_index = i;
+                self.merge_layout = usize::MAX;
+                for parent in self.parents.clone() {
+                    // a merge, or a lane with nothing above it, gets a new color
+                    if self.parents.len() > 1 || !in_columns {
+                        self.increment_color();
+                    }
+                    self.insert_into_new_columns(&parent, Some(i));
+                }
+                // the commit takes up two columns even with no parents
+                if self.parents.is_empty() {
+                    self.width += 2;
+                }
+            } else {
+                self.insert_into_new_columns(&commit, None);
+            }
+        }
+
+        while self.mapping.len() > 1 && self.mapping.last() == Some(&None) {
+            self.mapping.pop();
+        }
+    }
+
+    fn insert_into_new_columns(&mut self, commit: &str, index: Option<usize>) {
+        let i = match self.find_new_column(commit) {
+            Some(i) => i,
+            None => {
+                let color = self
+                    .columns
+                    .iter()
+                    .find(|column| column.commit == commit)
+                    .map_or_else(|| self.current_color(), |column| column.color);
+                self.new_columns.push(Column {
+                    commit: commit.to_string(),
+                    color,
+                });
+                self.new_columns.len() - 1
+            }
+        };
+
+        let mapping_index;
+        if let Some(index) = index
+            && self.parents.len() > 1
+            && self.merge_layout == usize::MAX
+        {
+            // a merge's first parent: lay the merge out depending on whether
+            // that parent is in a lane to its left
+            let dist = index as isize - i as isize;
+            let shift = if dist > 1 { 2 * dist - 3 } else { 1 };
+            self.merge_layout = if dist > 0 { 0 } else { 1 };
+            self.edges_added = self.parents.len() as isize + self.merge_layout as isize - 2;
+            mapping_index =
+                (self.width as isize + (self.merge_layout as isize - 1) * shift) as usize;
+            self.width += 2 * self.merge_layout;
+        } else if self.edges_added > 0 && self.width >= 2 && self.mapping[self.width - 2] == Some(i)
+        {
+            // the merge's edges join the lane just to the left straight away
+            mapping_index = self.width - 2;
+            self.edges_added = -1;
+        } else {
+            mapping_index = self.width;
+            self.width += 2;
+        }
+        self.mapping[mapping_index] = Some(i);
+    }
+
+    fn pre_commit_line(&mut self, line: &mut Line) {
+        let mut seen_this = false;
+        for (i, column) in self.columns.iter().enumerate() {
+            if column.commit == self.commit {
+                seen_this = true;
+                line.write_column(column, '|');
+                line.add_spaces(self.expansion_row);
+            } else if seen_this && self.expansion_row == 0 {
+                // lanes that ended in '\' after a merge carry on that way
+                let c = match self.prev_state == State::PostMerge && self.prev_commit_index < i {
+                    true => '\\',
+                    false => '|',
+                };
+                line.write_column(column, c);
+            } else if seen_this {
+                line.write_column(column, '\\');
+            } else {
+                line.write_column(column, '|');
+            }
+            line.add(' ');
+        }
+        self.expansion_row += 1;
+        if !self.needs_pre_commit_line() {
+            self.set_state(State::Commit);
+        }
+    }
+
+    fn commit_line(&mut self, line: &mut Line) {
+        let mut seen_this = false;
+        for i in 0..=self.columns.len() {
+            let commit = match self.columns.get(i) {
+                Some(column) => &column.commit,
+                None if seen_this => break,
+                None => &self.commit,
+            };
+            if *commit == self.commit {
+                seen_this = true;
+                line.add('*');
+                if self.parents.len() > 2 {
+                    self.draw_octopus_merge(line);
+                }
+            } else if seen_this && self.edges_added > 1 {
+                line.write_column(&self.columns[i], '\\');
+            } else if seen_this && self.edges_added == 1 {
+                // after a merge's '\', keep going that way rather than '|'
+                let c = match self.prev_state == State::PostMerge
+                    && self.prev_edges_added > 0
+                    && self.prev_commit_index < i
+                {
+                    true => '\\',
+                    false => '|',
+                };
+                line.write_column(&self.columns[i], c);
+            } else if self.prev_state == State::Collapsing
+                && self.old_mapping.get(2 * i + 1) == Some(&Some(i))
+                && self
+                    .mapping
+                    .get(2 * i)
+                    .copied()
+                    .flatten()
+                    .is_none_or(|m| m < i)
+            {
+                line.write_column(&self.columns[i], '/');
+            } else {
+                line.write_column(&self.columns[i], '|');
+            }
+            line.add(' ');
+        }
+
+        let state = if self.parents.len() > 1 {
+            State::PostMerge
+        } else if self.is_mapping_correct() {
+            State::Padding
+        } else {
+            State::Collapsing
+        };
+        self.set_state(state);
+    }
+
+    fn draw_octopus_merge(&self, line: &mut Line) {
+        let dashed = self.num_dashed_parents();
+        for i in 0..dashed {
+            let Some(Some(j)) = self.mapping.get((self.commit_index + i + 2) * 2) else {
+                continue;
+            };
+            let column = &self.new_columns[*j];
+            line.write_column(column, '-');
+            line.write_column(column, if i + 1 == dashed { '.' } else { '-' });
+        }
+    }
+
+    fn post_merge_line(&mut self, line: &mut Line) {
+        const MERGE_CHARS: [char; 3] = ['/', '|', '\\'];
+        let first_parent = self.parents[0].clone();
+        let mut parent_column: Option<Column> = None;
+        let mut seen_this = false;
+        for i in 0..=self.columns.len() {
+            let commit = match self.columns.get(i) {
+                Some(column) => column.commit.clone(),
+                None if seen_this => break,
+                None => self.commit.clone(),
+            };
+            if commit == self.commit {
+                seen_this = true;
+                let mut idx = self.merge_layout;
+                for (j, parent) in self.parents.iter().enumerate() {
+                    let Some(column) = self.find_new_column(parent) else {
+                        continue;
+                    };
+                    line.write_column(&self.new_columns[column], MERGE_CHARS[idx]);
+                    if idx == 2 {
+                        if self.edges_added > 0 || j + 1 < self.parents.len() {
+                            line.add(' ');
+                        }
+                    } else {
+                        idx += 1;
+                    }
+                }
+                if self.edges_added == 0 {
+                    line.add(' ');
+                }
+            } else if seen_this {
+                let c = if self.edges_added > 0 { '\\' } else { '|' };
+                line.write_column(&self.columns[i], c);
+                line.add(' ');
+            } else {
+                line.write_column(&self.columns[i], '|');
+                if self.merge_layout != 0 || i + 1 != self.commit_index {
+                    match &parent_column {
+                        Some(column) => line.write_column(column, '_'),
+                        None => line.add(' '),
+                    }
+                }
+            }
+            if commit == first_parent {
+                parent_column = self.columns.get(i).cloned();
+            }
+        }
+
+        let state = match self.is_mapping_correct() {
+            true => State::Padding,
+            false => State::Collapsing,
+        };
+        self.set_state(state);
+    }
+
+    fn collapsing_line(&mut self, line: &mut Line) {
+        let mut used_horizontal = false;
+        let mut horizontal_edge: Option<usize> = None;
+        let mut horizontal_edge_target: Option<usize> = None;
+
+        std::mem::swap(&mut self.mapping, &mut self.old_mapping);
+        self.mapping = vec![None; self.old_mapping.len()];
+
+        for i in 0..self.old_mapping.len() {
+            let Some(target) = self.old_mapping[i] else {
+                continue;
+            };
+            // lanes only ever move left, towards where they belong
+            if target * 2 == i {
+                self.mapping[i] = Some(target);
+            } else if self.mapping[i - 1].is_none() {
+                // nothing to the left, so move left by one
+                self.mapping[i - 1] = Some(target);
+                if horizontal_edge.is_none() {
+                    horizontal_edge = Some(i);
+                    horizontal_edge_target = Some(target);
+                    let mut j = target * 2 + 3;
+                    while j + 2 < i {
+                        self.mapping[j] = Some(target);
+                        j += 2;
+                    }
+                }
+            } else if self.mapping[i - 1] == Some(target) {
+                // the lane to the left goes to the same commit; join it
+            } else {
+                // cross over the lane to the left
+                self.mapping[i - 2] = Some(target);
+                if horizontal_edge.is_none() {
+                    horizontal_edge_target = Some(target);
+                    horizontal_edge = Some(i - 1);
+                    let mut j = target * 2 + 3;
+                    while j + 2 < i {
+                        self.mapping[j] = Some(target);
+                        j += 2;
+                    }
+                }
+            }
+        }
+
+        self.old_mapping = self.mapping.clone();
+        // the new mapping may be a column shorter
+        if self.mapping.last() == Some(&None) {
+            self.mapping.pop();
+        }
+
+        for i in 0..self.mapping.len() {
+            match self.mapping[i] {
+                None => line.add(' '),
+                Some(target) if target * 2 == i => {
+                    line.write_column(&self.new_columns[target], '|');
+                }
+                Some(target)
+                    if Some(target) == horizontal_edge_target && Some(i + 1) != horizontal_edge =>
+                {
+                    // only the first segment carries on to the next line
+                    if i != target * 2 + 3 {
+                        self.mapping[i] = None;
+                    }
+                    used_horizontal = true;
+                    line.write_column(&self.new_columns[target], '_');
+                }
+                Some(target) => {
+                    if used_horizontal && horizontal_edge.is_some_and(|edge| i < edge) {
+                        self.mapping[i] = None;
+                    }
+                    line.write_column(&self.new_columns[target], '/');
+                }
+            }
+        }
+
+        if self.is_mapping_correct() {
+            self.set_state(State::Padding);
+        }
+    }
+}
@@ -2,12 +2,13 @@
 // a hashbrown is a type of potato.
 // i like potato.
 
+use crate::utils::repo;
 use flate2::Compression;
 use flate2::write::ZlibEncoder;
 use sha1::{Digest, Sha1};
 use std::fs;
 use std::io::Write;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 
 pub fn hash_object(file_path: &str, write: bool, print: bool) -> String {
     // Resolve the absolute path of the file
@@ -37,9 +38,8 @@ pub fn hash_object(file_path: &str, write: bool, print: bool) -> String {
 
     if write {
         // Prepare the path: .git/objects/ab/cdef... based on hash
-        let object_dir = format!(".hit/objects/{}", &hash_hex[..2]);
-        let object_file = hash_hex[2..].to_string();
-        let object_path = PathBuf::from(&object_dir).join(object_file);
+        let object_path = repo::object_path(&hash_hex);
+        let object_dir = object_path.parent().unwrap();
 
         // Skip if the object already exists
         if object_path.exists() {
@@ -47,7 +47,7 @@ pub fn hash_object(file_path: &str, write: bool, print: bool) -> String {
         }
 
         // Ensure the directory exists
-        fs::create_dir_all(&object_dir).expect("Failed to create object directory");
+        fs::create_dir_all(object_dir).expect("Failed to create object directory");
 
         // Compress the object data using zlib
         let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
@@ -64,10 +64,9 @@ pub fn hash_object(file_path: &str, write: bool, print: bool) -> String {
 }
 
 pub fn resolve_head() -> Option<String> {
-    let head = fs::read_to_string(".hit/HEAD").ok()?;
-    if head.starts_with("ref: ") {
-        let ref_path = head[5..].trim();
-        let full_path = Path::new(".hit").join(ref_path);
+    let head = fs::read_to_string(repo::path("HEAD")).ok()?;
+    if let Some(ref_path) = head.strip_prefix("ref: ") {
+        let full_path = repo::path(ref_path.trim());
 
         // Check if the ref file exists
         if !full_path.exists() {
@@ -0,0 +1,77 @@
+// hooks: other people's scripts, run at the worst possible moment
+
+use crate::utils::config;
+use crate::utils::refs::NULL_SHA;
+use crate::utils::repo;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Where hooks live: `core.hooksPath` if set, otherwise `.hit/hooks`
+pub fn hooks_dir() -> PathBuf {
+    config::get_path("core.hooksPath").unwrap_or_else(|| repo::path("hooks"))
+}
+
+/// The hook called `name`, if one is installed and executable
+pub fn find(name: &str) -> Option<PathBuf> {
+    let path = hooks_dir().join(name);
+    if !path.is_file() {
+        return None;
+    }
+    if !is_executable(&path) {
+        eprintln!(
+            "hint: The '{}' hook was ignored because it's not set as executable.",
+            path.display()
+        );
+        return None;
+    }
+    Some(path)
+}
+
+/// Run the hook called `name` (if installed) from the top of the working tree
+///
+/// Like git, hooks see HIT_DIR pointing at the repository, plus whatever
+/// `env` adds. A hook exiting non-zero is an error; it's up to the caller
+/// whether that matters.
+pub fn run(name: &str, args: &[&str], env: &[(&str, String)]) -> Result<(), String> {
+    let Some(path) = find(name) else {
+        return Ok(());
+    };
+
+    let hit_dir = std::path::absolute(repo::hit_dir()).unwrap_or_else(|_| repo::hit_dir());
+    let status = Command::new(&path)
+        .args(args)
+        .env("HIT_DIR", hit_dir)
+        .envs(env.iter().cloned())
+        .status()
+        .map_err(|e| format!("cannot run {} hook '{}': {}", name, path.display(), e))?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("{} hook exited with {}", name, status))
+    }
+}
+
+/// Run post-checkout with the previous and new HEAD (the null SHA when
+/// unborn) and "1" for a branch checkout or "0" for a checkout of paths
+pub fn post_checkout(old: Option<&str>, new: Option<&str>, branch: bool) -> Result<(), String> {
+    let old = old.unwrap_or(NULL_SHA);
+    let new = new.unwrap_or(NULL_SHA);
+    run(
+        "post-checkout",
+        &[old, new, if branch { "1" } else { "0" }],
+        &[],
+    )
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|meta| meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_path: &Path) -> bool {
+    true
+}
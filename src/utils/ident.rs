@@ -0,0 +1,196 @@
+// who did this? (answers range from "you" to "definitely not me")
+
+use crate::utils::config;
+use crate::utils::date;
+use std::env;
+use std::fmt;
+use std::fs;
+
+/// A name, email and point in time, as found on author/committer/tagger lines
+#[derive(Clone)]
+pub struct Ident {
+    pub name: String,
+    pub email: String,
+    pub timestamp: i64,
+    /// UTC offset formatted as "+hhmm"
+    pub timezone: String,
+}
+
+impl fmt::Display for Ident {
+    /// "Name <email> <seconds> <+hhmm>", the form used inside objects and reflogs
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} <{}> {} {}",
+            self.name, self.email, self.timestamp, self.timezone
+        )
+    }
+}
+
+impl Ident {
+    /// Parse "Name <email> <seconds> <+hhmm>" from an object header
+    pub fn parse(value: &str) -> Option<Ident> {
+        let mut parts = value.rsplitn(3, ' ');
+        let timezone = parts.next()?.to_string();
+        let timestamp = parts.next()?.parse().ok()?;
+        let (name, email) = parse_name_email(parts.next()?)?;
+        Some(Ident {
+            name,
+            email,
+            timestamp,
+            timezone,
+        })
+    }
+
+    /// "Name <email>" without the date
+    pub fn name_email(&self) -> String {
+        format!("{} <{}>", self.name, self.email)
+    }
+
+    /// Replace the date with one given to `--date` or an environment variable
+    pub fn set_date(&mut self, value: &str) -> Result<(), String> {
+        let (timestamp, offset) = date::parse(value)?;
+        self.timestamp = timestamp;
+        self.timezone = date::format_timezone(offset);
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Role {
+    Author,
+    Committer,
+}
+
+impl Role {
+    /// Prefix of this role's environment variables and config section
+    fn key(self) -> &'static str {
+        match self {
+            Role::Author => "author",
+            Role::Committer => "committer",
+        }
+    }
+
+    fn env(self, what: &str) -> Option<String> {
+        env::var(format!("HIT_{}_{}", self.key().to_uppercase(), what))
+            .ok()
+            .filter(|v| !v.is_empty())
+    }
+}
+
+/// The author of a new commit
+///
+/// Taken from HIT_AUTHOR_NAME/EMAIL/DATE, then author.name/email, then
+/// user.name/email. Fails if that turns up nothing and guessing is disabled.
+pub fn author() -> Result<Ident, String> {
+    ident(Role::Author, true)
+}
+
+/// The committer of a new commit (HIT_COMMITTER_*, committer.*, user.*)
+pub fn committer() -> Result<Ident, String> {
+    ident(Role::Committer, true)
+}
+
+/// The committer for reflog entries, which are never worth failing over
+pub fn reflog_committer() -> Ident {
+    ident(Role::Committer, false).unwrap_or_else(|_| Ident {
+        name: guess_name(),
+        email: guess_email(),
+        timestamp: date::now().0,
+        timezone: date::format_timezone(date::now().1),
+    })
+}
+
+/// An identity given on the command line, dated from HIT_<ROLE>_DATE or now
+pub fn explicit(role: Role, name: &str, email: &str) -> Result<Ident, String> {
+    let (timestamp, offset) = match role.env("DATE") {
+        Some(value) => date::parse(&value)?,
+        None => date::now(),
+    };
+    Ok(Ident {
+        name: name.to_string(),
+        email: email.to_string(),
+        timestamp,
+        timezone: date::format_timezone(offset),
+    })
+}
+
+/// Split "Name <email>" as given to `--author`
+pub fn parse_name_email(value: &str) -> Option<(String, String)> {
+    let (name, rest) = value.split_once('<')?;
+    let email = rest.strip_suffix('>')?;
+    if email.contains('<') || email.contains('>') {
+        return None;
+    }
+    Some((name.trim().to_string(), email.trim().to_string()))
+}
+
+fn ident(role: Role, strict: bool) -> Result<Ident, String> {
+    let lookup = |what: &str| {
+        role.env(&what.to_uppercase())
+            .or_else(|| config::get(&format!("{}.{}", role.key(), what)))
+            .or_else(|| config::get(&format!("user.{}", what)))
+            .filter(|v| !v.is_empty())
+    };
+    // guessing is opt-in: an identity nobody chose ends up in history forever
+    let guess = !strict || !config::get_bool("user.useConfigOnly", true);
+
+    let email = match lookup("email").or_else(|| env::var("EMAIL").ok().filter(|e| !e.is_empty())) {
+        Some(email) => email,
+        None if guess => guess_email(),
+        None => {
+            return Err(unknown(
+                role,
+                "no email was given and auto-detection is disabled",
+            ));
+        }
+    };
+    let name = match lookup("name") {
+        Some(name) => name,
+        None if guess => guess_name(),
+        None => {
+            return Err(unknown(
+                role,
+                "no name was given and auto-detection is disabled",
+            ));
+        }
+    };
+
+    explicit(role, &name, &email)
+}
+
+fn unknown(role: Role, reason: &str) -> String {
+    let who = match role {
+        Role::Author => "Author",
+        Role::Committer => "Committer",
+    };
+    format!(
+        "{} identity unknown\n\n\
+         *** Please tell me who you are.\n\n\
+         Run\n\n  \
+         hit config --global user.email \"you@example.com\"\n  \
+         hit config --global user.name \"Your Name\"\n\n\
+         to set your account's default identity.\n\
+         Omit --global to set the identity only in this repository.\n\
+         (Or set user.useConfigOnly to false to let hit guess.)\n\n\
+         {}",
+        who, reason
+    )
+}
+
+fn guess_name() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+fn guess_email() -> String {
+    let host = env::var("HOSTNAME")
+        .ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .or_else(|| env::var("COMPUTERNAME").ok())
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+        .unwrap_or_else(|| "(none)".to_string());
+    format!("{}@{}", guess_name(), host)
+}
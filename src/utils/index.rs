@@ -1,4 +1,8 @@
+use crate::utils::objects::{Object, write_object};
+use crate::utils::repo;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct IndexEntry {
@@ -7,15 +11,23 @@ pub struct IndexEntry {
     pub mode: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Index {
     pub entries: Vec<IndexEntry>,
 }
 
 impl Index {
     pub fn load() -> Self {
-        let path = ".hit/index";
-        if std::path::Path::new(path).exists() {
+        Self::load_from(&repo::path("index"))
+    }
+
+    pub fn save(&self) {
+        self.save_to(&repo::path("index"));
+    }
+
+    /// Load an index from somewhere other than the usual place (e.g. a temporary index)
+    pub fn load_from(path: &Path) -> Self {
+        if path.exists() {
             let data = std::fs::read_to_string(path).expect("Failed to read index");
             serde_json::from_str(&data).expect("Invalid index format")
         } else {
@@ -25,9 +37,9 @@ impl Index {
         }
     }
 
-    pub fn save(&self) {
+    pub fn save_to(&self, path: &Path) {
         let json = serde_json::to_string_pretty(&self).expect("Failed to serialize index");
-        std::fs::write(".hit/index", json).expect("Failed to write index");
+        std::fs::write(path, json).expect("Failed to write index");
     }
 
     /// Insert or update an entry by path
@@ -54,3 +66,91 @@ impl Index {
         self.entries.retain(|e| e.path != path);
     }
 }
+
+impl Index {
+    /// Build an index holding every blob reachable from a tree
+    pub fn from_tree(tree_sha: &str) -> Result<Self, String> {
+        let mut entries = Vec::new();
+        collect_tree_entries(tree_sha, "", &mut entries)?;
+        Ok(Index { entries })
+    }
+
+    pub fn get(&self, path: &str) -> Option<&IndexEntry> {
+        self.entries.iter().find(|e| e.path == path)
+    }
+
+    /// Write the index out as a tree (and its subtrees), returning the root tree SHA
+    pub fn write_tree(&self) -> String {
+        let mut root = TreeNode::default();
+        for entry in &self.entries {
+            let mut node = &mut root;
+            let mut parts = entry.path.split('/').peekable();
+            while let Some(part) = parts.next() {
+                if parts.peek().is_none() {
+                    node.files.insert(part.to_string(), entry);
+                } else {
+                    node = node.dirs.entry(part.to_string()).or_default();
+                }
+            }
+        }
+        root.write()
+    }
+}
+
+/// A directory level while turning the flat index into nested trees
+#[derive(Default)]
+struct TreeNode<'a> {
+    files: BTreeMap<String, &'a IndexEntry>,
+    dirs: BTreeMap<String, TreeNode<'a>>,
+}
+
+impl TreeNode<'_> {
+    fn write(&self) -> String {
+        // git sorts directories as if their names ended in '/'
+        let mut entries: Vec<(String, &str, &str, String)> = Vec::new();
+        for (name, entry) in &self.files {
+            entries.push((name.clone(), &entry.mode, name, entry.sha.clone()));
+        }
+        for (name, dir) in &self.dirs {
+            entries.push((format!("{}/", name), "40000", name, dir.write()));
+        }
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut raw = Vec::new();
+        for (_, mode, name, sha) in entries {
+            raw.extend_from_slice(format!("{} {}\0", mode, name).as_bytes());
+            raw.extend_from_slice(&hex::decode(sha).expect("Invalid SHA in index"));
+        }
+        write_object("tree", &raw)
+    }
+}
+
+fn collect_tree_entries(
+    tree_sha: &str,
+    prefix: &str,
+    entries: &mut Vec<IndexEntry>,
+) -> Result<(), String> {
+    let tree = match Object::read(tree_sha)? {
+        Object::Tree(tree) => tree,
+        _ => return Err(format!("{} is not a tree object", tree_sha)),
+    };
+
+    for entry in tree.entries {
+        let path = if prefix.is_empty() {
+            entry.name
+        } else {
+            format!("{}/{}", prefix, entry.name)
+        };
+        if entry.mode == "40000" {
+            collect_tree_entries(&entry.sha, &path, entries)?;
+        } else {
+            entries.push(IndexEntry {
+                path,
+                sha: entry.sha,
+                mode: entry.mode,
+            });
+        }
+    }
+
+    Ok(())
+}
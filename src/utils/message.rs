@@ -0,0 +1,38 @@
+// commit messages: the part of version control everyone agrees matters and nobody does well
+
+/// Tidy a commit message like git's cleanup modes
+///
+/// Trailing whitespace is stripped from every line, runs of blank lines are
+/// collapsed and leading/trailing blank lines removed. With `strip_comments`,
+/// lines starting with '#' are dropped first (the "strip" mode used for
+/// messages that went through the editor).
+pub fn cleanup(message: &str, strip_comments: bool) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    for line in message.lines() {
+        if strip_comments && line.starts_with('#') {
+            continue;
+        }
+        let line = line.trim_end();
+        if line.is_empty() && lines.last().is_none_or(|last| last.is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    while lines.last().is_some_and(|last| last.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+/// Prefix every line with "# " ("#" for empty or tab-indented lines) so cleanup removes it
+pub fn comment_lines(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            if line.is_empty() || line.starts_with('\t') {
+                format!("#{}\n", line)
+            } else {
+                format!("# {}\n", line)
+            }
+        })
+        .collect()
+}
@@ -1,17 +1,38 @@
 // object is a word refering to disagreeing with something
 
+use crate::utils::config;
+use crate::utils::ident::Ident;
+use crate::utils::repo;
+use flate2::Compression;
 use flate2::read::ZlibDecoder;
-use std::io::Read;
+use flate2::write::ZlibEncoder;
+use sha1::{Digest, Sha1};
+use std::fs;
+use std::io::{Read, Write};
 
 // datatype for git objects epic rust enums
 pub enum Object {
     Blob(Vec<u8>),
     Tree(Tree),
-    Commit(Commit),
+    Commit(Box<Commit>),
+    Tag(Box<Tag>),
 }
 
 impl Object {
     pub fn read(sha: &str) -> Result<Self, String> {
+        let (kind, content) = Object::read_raw(sha)?;
+        match kind.as_str() {
+            "blob" => Ok(Object::Blob(content)),
+            "tree" => Tree::parse(&content).map(Object::Tree),
+            "commit" => Commit::parse(&content).map(|c| Object::Commit(Box::new(c))),
+            "tag" => Tag::parse(&content).map(|t| Object::Tag(Box::new(t))),
+            _ => Err("Unknown object type".into()),
+        }
+    }
+
+    /// An object's type ("blob", "commit", ...) and its content exactly as
+    /// stored, for when parsing it would lose something (signed payloads)
+    pub fn read_raw(sha: &str) -> Result<(String, Vec<u8>), String> {
         // Validate SHA is not empty
         if sha.is_empty() {
             return Err("SHA cannot be empty".into());
@@ -23,7 +44,7 @@ impl Object {
         }
 
         // Build object path from SHA
-        let path = format!(".hit/objects/{}/{}", &sha[..2], &sha[2..]);
+        let path = repo::object_path(sha);
         let compressed = std::fs::read(&path).map_err(|_| "Object not found")?;
 
         // decompress
@@ -34,23 +55,37 @@ impl Object {
             .map_err(|_| "Decompression failed")?;
 
         // Parse header
-        if let Some(null_pos) = data.iter().position(|&b| b == 0) {
-            let header = std::str::from_utf8(&data[..null_pos]).map_err(|_| "UTF-8 error")?;
-            let content = &data[null_pos + 1..];
-
-            if header.starts_with("blob ") {
-                Ok(Object::Blob(content.to_vec()))
-            } else if header.starts_with("tree ") {
-                Tree::parse(content).map(Object::Tree)
-            } else if header.starts_with("commit ") {
-                Commit::parse(content).map(Object::Commit)
-            } else {
-                Err("Unknown object type".into())
-            }
-        } else {
-            Err("Invalid object format".into())
-        }
+        let null_pos = data
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or("Invalid object format")?;
+        let header = std::str::from_utf8(&data[..null_pos]).map_err(|_| "UTF-8 error")?;
+        let kind = header.split(' ').next().unwrap_or_default().to_string();
+        Ok((kind, data[null_pos + 1..].to_vec()))
+    }
+}
+
+/// Hash and store a loose object ("blob", "tree", "commit"), returning its SHA
+pub fn write_object(kind: &str, content: &[u8]) -> String {
+    let mut data = format!("{} {}\0", kind, content.len()).into_bytes();
+    data.extend_from_slice(content);
+
+    let mut hasher = Sha1::new();
+    hasher.update(&data);
+    let sha_hex = format!("{:x}", hasher.finalize());
+
+    let object_path = repo::object_path(&sha_hex);
+
+    if !object_path.exists() {
+        fs::create_dir_all(object_path.parent().unwrap())
+            .expect("Failed to create object directory");
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&data).expect("Failed to compress object");
+        let compressed = encoder.finish().expect("Failed to finalize compression");
+        fs::write(&object_path, compressed).expect("Failed to write object file");
     }
+
+    sha_hex
 }
 
 // wht is this
@@ -106,64 +141,282 @@ impl Tree {
 // if this goes wrong, we have a commitment issue :(
 pub struct Commit {
     pub tree: String,
-    pub parent: Option<String>,
+    pub parents: Vec<String>,
+    pub author: Ident,
+    pub committer: Ident,
+    /// The `encoding` header, present when the message isn't UTF-8
+    pub encoding: Option<String>,
+    /// Signature over everything else in the commit (the `gpgsig` header)
+    pub gpgsig: Option<String>,
+    /// Headers we don't otherwise understand (mergetag, ...), kept so
+    /// rewriting a commit doesn't silently drop them
+    pub extra_headers: Vec<(String, String)>,
+    /// The message exactly as stored, normally ending in a newline
     pub message: String,
-    pub author: String,
-    pub timestamp: u64,
-    pub timezone: String,
 }
 
 impl Commit {
+    /// A new unsigned commit; the encoding header follows `i18n.commitEncoding`
+    pub fn new(
+        tree: &str,
+        parents: &[String],
+        author: Ident,
+        committer: Ident,
+        message: &str,
+    ) -> Self {
+        let encoding =
+            config::get("i18n.commitEncoding").filter(|e| !e.eq_ignore_ascii_case("utf-8"));
+        Commit {
+            tree: tree.to_string(),
+            parents: parents.to_vec(),
+            author,
+            committer,
+            encoding,
+            gpgsig: None,
+            extra_headers: Vec::new(),
+            message: message.to_string(),
+        }
+    }
+
     pub fn parse(data: &[u8]) -> Result<Self, String> {
-        let text = std::str::from_utf8(data).map_err(|_| "Invalid UTF-8 in commit")?;
-        let lines = text.lines();
-
-        let mut tree = String::new();
-        let mut parent = None;
-        let mut author = String::new();
-        let mut timestamp = 0;
-        let mut message = String::new();
-        let mut timezone = String::new();
-
-        let mut in_message = false;
-
-        for line in lines {
-            if line.is_empty() {
-                in_message = true;
-                continue;
-            }
+        // headers are UTF-8 even when the message isn't
+        let split = data
+            .windows(2)
+            .position(|w| w == b"\n\n")
+            .map_or(data.len(), |pos| pos + 1);
+        let header = std::str::from_utf8(&data[..split]).map_err(|_| "Invalid UTF-8 in commit")?;
+        let message = String::from_utf8_lossy(data.get(split + 1..).unwrap_or_default());
 
-            if in_message {
-                message.push_str(line);
-                message.push('\n');
-                continue;
+        // a header value continues on following lines that start with a space
+        let mut headers: Vec<(String, String)> = Vec::new();
+        for line in header.lines() {
+            if let Some(continued) = line.strip_prefix(' ')
+                && let Some((_, value)) = headers.last_mut()
+            {
+                value.push('\n');
+                value.push_str(continued);
+            } else if let Some((key, value)) = line.split_once(' ') {
+                headers.push((key.to_string(), value.to_string()));
+            } else if !line.is_empty() {
+                headers.push((line.to_string(), String::new()));
             }
+        }
 
-            if line.starts_with("tree ") {
-                tree = line[5..].to_string();
-            } else if line.starts_with("parent ") {
-                parent = Some(line[7..].to_string());
-            } else if line.starts_with("author ") {
-                let rest = &line[7..];
-                // rest: "Chris <chris@example.com> 1716594600 +0000"
-
-                let mut parts = rest.rsplitn(3, ' '); // split from end: tz, timestamp, name
-                timezone = parts.next().unwrap().to_owned(); // "+0000"
-                let ts = parts.next().unwrap(); // "1716594600"
-                let name = parts.next().unwrap(); // "Chris <chris@example.com>"
-
-                author = name.trim().to_string();
-                timestamp = ts.parse().unwrap_or(0);
+        let mut tree = None;
+        let mut parents = Vec::new();
+        let mut author = None;
+        let mut committer = None;
+        let mut encoding = None;
+        let mut gpgsig = None;
+        let mut extra_headers = Vec::new();
+        for (key, value) in headers {
+            match key.as_str() {
+                "tree" => tree = Some(value),
+                "parent" => parents.push(value),
+                "author" => author = Some(Ident::parse(&value).ok_or("Malformed author line")?),
+                "committer" => {
+                    committer = Some(Ident::parse(&value).ok_or("Malformed committer line")?)
+                }
+                "encoding" => encoding = Some(value),
+                "gpgsig" => gpgsig = Some(value),
+                _ => extra_headers.push((key, value)),
             }
         }
 
+        let author = author.ok_or("Commit has no author")?;
         Ok(Commit {
-            tree,
-            parent,
+            tree: tree.ok_or("Commit has no tree")?,
+            parents,
+            // commits from older versions of hit only recorded an author
+            committer: committer.unwrap_or_else(|| author.clone()),
             author,
-            timestamp,
-            message: message.trim().to_string(),
-            timezone,
+            encoding,
+            gpgsig,
+            extra_headers,
+            message: message.into_owned(),
+        })
+    }
+
+    /// The commit in git's canonical format
+    ///
+    /// Multi-line header values (signatures) are continued with a leading
+    /// space, and a non-empty message always ends in a newline.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut content = self.serialize_unsigned();
+        if let Some(signature) = &self.gpgsig {
+            // the signature goes last among the headers
+            let blank = content
+                .windows(2)
+                .position(|w| w == b"\n\n")
+                .map_or(content.len(), |pos| pos + 1);
+            let header = format!("gpgsig {}\n", signature.replace('\n', "\n "));
+            content.splice(blank..blank, header.into_bytes());
+        }
+        content
+    }
+
+    /// The commit without its signature: the payload that gets signed
+    pub fn serialize_unsigned(&self) -> Vec<u8> {
+        let mut content = format!("tree {}\n", self.tree);
+        for parent in &self.parents {
+            content += &format!("parent {}\n", parent);
+        }
+        content += &format!("author {}\n", self.author);
+        content += &format!("committer {}\n", self.committer);
+        if let Some(encoding) = &self.encoding {
+            content += &format!("encoding {}\n", encoding);
+        }
+        for (key, value) in &self.extra_headers {
+            content += &format!("{} {}\n", key, value.replace('\n', "\n "));
+        }
+        content += "\n";
+        content += &self.message;
+        if !self.message.is_empty() && !self.message.ends_with('\n') {
+            content.push('\n');
+        }
+        content.into_bytes()
+    }
+
+    /// What a stored commit's signature signs: its raw bytes without the
+    /// `gpgsig` header (and its continuation lines), as git checks it
+    pub fn signed_payload(data: &[u8]) -> Vec<u8> {
+        let header_end = data
+            .windows(2)
+            .position(|w| w == b"\n\n")
+            .map_or(data.len(), |pos| pos + 1);
+        let mut payload = Vec::with_capacity(data.len());
+        let mut in_signature = false;
+        for line in data[..header_end].split_inclusive(|&b| b == b'\n') {
+            in_signature = match line.first() {
+                Some(b' ') => in_signature,
+                _ => line.starts_with(b"gpgsig ") || line.starts_with(b"gpgsig-sha256 "),
+            };
+            if !in_signature {
+                payload.extend_from_slice(line);
+            }
+        }
+        payload.extend_from_slice(&data[header_end..]);
+        payload
+    }
+
+    /// Store the commit, returning its SHA
+    pub fn write(&self) -> String {
+        write_object("commit", &self.serialize())
+    }
+
+    /// First line of the message
+    pub fn subject(&self) -> &str {
+        self.message.lines().next().unwrap_or("")
+    }
+}
+
+/// Lines that start a signature appended to a tag message
+const SIGNATURE_MARKERS: [&str; 4] = [
+    "-----BEGIN PGP SIGNATURE-----",
+    "-----BEGIN PGP MESSAGE-----",
+    "-----BEGIN SIGNED MESSAGE-----",
+    "-----BEGIN SSH SIGNATURE-----",
+];
+
+// a tag is a commit's name badge, occasionally notarised
+pub struct Tag {
+    /// SHA of the tagged object
+    pub object: String,
+    /// Type of the tagged object ("commit", "tree", ...)
+    pub kind: String,
+    pub name: String,
+    /// Very old tags have no tagger
+    pub tagger: Option<Ident>,
+    /// The message, without any signature
+    pub message: String,
+    /// Signature appended after the message, if the tag is signed
+    pub signature: Option<String>,
+}
+
+impl Tag {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        let text = std::str::from_utf8(data).map_err(|_| "Invalid UTF-8 in tag")?;
+        let (header, body) = text.split_once("\n\n").unwrap_or((text, ""));
+
+        let mut object = None;
+        let mut kind = None;
+        let mut name = None;
+        let mut tagger = None;
+        for line in header.lines() {
+            if let Some(rest) = line.strip_prefix("object ") {
+                object = Some(rest.to_string());
+            } else if let Some(rest) = line.strip_prefix("type ") {
+                kind = Some(rest.to_string());
+            } else if let Some(rest) = line.strip_prefix("tag ") {
+                name = Some(rest.to_string());
+            } else if let Some(rest) = line.strip_prefix("tagger ") {
+                tagger = Some(Ident::parse(rest).ok_or("Malformed tagger line")?);
+            }
+        }
+
+        let (message, signature) = match signature_start(body.as_bytes()) {
+            Some(at) => (&body[..at], Some(body[at..].to_string())),
+            None => (body, None),
+        };
+
+        Ok(Tag {
+            object: object.ok_or("Tag has no object")?,
+            kind: kind.ok_or("Tag has no type")?,
+            name: name.ok_or("Tag has no name")?,
+            tagger,
+            message: message.to_string(),
+            signature,
         })
     }
+
+    /// The tag without its signature: the payload that gets signed
+    pub fn serialize_unsigned(&self) -> Vec<u8> {
+        let mut content = format!(
+            "object {}\ntype {}\ntag {}\n",
+            self.object, self.kind, self.name
+        );
+        if let Some(tagger) = &self.tagger {
+            content += &format!("tagger {}\n", tagger);
+        }
+        content += "\n";
+        content += &self.message;
+        content.into_bytes()
+    }
+
+    /// What a stored tag's signature signs: its raw bytes up to the
+    /// signature block at the end, as git checks it
+    pub fn signed_payload(data: &[u8]) -> Vec<u8> {
+        let body = data
+            .windows(2)
+            .position(|w| w == b"\n\n")
+            .map_or(data.len(), |pos| pos + 2);
+        let end = signature_start(&data[body..]).map_or(data.len(), |at| body + at);
+        data[..end].to_vec()
+    }
+
+    /// The tag in git's format, signature included
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut content = self.serialize_unsigned();
+        if let Some(signature) = &self.signature {
+            content.extend_from_slice(signature.as_bytes());
+        }
+        content
+    }
+}
+
+/// Where the signature in a tag's body starts: the last line that starts a
+/// signature block
+fn signature_start(body: &[u8]) -> Option<usize> {
+    let mut offset = 0;
+    let mut start = None;
+    for line in body.split_inclusive(|&b| b == b'\n') {
+        if SIGNATURE_MARKERS
+            .iter()
+            .any(|m| line.starts_with(m.as_bytes()))
+        {
+            start = Some(offset);
+        }
+        offset += line.len();
+    }
+    start
 }
@@ -0,0 +1,28 @@
+// a pathspec is a path with ambitions
+
+/// Normalize a user supplied path ("./src/", "src\\main.rs") into index form
+pub fn normalize(spec: &str) -> String {
+    let spec = spec.replace('\\', "/");
+    let mut spec = spec.as_str();
+    while let Some(rest) = spec.strip_prefix("./") {
+        spec = rest;
+    }
+    let spec = spec.trim_end_matches('/');
+    if spec == "." {
+        String::new()
+    } else {
+        spec.to_string()
+    }
+}
+
+/// True if `path` is the spec itself or lives under it ("" matches everything)
+pub fn matches(path: &str, spec: &str) -> bool {
+    spec.is_empty()
+        || path == spec
+        || (path.starts_with(spec) && path.as_bytes().get(spec.len()) == Some(&b'/'))
+}
+
+/// True if `path` matches any of the (normalized) specs
+pub fn matches_any(path: &str, specs: &[String]) -> bool {
+    specs.iter().any(|spec| matches(path, spec))
+}
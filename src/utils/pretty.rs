@@ -0,0 +1,622 @@
+// pretty: because every commit deserves to look its best, even "wip"
+
+use crate::utils::color;
+use crate::utils::config;
+use crate::utils::date::{self, Style};
+use crate::utils::diff::DiffColors;
+use crate::utils::graph::Graph;
+use crate::utils::ident::Ident;
+use crate::utils::objects::{Commit, Object};
+use crate::utils::refs::{self, Head};
+use crate::utils::signature;
+use std::collections::HashMap;
+use std::io::IsTerminal;
+
+/// How to print a commit (`--format`, `--pretty`, `--oneline`)
+#[derive(Clone, PartialEq)]
+pub enum Format {
+    /// "<sha> <subject>" on one line
+    Oneline,
+    /// Author and subject
+    Short,
+    /// Author, date and the whole message, the default
+    Medium,
+    /// Author, committer and the whole message
+    Full,
+    /// Author, committer, both dates and the whole message
+    Fuller,
+    /// A template of %-placeholders, each commit followed by a newline
+    /// (`tformat:`) or commits separated by one (`format:`)
+    User { template: String, terminator: bool },
+}
+
+impl Format {
+    /// A built-in format's name, "format:<template>", "tformat:<template>",
+    /// or a bare template, which like git must have a '%' in it (or be empty)
+    pub fn parse(spec: &str) -> Option<Self> {
+        let user = |template: &str, terminator| {
+            Some(Format::User {
+                template: template.to_string(),
+                terminator,
+            })
+        };
+        if let Some(template) = spec.strip_prefix("format:") {
+            return user(template, false);
+        }
+        if let Some(template) = spec.strip_prefix("tformat:") {
+            return user(template, true);
+        }
+        match spec {
+            "oneline" => Some(Format::Oneline),
+            "short" => Some(Format::Short),
+            "medium" => Some(Format::Medium),
+            "full" => Some(Format::Full),
+            "fuller" => Some(Format::Fuller),
+            _ if spec.is_empty() || spec.contains('%') => user(spec, true),
+            _ => None,
+        }
+    }
+
+    /// Each commit ends in a newline, rather than a blank line going between commits
+    fn terminates(&self) -> bool {
+        matches!(
+            self,
+            Format::Oneline
+                | Format::User {
+                    terminator: true,
+                    ..
+                }
+        )
+    }
+
+    /// `--format=`: nothing at all, not even a newline
+    fn is_empty(&self) -> bool {
+        matches!(self, Format::User { template, .. } if template.is_empty())
+    }
+
+    /// The template has `%d` or `%D` in it, so needs the refs loaded
+    fn wants_decorations(&self) -> bool {
+        matches!(self, Format::User { template, .. }
+            if template.contains("%d") || template.contains("%D"))
+    }
+}
+
+/// Which refs to show next to each commit (`--decorate`, `log.decorate`)
+#[derive(Clone, Copy, PartialEq)]
+pub enum Decorate {
+    No,
+    /// "master", "tag: v1"
+    Short,
+    /// "refs/heads/master", "tag: refs/tags/v1"
+    Full,
+}
+
+impl Decorate {
+    /// "short", "full" or "no", as `--decorate=` takes them
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "short" => Some(Decorate::Short),
+            "full" => Some(Decorate::Full),
+            "no" => Some(Decorate::No),
+            _ => None,
+        }
+    }
+
+    /// `log.decorate`, which also takes a boolean or "auto"; like git,
+    /// auto (the default) means only when stdout is a terminal
+    pub fn configured() -> Self {
+        let value = config::get("log.decorate").map(|value| value.to_lowercase());
+        match value.as_deref() {
+            None | Some("auto") => match std::io::stdout().is_terminal() {
+                true => Decorate::Short,
+                false => Decorate::No,
+            },
+            Some("true" | "yes" | "on" | "1" | "short") => Decorate::Short,
+            Some("full") => Decorate::Full,
+            _ => Decorate::No,
+        }
+    }
+}
+
+/// The refs pointing at each commit (and tag), newest name first like git
+pub struct Decorations {
+    names: HashMap<String, Vec<String>>,
+    /// The branch HEAD is on, shown as "HEAD -> <branch>"
+    head_branch: Option<String>,
+    full: bool,
+}
+
+impl Decorations {
+    pub fn load(full: bool) -> Self {
+        let mut names: HashMap<String, Vec<String>> = HashMap::new();
+        let head = refs::read_head().and_then(|head| match head {
+            Head::Branch(refname) => refs::read_ref(&refname),
+            Head::Detached(sha) => Some(sha),
+        });
+        let mut all = refs::all_refs();
+        all.extend(head.map(|sha| ("HEAD".to_string(), sha)));
+        for (refname, mut sha) in all {
+            names.entry(sha.clone()).or_default().push(refname.clone());
+            // an annotated tag decorates what it points at too
+            while let Ok(Object::Tag(tag)) = Object::read(&sha) {
+                sha = tag.object;
+                names.entry(sha.clone()).or_default().push(refname.clone());
+            }
+        }
+        for list in names.values_mut() {
+            list.reverse();
+        }
+        let head_branch = match refs::read_head() {
+            Some(Head::Branch(refname)) => Some(refname),
+            _ => None,
+        };
+        Decorations {
+            names,
+            head_branch,
+            full,
+        }
+    }
+
+    /// The refs at `sha` between `prefix` and `suffix` (" (" and ")" for
+    /// `%d`), or nothing when there are none; `commit` and `reset` are the
+    /// colors for the punctuation, empty when uncolored
+    fn format(&self, sha: &str, prefix: &str, suffix: &str, commit: &str, reset: &str) -> String {
+        let Some(list) = self.names.get(sha) else {
+            return String::new();
+        };
+        let colored = !reset.is_empty();
+        let paint = |refname: &str| match colored {
+            true => decoration_color(refname),
+            false => String::new(),
+        };
+        // HEAD and its branch go together, where HEAD would be
+        let current = self
+            .head_branch
+            .as_ref()
+            .filter(|branch| list.iter().any(|name| name == "HEAD") && list.contains(branch));
+
+        let mut text = String::new();
+        let mut separator = prefix;
+        for refname in list {
+            if Some(refname) == current {
+                continue;
+            }
+            text += &format!("{}{}{}{}", commit, separator, reset, paint(refname));
+            if refname.starts_with("refs/tags/") {
+                text += "tag: ";
+            }
+            text += &self.name(refname);
+            if let Some(branch) = current
+                && refname == "HEAD"
+            {
+                text += &format!(" -> {}{}{}", reset, paint(branch), self.name(branch));
+            }
+            text += reset;
+            separator = ", ";
+        }
+        format!("{}{}{}{}", text, commit, suffix, reset)
+    }
+
+    fn name(&self, refname: &str) -> String {
+        if self.full {
+            return refname.to_string();
+        }
+        ["refs/heads/", "refs/tags/", "refs/remotes/"]
+            .iter()
+            .find_map(|prefix| refname.strip_prefix(prefix))
+            .unwrap_or(refname)
+            .to_string()
+    }
+}
+
+/// git's colors for each kind of ref, unless `color.decorate.<slot>` says otherwise
+fn decoration_color(refname: &str) -> String {
+    let (slot, default) = if refname == "HEAD" {
+        ("HEAD", "\x1b[1;36m")
+    } else if refname.starts_with("refs/heads/") {
+        ("branch", "\x1b[1;32m")
+    } else if refname.starts_with("refs/remotes/") {
+        ("remoteBranch", "\x1b[1;31m")
+    } else if refname.starts_with("refs/tags/") {
+        ("tag", "\x1b[1;33m")
+    } else if refname == "refs/stash" {
+        ("stash", "\x1b[1;35m")
+    } else {
+        return color::RESET.to_string();
+    };
+    color::slot(&format!("color.decorate.{}", slot), default)
+}
+
+/// Writes commits one after another, with git's blank lines between them
+/// and between each commit and its changes
+pub struct Writer {
+    pub format: Format,
+    /// Check and show each commit's signature (`--show-signature`)
+    pub show_signature: bool,
+    pub colors: DiffColors,
+    /// Something has been written already, so the next thing needs setting apart
+    pub shown: bool,
+    /// Show the refs pointing at each commit after its hash
+    pub decorate: Decorate,
+    /// Draw the history down the left (`--graph`); the caller moves it on
+    /// to each commit before writing it
+    pub graph: Option<Graph>,
+    /// Loaded the first time they're needed
+    decorations: Option<Decorations>,
+    /// The last commit written didn't end in a newline, so the blank line
+    /// after it goes without the graph
+    missing_newline: bool,
+}
+
+impl Writer {
+    pub fn new(format: Format, show_signature: bool, colors: DiffColors) -> Self {
+        Writer {
+            format,
+            show_signature,
+            colors,
+            shown: false,
+            decorate: Decorate::No,
+            graph: None,
+            decorations: None,
+            missing_newline: false,
+        }
+    }
+
+    /// Write a commit and what it changed (a stat, a patch, ...), with "---"
+    /// between the two when `dashes` is set
+    pub fn write(
+        &mut self,
+        out: &mut Vec<u8>,
+        sha: &str,
+        commit: &Commit,
+        changes: &[u8],
+        dashes: bool,
+    ) {
+        if self.decorations.is_none()
+            && (self.decorate != Decorate::No || self.format.wants_decorations())
+        {
+            self.decorations = Some(Decorations::load(self.decorate == Decorate::Full));
+        }
+        let header = self.header(sha, commit);
+        let Some(graph) = &mut self.graph else {
+            if self.shown && !self.format.terminates() {
+                out.push(b'\n');
+            }
+            self.shown = true;
+            out.extend_from_slice(header.as_bytes());
+            if self.format.terminates() && !self.format.is_empty() {
+                out.push(b'\n');
+            }
+            if !changes.is_empty() {
+                if self.format != Format::Oneline && !self.format.is_empty() {
+                    if dashes {
+                        out.extend_from_slice(b"---");
+                    }
+                    out.push(b'\n');
+                }
+                out.extend_from_slice(changes);
+            }
+            return;
+        };
+
+        // like git, the graph goes down the left of every line, even the
+        // blank ones, unless they are left off a message with no newline
+        let mut text = String::new();
+        if self.shown && !self.format.terminates() {
+            if !self.missing_newline {
+                text += &graph.padding_line();
+            }
+            text.push('\n');
+        }
+        self.shown = true;
+        graph.show_commit(&mut text);
+        graph.show_text(&mut text, &header);
+        self.missing_newline = !header.ends_with('\n');
+        if self.format.terminates() && !self.format.is_empty() {
+            if header.ends_with('\n') {
+                text += &graph.padding_line();
+            }
+            text.push('\n');
+        }
+        if !changes.is_empty() && self.format != Format::Oneline && !self.format.is_empty() {
+            text += &graph.padding_line();
+            if dashes {
+                text += "---";
+            }
+            text.push('\n');
+        }
+        out.extend_from_slice(text.as_bytes());
+        for line in changes.split_inclusive(|&b| b == b'\n') {
+            out.extend_from_slice(graph.padding_line().as_bytes());
+            out.extend_from_slice(line);
+        }
+    }
+
+    /// The commit in the chosen format; built-in formats other than oneline
+    /// end in a newline
+    fn header(&self, sha: &str, commit: &Commit) -> String {
+        let colors = &self.colors;
+        let decorations = self.decorations.as_ref();
+        let decoration = match decorations {
+            Some(decorations) if self.decorate != Decorate::No => {
+                decorations.format(sha, " (", ")", &colors.commit, &colors.reset)
+            }
+            _ => String::new(),
+        };
+        let layout = match &self.format {
+            Format::User { template, .. } => {
+                return expand(template, sha, commit, colors, decorations);
+            }
+            Format::Oneline => {
+                return format!(
+                    "{}{}{}{} {}",
+                    colors.commit,
+                    &sha[..7],
+                    colors.reset,
+                    decoration,
+                    subject(&commit.message)
+                );
+            }
+            layout => layout,
+        };
+
+        let mut text = format!(
+            "{}commit {}{}{}\n",
+            colors.commit, sha, colors.reset, decoration
+        );
+        if self.show_signature
+            && let Some(gpgsig) = &commit.gpgsig
+        {
+            let payload = Object::read_raw(sha).map(|(_, raw)| Commit::signed_payload(&raw));
+            match payload.and_then(|payload| signature::verify(&payload, gpgsig)) {
+                Ok(result) => text += &result.output,
+                Err(e) => text += &format!("error: {}\n", e),
+            }
+        }
+        if commit.parents.len() > 1 {
+            let parents: Vec<&str> = commit.parents.iter().map(|p| &p[..7]).collect();
+            text += &format!("Merge: {}\n", parents.join(" "));
+        }
+        let (author, committer) = (&commit.author, &commit.committer);
+        let when = |ident: &Ident| date::format(ident.timestamp, &ident.timezone, Style::Default);
+        text += &match layout {
+            Format::Short => format!("Author: {}\n", author.name_email()),
+            Format::Medium => format!(
+                "Author: {}\nDate:   {}\n",
+                author.name_email(),
+                when(author)
+            ),
+            Format::Full => format!(
+                "Author: {}\nCommit: {}\n",
+                author.name_email(),
+                committer.name_email()
+            ),
+            _ => format!(
+                "Author:     {}\nAuthorDate: {}\nCommit:     {}\nCommitDate: {}\n",
+                author.name_email(),
+                when(author),
+                committer.name_email(),
+                when(committer)
+            ),
+        };
+        text.push('\n');
+        text += &indent(&commit.message, *layout == Format::Short);
+        text
+    }
+}
+
+/// The message indented by four spaces, without leading or trailing blank
+/// lines; tabs are expanded except in the short format, which keeps only
+/// the first paragraph
+fn indent(message: &str, short: bool) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for line in message.lines().map(str::trim_end) {
+        if line.is_empty() {
+            if lines.is_empty() {
+                continue;
+            }
+            if short {
+                break;
+            }
+        }
+        let line = match short {
+            true => line.to_string(),
+            false => expand_tabs(line),
+        };
+        lines.push(format!("    {}\n", line));
+    }
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+    lines.concat()
+}
+
+/// Tabs turned into spaces up to the next multiple of eight columns
+fn expand_tabs(line: &str) -> String {
+    let mut expanded = String::new();
+    let mut column = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let width = 8 - column % 8;
+            expanded.extend(std::iter::repeat_n(' ', width));
+            column += width;
+        } else {
+            expanded.push(c);
+            column += 1;
+        }
+    }
+    expanded
+}
+
+/// The first paragraph of a message on one line, as `%s` and oneline show it
+pub fn subject(message: &str) -> String {
+    message
+        .lines()
+        .map(str::trim_end)
+        .skip_while(|line| line.is_empty())
+        .take_while(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Everything after the first paragraph and the blank lines that follow it (`%b`)
+fn body(message: &str) -> &str {
+    let (mut in_subject, mut after_subject) = (false, false);
+    let mut offset = 0;
+    for line in message.split_inclusive('\n') {
+        let blank = line.trim().is_empty();
+        if after_subject && !blank {
+            return &message[offset..];
+        }
+        in_subject |= !blank;
+        after_subject |= in_subject && blank;
+        offset += line.len();
+    }
+    ""
+}
+
+/// Fill in a `--format` template's placeholders
+fn expand(
+    template: &str,
+    sha: &str,
+    commit: &Commit,
+    colors: &DiffColors,
+    decorations: Option<&Decorations>,
+) -> String {
+    let colored = !colors.reset.is_empty();
+    // after %C(auto), hashes are colored like a commit line
+    let mut auto = false;
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(at) = rest.find('%') {
+        out += &rest[..at];
+        rest = &rest[at + 1..];
+        let paint = |text: &str, auto: bool| match auto {
+            true => format!("{}{}{}", colors.commit, text, colors.reset),
+            false => text.to_string(),
+        };
+        let decorate = |prefix: &str, suffix: &str, auto: bool| match (decorations, auto) {
+            (Some(decorations), true) => {
+                decorations.format(sha, prefix, suffix, &colors.commit, &colors.reset)
+            }
+            (Some(decorations), false) => decorations.format(sha, prefix, suffix, "", ""),
+            (None, _) => String::new(),
+        };
+        let ident = |role: Option<char>| match role {
+            Some('a') => Some(&commit.author),
+            Some('c') => Some(&commit.committer),
+            _ => None,
+        };
+
+        let mut chars = rest.chars();
+        let first = chars.next();
+        let (text, used) = match first {
+            Some('%') => ("%".to_string(), 1),
+            Some('n') => ("\n".to_string(), 1),
+            Some('H') => (paint(sha, auto), 1),
+            Some('h') => (paint(&sha[..7], auto), 1),
+            Some('T') => (commit.tree.clone(), 1),
+            Some('t') => (commit.tree[..7].to_string(), 1),
+            Some('P') => (commit.parents.join(" "), 1),
+            Some('p') => {
+                let parents: Vec<&str> = commit.parents.iter().map(|p| &p[..7]).collect();
+                (parents.join(" "), 1)
+            }
+            Some('s') => (subject(&commit.message), 1),
+            Some('b') => (body(&commit.message).to_string(), 1),
+            Some('B') => (commit.message.clone(), 1),
+            Some('d') => (decorate(" (", ")", auto), 1),
+            Some('D') => (decorate("", "", auto), 1),
+            Some('x') => match rest
+                .get(1..3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                Some(byte) => ((byte as char).to_string(), 3),
+                None => (String::new(), 0),
+            },
+            Some('C') => {
+                let named = [
+                    ("red", "\x1b[31m"),
+                    ("green", "\x1b[32m"),
+                    ("blue", "\x1b[34m"),
+                    ("reset", color::RESET),
+                ];
+                if let Some((name, code)) =
+                    named.iter().find(|(name, _)| rest[1..].starts_with(name))
+                {
+                    (
+                        if colored {
+                            code.to_string()
+                        } else {
+                            String::new()
+                        },
+                        1 + name.len(),
+                    )
+                } else if let Some(spec) = rest.strip_prefix("C(")
+                    && let Some(end) = spec.find(')')
+                {
+                    let spec = &spec[..end];
+                    let text = match spec {
+                        "auto" => {
+                            auto = colored;
+                            match colored && !out.is_empty() {
+                                true => color::RESET.to_string(),
+                                false => String::new(),
+                            }
+                        }
+                        _ => {
+                            let (always, spec) = match spec.split_once(',') {
+                                Some(("always", spec)) => (true, spec),
+                                Some(("auto", spec)) => (false, spec),
+                                _ => (false, spec),
+                            };
+                            let code = match spec {
+                                "reset" => Some(color::RESET.to_string()),
+                                _ => color::parse(spec),
+                            };
+                            match code {
+                                Some(code) if colored || always => code,
+                                _ => String::new(),
+                            }
+                        }
+                    };
+                    (text, 2 + end + 1)
+                } else {
+                    (String::new(), 0)
+                }
+            }
+            Some(role @ ('a' | 'c')) => match (ident(Some(role)), chars.next()) {
+                (Some(ident), Some(field)) => match ident_field(ident, field) {
+                    Some(text) => (text, 2),
+                    None => (String::new(), 0),
+                },
+                _ => (String::new(), 0),
+            },
+            _ => (String::new(), 0),
+        };
+        if used == 0 {
+            // not a placeholder we know, so it's left as it is
+            out.push('%');
+            continue;
+        }
+        out += &text;
+        rest = &rest[used..];
+    }
+    out + rest
+}
+
+/// `%an`, `%ae`, `%ad` and friends
+fn ident_field(ident: &Ident, field: char) -> Option<String> {
+    let when = |style| date::format(ident.timestamp, &ident.timezone, style);
+    Some(match field {
+        'n' => ident.name.clone(),
+        'e' => ident.email.clone(),
+        'd' => when(Style::Default),
+        'r' => when(Style::Relative),
+        'i' => when(Style::Iso),
+        'I' => when(Style::IsoStrict),
+        's' => when(Style::Short),
+        't' => when(Style::Unix),
+        _ => return None,
+    })
+}
@@ -0,0 +1,472 @@
+// refs are the sticky notes we put on commits so we don't have to remember 40 hex digits
+
+use crate::utils::hash_object::resolve_head;
+use crate::utils::ident;
+use crate::utils::index::Index;
+use crate::utils::objects::{Commit, Object};
+use crate::utils::repo;
+use std::fs;
+use std::io::Write;
+
+pub const NULL_SHA: &str = "0000000000000000000000000000000000000000";
+
+/// What HEAD currently points at
+pub enum Head {
+    /// Symbolic HEAD, e.g. "refs/heads/master" (the ref may not exist yet)
+    Branch(String),
+    /// Detached HEAD holding a raw commit SHA
+    Detached(String),
+}
+
+/// Read HEAD without resolving it
+pub fn read_head() -> Option<Head> {
+    let head = fs::read_to_string(repo::path("HEAD")).ok()?;
+    let head = head.trim();
+    if let Some(refname) = head.strip_prefix("ref: ") {
+        Some(Head::Branch(refname.trim().to_string()))
+    } else if head.is_empty() {
+        None
+    } else {
+        Some(Head::Detached(head.to_string()))
+    }
+}
+
+/// Name of the checked out branch, or None when HEAD is detached
+pub fn current_branch() -> Option<String> {
+    match read_head()? {
+        Head::Branch(refname) => refname.strip_prefix("refs/heads/").map(str::to_string),
+        Head::Detached(_) => None,
+    }
+}
+
+/// Read the SHA a ref file points to (None if missing or empty)
+pub fn read_ref(refname: &str) -> Option<String> {
+    let content = fs::read_to_string(repo::path(refname)).ok()?;
+    let trimmed = content.trim();
+    if trimmed.is_empty() {
+        None
+    } else {
+        Some(trimmed.to_string())
+    }
+}
+
+/// Every ref under refs/ with the SHA it holds, sorted by name
+pub fn all_refs() -> Vec<(String, String)> {
+    fn walk(dir: &std::path::Path, prefix: &str, refs: &mut Vec<(String, String)>) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.filter_map(Result::ok) {
+            let name = format!("{}/{}", prefix, entry.file_name().to_string_lossy());
+            if entry.path().is_dir() {
+                walk(&entry.path(), &name, refs);
+            } else if let Some(sha) = read_ref(&name) {
+                refs.push((name, sha));
+            }
+        }
+    }
+    let mut refs = Vec::new();
+    walk(&repo::path("refs"), "refs", &mut refs);
+    refs.sort();
+    refs
+}
+
+/// Point a ref at a commit, creating parent directories as needed
+pub fn write_ref(refname: &str, sha: &str) -> Result<(), String> {
+    let path = repo::path(refname);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("cannot create {}: {}", refname, e))?;
+    }
+    fs::write(&path, format!("{}\n", sha)).map_err(|e| format!("cannot write {}: {}", refname, e))
+}
+
+/// Move a ref and record the change in its reflog
+pub fn update_ref(refname: &str, sha: &str, message: &str) -> Result<(), String> {
+    let old = read_ref(refname);
+    write_ref(refname, sha)?;
+    append_reflog(refname, old.as_deref(), sha, message);
+
+    // the checked out branch shares its history with HEAD's reflog
+    if let Some(Head::Branch(head_ref)) = read_head()
+        && head_ref == refname
+    {
+        append_reflog("HEAD", old.as_deref(), sha, message);
+    }
+    Ok(())
+}
+
+/// Remove a ref and its reflog
+pub fn delete_ref(refname: &str) -> Result<(), String> {
+    fs::remove_file(repo::path(refname))
+        .map_err(|e| format!("cannot delete {}: {}", refname, e))?;
+    let _ = fs::remove_file(repo::path(&format!("logs/{}", refname)));
+    Ok(())
+}
+
+/// Move whatever HEAD points at: the checked out branch, or HEAD itself when detached
+pub fn update_head(sha: &str, message: &str) -> Result<(), String> {
+    match read_head() {
+        Some(Head::Branch(refname)) => update_ref(&refname, sha, message),
+        _ => {
+            set_head_detached(sha, message);
+            Ok(())
+        }
+    }
+}
+
+/// Make HEAD a symbolic ref to `refname`
+pub fn set_head_symbolic(refname: &str, message: &str) {
+    let old = resolve_head();
+    fs::write(repo::path("HEAD"), format!("ref: {}\n", refname)).expect("Failed to write HEAD");
+    // an unborn branch has nothing to log yet
+    if let Some(new) = read_ref(refname) {
+        append_reflog("HEAD", old.as_deref(), &new, message);
+    }
+}
+
+/// Detach HEAD at `sha`
+pub fn set_head_detached(sha: &str, message: &str) {
+    let old = resolve_head();
+    fs::write(repo::path("HEAD"), format!("{}\n", sha)).expect("Failed to write detached HEAD");
+    append_reflog("HEAD", old.as_deref(), sha, message);
+}
+
+/// Short human name for where HEAD is: the branch name or an abbreviated SHA
+pub fn head_description() -> String {
+    match read_head() {
+        Some(Head::Branch(refname)) => refname
+            .strip_prefix("refs/heads/")
+            .unwrap_or(&refname)
+            .to_string(),
+        Some(Head::Detached(sha)) => sha.chars().take(7).collect(),
+        None => "HEAD".to_string(),
+    }
+}
+
+pub fn branch_ref(branch: &str) -> String {
+    format!("refs/heads/{}", branch)
+}
+
+pub fn branch_exists(branch: &str) -> bool {
+    repo::path(&branch_ref(branch)).is_file()
+}
+
+/// Find the full refname a short name like "master" or "stash" refers to
+pub fn dwim_ref(name: &str) -> Option<String> {
+    if name == "HEAD" {
+        return Some("HEAD".to_string());
+    }
+    [
+        name.to_string(),
+        format!("refs/{}", name),
+        format!("refs/tags/{}", name),
+        format!("refs/heads/{}", name),
+        format!("refs/remotes/{}", name),
+    ]
+    .into_iter()
+    .find(|candidate| candidate.starts_with("refs/") && read_ref(candidate).is_some())
+}
+
+/// The shortest unambiguous name of a ref, like "master" for
+/// refs/heads/master or "stash" for refs/stash
+pub fn short_name(refname: &str) -> &str {
+    ["refs/heads/", "refs/tags/", "refs/remotes/", "refs/"]
+        .iter()
+        .find_map(|prefix| refname.strip_prefix(prefix))
+        .unwrap_or(refname)
+}
+
+/// Check a branch name the same way `hit branch` does
+pub fn validate_branch_name(name: &str) -> Result<(), String> {
+    if name.trim().is_empty()
+        || name.contains('/')
+        || name.contains('\\')
+        || name.contains("..")
+        || name.starts_with('-')
+        || name
+            .chars()
+            .any(|c| c.is_whitespace() || "~^:?*[".contains(c))
+    {
+        return Err(format!("'{}' is not a valid branch name", name));
+    }
+    Ok(())
+}
+
+/// Resolve a revision like "HEAD~2", "master^", "abc1234" or "v1.0" to a full commit SHA
+pub fn resolve_revision(rev: &str) -> Result<String, String> {
+    // split off the trailing ~N / ^N navigation
+    let base_end = rev.find(['~', '^']).unwrap_or(rev.len());
+    let (base, mut suffix) = rev.split_at(base_end);
+
+    let mut sha = resolve_base(base)?;
+
+    while !suffix.is_empty() {
+        let op = suffix.as_bytes()[0];
+        suffix = &suffix[1..];
+        let digits_end = suffix
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(suffix.len());
+        let n = if digits_end == 0 {
+            1
+        } else {
+            suffix[..digits_end]
+                .parse::<usize>()
+                .map_err(|_| format!("bad revision '{}'", rev))?
+        };
+        suffix = &suffix[digits_end..];
+
+        if op == b'~' {
+            for _ in 0..n {
+                sha = nth_parent(&sha, 1).ok_or_else(|| format!("bad revision '{}'", rev))?;
+            }
+        } else if n > 0 {
+            sha = nth_parent(&sha, n).ok_or_else(|| format!("bad revision '{}'", rev))?;
+        }
+    }
+
+    Ok(sha)
+}
+
+/// Resolve a name without navigation suffixes
+fn resolve_base(name: &str) -> Result<String, String> {
+    if name.is_empty() || name == "HEAD" || name == "@" {
+        return resolve_head().ok_or_else(|| "HEAD does not point to a commit yet".to_string());
+    }
+
+    if let Some(at) = name.find("@{")
+        && name.ends_with('}')
+    {
+        return resolve_reflog_entry(&name[..at], &name[at + 2..name.len() - 1]);
+    }
+
+    if let Some(refname) = dwim_ref(name) {
+        return read_ref(&refname).ok_or_else(|| format!("unknown revision '{}'", name));
+    }
+
+    if name.len() >= 4 && name.chars().all(|c| c.is_ascii_hexdigit()) {
+        return expand_sha(&name.to_lowercase());
+    }
+
+    Err(format!("unknown revision '{}'", name))
+}
+
+/// Resolve "<ref>@{n}": the value `ref` had n moves ago
+fn resolve_reflog_entry(name: &str, selector: &str) -> Result<String, String> {
+    let n: usize = selector
+        .parse()
+        .map_err(|_| format!("unsupported reflog selector '@{{{}}}'", selector))?;
+
+    // a bare "@{n}" means the current branch (or HEAD when detached)
+    let refname = match name {
+        "" => match read_head() {
+            Some(Head::Branch(refname)) => refname,
+            _ => "HEAD".to_string(),
+        },
+        _ => dwim_ref(name).ok_or_else(|| format!("unknown revision '{}'", name))?,
+    };
+
+    let entries = read_reflog(&refname);
+    entries
+        .iter()
+        .rev()
+        .nth(n)
+        .map(|entry| entry.new.clone())
+        .ok_or_else(|| format!("log for '{}' only has {} entries", refname, entries.len()))
+}
+
+/// Expand an abbreviated SHA by scanning the object store
+fn expand_sha(prefix: &str) -> Result<String, String> {
+    let dir = repo::path("objects").join(&prefix[..2]);
+    let rest = &prefix[2..];
+    let mut matches: Vec<String> = fs::read_dir(&dir)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .filter_map(|e| e.file_name().to_str().map(str::to_string))
+                .filter(|name| name.starts_with(rest))
+                .map(|name| format!("{}{}", &prefix[..2], name))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    match matches.len() {
+        0 => Err(format!("unknown revision '{}'", prefix)),
+        1 => Ok(matches.remove(0)),
+        _ => Err(format!("short SHA '{}' is ambiguous", prefix)),
+    }
+}
+
+/// The first `len` (at least 4) characters of `sha`, or more if that
+/// would be ambiguous
+pub fn abbreviate(sha: &str, len: usize) -> String {
+    (len.max(4)..sha.len())
+        .map(|len| &sha[..len])
+        .find(|prefix| expand_sha(prefix).is_ok_and(|found| found == sha))
+        .unwrap_or(sha)
+        .to_string()
+}
+
+/// The n-th (1-based) parent of a commit (or of the commit a tag points at)
+fn nth_parent(sha: &str, n: usize) -> Option<String> {
+    let (_, commit) = peel_to_commit(sha).ok()?;
+    commit.parents.get(n - 1).cloned()
+}
+
+/// Follow tags until reaching a commit, returning its SHA and contents
+pub fn peel_to_commit(sha: &str) -> Result<(String, Commit), String> {
+    let mut sha = sha.to_string();
+    loop {
+        match Object::read(&sha) {
+            Ok(Object::Commit(commit)) => return Ok((sha, *commit)),
+            Ok(Object::Tag(tag)) => sha = tag.object,
+            Ok(_) => return Err(format!("{} is not a commit", sha)),
+            Err(e) => return Err(format!("cannot read {}: {}", sha, e)),
+        }
+    }
+}
+
+/// Resolve a revision and make sure it names a commit, returning (sha, tree)
+pub fn resolve_commit(rev: &str) -> Result<(String, String), String> {
+    let sha = resolve_revision(rev)?;
+    match peel_to_commit(&sha) {
+        Ok((sha, commit)) => Ok((sha, commit.tree)),
+        Err(_) if Object::read(&sha).is_ok() => Err(format!("'{}' is not a commit", rev)),
+        Err(e) => Err(format!("cannot read '{}': {}", rev, e)),
+    }
+}
+
+/// Resolve any object name to a SHA: a revision, "<rev>:<path>" for a file
+/// or directory in a commit, ":<path>" for a file in the index, or
+/// "<rev>^{<type>}" to peel tags (and commits) down to an object of that type
+pub fn resolve_object(name: &str) -> Result<String, String> {
+    if let Some((rev, path)) = name.split_once(':') {
+        let path = path.trim_matches('/');
+        if rev.is_empty() {
+            return Index::load()
+                .get(path)
+                .map(|entry| entry.sha.clone())
+                .ok_or_else(|| format!("path '{}' is not in the index", path));
+        }
+        let mut sha = resolve_commit(rev)?.1;
+        for part in path.split('/').filter(|part| !part.is_empty()) {
+            let tree = match Object::read(&sha)? {
+                Object::Tree(tree) => tree,
+                _ => return Err(format!("path '{}' does not exist in '{}'", path, rev)),
+            };
+            sha = tree
+                .entries
+                .into_iter()
+                .find(|entry| entry.name == part)
+                .map(|entry| entry.sha)
+                .ok_or_else(|| format!("path '{}' does not exist in '{}'", path, rev))?;
+        }
+        return Ok(sha);
+    }
+
+    if let Some(at) = name.rfind("^{")
+        && let Some(kind) = name[at + 2..].strip_suffix('}')
+    {
+        return peel(&name[..at], kind);
+    }
+
+    resolve_revision(name)
+}
+
+/// Resolve an object name and peel tags (and commits, for "tree") down to an
+/// object of type `kind`; an empty `kind` peels just the tags
+pub fn peel(name: &str, kind: &str) -> Result<String, String> {
+    let mut sha = resolve_object(name)?;
+    loop {
+        let object = Object::read(&sha)?;
+        let found = match &object {
+            Object::Blob(_) => "blob",
+            Object::Tree(_) => "tree",
+            Object::Commit(_) => "commit",
+            Object::Tag(_) => "tag",
+        };
+        if found == kind || (kind.is_empty() && found != "tag") {
+            return Ok(sha);
+        }
+        sha = match object {
+            Object::Tag(tag) => tag.object,
+            Object::Commit(commit) if kind == "tree" => commit.tree,
+            _ => return Err(format!("'{}' is not a {}", name, kind)),
+        };
+    }
+}
+
+/// One line of logs/<ref>
+pub struct ReflogEntry {
+    pub old: String,
+    pub new: String,
+    pub identity: String,
+    pub timestamp: u64,
+    pub timezone: String,
+    pub message: String,
+}
+
+/// Read a ref's reflog, oldest entry first
+pub fn read_reflog(refname: &str) -> Vec<ReflogEntry> {
+    let content = fs::read_to_string(repo::path(&format!("logs/{}", refname))).unwrap_or_default();
+    content.lines().filter_map(parse_reflog_line).collect()
+}
+
+fn parse_reflog_line(line: &str) -> Option<ReflogEntry> {
+    let (header, message) = line.split_once('\t').unwrap_or((line, ""));
+    let mut parts = header.splitn(3, ' ');
+    let old = parts.next()?.to_string();
+    let new = parts.next()?.to_string();
+
+    // "<name> <email> <timestamp> <tz>"
+    let mut ident = parts.next()?.rsplitn(3, ' ');
+    let timezone = ident.next()?.to_string();
+    let timestamp = ident.next()?.parse().ok()?;
+    let identity = ident.next()?.to_string();
+
+    Some(ReflogEntry {
+        old,
+        new,
+        identity,
+        timestamp,
+        timezone,
+        message: message.to_string(),
+    })
+}
+
+/// Replace a ref's whole reflog (used when dropping entries)
+pub fn write_reflog(refname: &str, entries: &[ReflogEntry]) -> Result<(), String> {
+    let path = repo::path(&format!("logs/{}", refname));
+    let content: String = entries
+        .iter()
+        .map(|e| {
+            format!(
+                "{} {} {} {} {}\t{}\n",
+                e.old, e.new, e.identity, e.timestamp, e.timezone, e.message
+            )
+        })
+        .collect();
+    fs::write(&path, content).map_err(|e| format!("cannot write reflog for {}: {}", refname, e))
+}
+
+/// Append one entry to logs/<refname>
+pub fn append_reflog(refname: &str, old: Option<&str>, new: &str, message: &str) {
+    let path = repo::path(&format!("logs/{}", refname));
+    if let Some(parent) = path.parent()
+        && fs::create_dir_all(parent).is_err()
+    {
+        return;
+    }
+
+    let line = format!(
+        "{} {} {}\t{}\n",
+        old.unwrap_or(NULL_SHA),
+        new,
+        ident::reflog_committer(),
+        message.lines().next().unwrap_or("")
+    );
+
+    // a reflog that can't be written shouldn't stop the ref update itself
+    if let Ok(mut file) = fs::OpenOptions::new().create(true).append(true).open(&path) {
+        let _ = file.write_all(line.as_bytes());
+    }
+}
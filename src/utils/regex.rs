@@ -0,0 +1,562 @@
+// regex: a tiny POSIX extended regex engine, because some problems need two problems
+
+/// Zero-width conditions a match can depend on
+#[derive(Clone, Copy)]
+enum Assertion {
+    /// `^`: start of the text or of a line
+    LineStart,
+    /// `$`: end of the text or of a line
+    LineEnd,
+    /// `\b`
+    WordBoundary,
+    /// `\B`
+    NotWordBoundary,
+    /// `\<`
+    WordStart,
+    /// `\>`
+    WordEnd,
+}
+
+enum Node {
+    Empty,
+    /// Any one byte marked in the table
+    Class(Box<[bool; 256]>),
+    Assert(Assertion),
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat(Box<Node>, usize, Option<usize>),
+}
+
+/// The most a `{n,m}` bound may be, like POSIX's RE_DUP_MAX
+const RE_DUP_MAX: usize = 255;
+
+/// The most instructions a compiled expression may have, since nested bounds
+/// multiply (`((x{255}){255}){255}` would take millions)
+const MAX_PROGRAM: usize = 100_000;
+
+#[derive(Clone)]
+enum Inst {
+    Class(Box<[bool; 256]>),
+    Assert(Assertion),
+    /// Try both branches
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+/// A compiled extended regular expression, as understood by `grep -E` and git's
+/// `--word-diff-regex`
+///
+/// Matching is leftmost-longest like POSIX, `.` and negated brackets don't match
+/// a newline, and `^`/`$` match at line boundaries. Bytes are matched one at a time.
+#[derive(Clone)]
+pub struct Regex {
+    program: Vec<Inst>,
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            pattern: pattern.as_bytes(),
+            pos: 0,
+        };
+        let node = parser.alternation()?;
+        if parser.pos < parser.pattern.len() {
+            return Err("Unmatched ) or \\)".to_string());
+        }
+        if size(&node).saturating_add(1) > MAX_PROGRAM {
+            return Err("Regular expression too big".to_string());
+        }
+        let mut program = Vec::new();
+        compile(&node, &mut program);
+        program.push(Inst::Match);
+        Ok(Regex { program })
+    }
+
+    /// A basic regular expression, like `grep` and git's `--grep` take by default:
+    /// `+ ? | ( ) { }` are ordinary characters unless escaped (GNU's extensions do
+    /// the rest), and `*`, `^` and `$` are too where they can't be operators
+    pub fn basic(pattern: &str) -> Result<Self, String> {
+        Regex::new(&basic_to_extended(pattern))
+    }
+
+    /// The same expression, but matching letters of either case
+    pub fn ignore_case(mut self) -> Self {
+        for inst in &mut self.program {
+            if let Inst::Class(class) = inst {
+                for b in b'a'..=b'z' {
+                    let upper = b.to_ascii_uppercase();
+                    let either = class[b as usize] || class[upper as usize];
+                    class[b as usize] = either;
+                    class[upper as usize] = either;
+                }
+            }
+        }
+        self
+    }
+
+    /// Whether the expression matches anywhere in `text`
+    pub fn is_match(&self, text: &[u8]) -> bool {
+        self.find_at(text, 0).is_some()
+    }
+
+    /// The first (and at that position longest) match at or after `start`, as a
+    /// byte range of `text`
+    pub fn find_at(&self, text: &[u8], start: usize) -> Option<(usize, usize)> {
+        (start..=text.len()).find_map(|from| self.longest_at(text, from).map(|end| (from, end)))
+    }
+
+    /// Where the longest match starting exactly at `from` ends, running every
+    /// possible path through the program in step (a Pike VM)
+    fn longest_at(&self, text: &[u8], from: usize) -> Option<usize> {
+        let mut seen = vec![usize::MAX; self.program.len()];
+        let mut current = Vec::new();
+        let mut longest = None;
+        self.add_thread(&mut current, &mut seen, 0, text, from);
+        let mut pos = from;
+        loop {
+            let mut next = Vec::new();
+            for &pc in &current {
+                match &self.program[pc] {
+                    Inst::Match => longest = Some(pos),
+                    Inst::Class(class) if pos < text.len() && class[text[pos] as usize] => {
+                        self.add_thread(&mut next, &mut seen, pc + 1, text, pos + 1);
+                    }
+                    _ => {}
+                }
+            }
+            if next.is_empty() {
+                return longest;
+            }
+            current = next;
+            pos += 1;
+        }
+    }
+
+    /// Queue `pc` and everything reachable from it without reading a byte
+    fn add_thread(
+        &self,
+        list: &mut Vec<usize>,
+        seen: &mut [usize],
+        pc: usize,
+        text: &[u8],
+        pos: usize,
+    ) {
+        if seen[pc] == pos {
+            return;
+        }
+        seen[pc] = pos;
+        match &self.program[pc] {
+            Inst::Jump(to) => self.add_thread(list, seen, *to, text, pos),
+            Inst::Split(a, b) => {
+                self.add_thread(list, seen, *a, text, pos);
+                self.add_thread(list, seen, *b, text, pos);
+            }
+            Inst::Assert(assertion) => {
+                if holds(*assertion, text, pos) {
+                    self.add_thread(list, seen, pc + 1, text, pos);
+                }
+            }
+            _ => list.push(pc),
+        }
+    }
+}
+
+fn holds(assertion: Assertion, text: &[u8], pos: usize) -> bool {
+    let word = |i: Option<usize>| i.and_then(|i| text.get(i)).is_some_and(|&b| is_word(b));
+    let before = word(pos.checked_sub(1));
+    let after = word(Some(pos));
+    match assertion {
+        Assertion::LineStart => pos == 0 || text[pos - 1] == b'\n',
+        Assertion::LineEnd => pos == text.len() || text[pos] == b'\n',
+        Assertion::WordBoundary => before != after,
+        Assertion::NotWordBoundary => before == after,
+        Assertion::WordStart => !before && after,
+        Assertion::WordEnd => before && !after,
+    }
+}
+
+fn is_word(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+/// How many instructions `compile` turns `node` into
+fn size(node: &Node) -> usize {
+    match node {
+        Node::Empty => 0,
+        Node::Class(_) | Node::Assert(_) => 1,
+        Node::Concat(nodes) => nodes
+            .iter()
+            .fold(0, |total, node| total.saturating_add(size(node))),
+        Node::Alternate(nodes) => nodes
+            .iter()
+            .fold(2 * nodes.len().saturating_sub(1), |total, node| {
+                total.saturating_add(size(node))
+            }),
+        Node::Repeat(node, min, max) => {
+            let one = size(node);
+            let optional = match max {
+                None => one.saturating_add(2),
+                Some(max) => one.saturating_add(1).saturating_mul(max - min),
+            };
+            one.saturating_mul(*min).saturating_add(optional)
+        }
+    }
+}
+
+fn compile(node: &Node, program: &mut Vec<Inst>) {
+    match node {
+        Node::Empty => {}
+        Node::Class(class) => program.push(Inst::Class(class.clone())),
+        Node::Assert(assertion) => program.push(Inst::Assert(*assertion)),
+        Node::Concat(nodes) => nodes.iter().for_each(|node| compile(node, program)),
+        Node::Alternate(nodes) => {
+            // split to each branch in turn, every branch jumping to the end
+            let mut jumps = Vec::new();
+            for (i, node) in nodes.iter().enumerate() {
+                let split = program.len();
+                if i + 1 < nodes.len() {
+                    program.push(Inst::Split(split + 1, 0));
+                }
+                compile(node, program);
+                if i + 1 < nodes.len() {
+                    jumps.push(program.len());
+                    program.push(Inst::Jump(0));
+                    let next = program.len();
+                    program[split] = Inst::Split(split + 1, next);
+                }
+            }
+            let end = program.len();
+            for jump in jumps {
+                program[jump] = Inst::Jump(end);
+            }
+        }
+        Node::Repeat(node, min, max) => {
+            for _ in 0..*min {
+                compile(node, program);
+            }
+            match max {
+                None => {
+                    let split = program.len();
+                    program.push(Inst::Split(split + 1, 0));
+                    compile(node, program);
+                    program.push(Inst::Jump(split));
+                    let end = program.len();
+                    program[split] = Inst::Split(split + 1, end);
+                }
+                Some(max) => {
+                    let mut splits = Vec::new();
+                    for _ in *min..*max {
+                        splits.push(program.len());
+                        program.push(Inst::Split(0, 0));
+                        compile(node, program);
+                    }
+                    let end = program.len();
+                    for split in splits {
+                        program[split] = Inst::Split(split + 1, end);
+                    }
+                }
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    pattern: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.pattern.get(self.pos).copied()
+    }
+
+    fn alternation(&mut self) -> Result<Node, String> {
+        let mut branches = vec![self.concatenation()?];
+        while self.peek() == Some(b'|') {
+            self.pos += 1;
+            branches.push(self.concatenation()?);
+        }
+        Ok(match branches.len() {
+            1 => branches.pop().unwrap_or(Node::Empty),
+            _ => Node::Alternate(branches),
+        })
+    }
+
+    fn concatenation(&mut self) -> Result<Node, String> {
+        let mut nodes = Vec::new();
+        while let Some(byte) = self.peek() {
+            if byte == b'|' || byte == b')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.quantified(atom)?);
+        }
+        Ok(Node::Concat(nodes))
+    }
+
+    fn quantified(&mut self, mut node: Node) -> Result<Node, String> {
+        loop {
+            let (min, max) = match self.peek() {
+                Some(b'*') => (0, None),
+                Some(b'+') => (1, None),
+                Some(b'?') => (0, Some(1)),
+                Some(b'{') => {
+                    self.pos += 1;
+                    let bounds = self.bounds()?;
+                    self.pos -= 1;
+                    bounds
+                }
+                _ => return Ok(node),
+            };
+            self.pos += 1;
+            if matches!(node, Node::Assert(_)) {
+                return Err("Invalid preceding regular expression".to_string());
+            }
+            node = Node::Repeat(Box::new(node), min, max);
+        }
+    }
+
+    /// "n}", "n,}" or "n,m}" after a '{', leaving pos just past the '}'
+    fn bounds(&mut self) -> Result<(usize, Option<usize>), String> {
+        let invalid = || "Invalid content of \\{\\}".to_string();
+        let min = self.number().ok_or_else(invalid)?;
+        let max = if self.peek() == Some(b',') {
+            self.pos += 1;
+            self.number()
+        } else {
+            Some(min)
+        };
+        if self.peek() != Some(b'}')
+            || max.is_some_and(|max| max < min || max > RE_DUP_MAX)
+            || min > RE_DUP_MAX
+        {
+            return Err(invalid());
+        }
+        self.pos += 1;
+        Ok((min, max))
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let start = self.pos;
+        while self.peek().is_some_and(|b| b.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.pattern[start..self.pos])
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        let Some(byte) = self.peek() else {
+            return Ok(Node::Empty);
+        };
+        self.pos += 1;
+        Ok(match byte {
+            b'(' => {
+                let node = self.alternation()?;
+                if self.peek() != Some(b')') {
+                    return Err("Unmatched ( or \\(".to_string());
+                }
+                self.pos += 1;
+                node
+            }
+            b'*' | b'+' | b'?' | b'{' => {
+                return Err("Invalid preceding regular expression".to_string());
+            }
+            b'^' => Node::Assert(Assertion::LineStart),
+            b'$' => Node::Assert(Assertion::LineEnd),
+            b'.' => {
+                let mut class = [true; 256];
+                class[b'\n' as usize] = false;
+                Node::Class(Box::new(class))
+            }
+            b'[' => Node::Class(Box::new(self.bracket()?)),
+            b'\\' => {
+                let Some(escaped) = self.peek() else {
+                    return Err("Trailing backslash".to_string());
+                };
+                self.pos += 1;
+                match escaped {
+                    b'b' => Node::Assert(Assertion::WordBoundary),
+                    b'B' => Node::Assert(Assertion::NotWordBoundary),
+                    b'<' => Node::Assert(Assertion::WordStart),
+                    b'>' => Node::Assert(Assertion::WordEnd),
+                    b'w' | b'W' | b's' | b'S' => {
+                        let test: fn(u8) -> bool = match escaped.to_ascii_lowercase() {
+                            b'w' => is_word,
+                            _ => |b: u8| b.is_ascii_whitespace() || b == 0x0b,
+                        };
+                        let negate = escaped.is_ascii_uppercase();
+                        let mut class = [false; 256];
+                        for (b, slot) in class.iter_mut().enumerate() {
+                            *slot = test(b as u8) != negate;
+                        }
+                        Node::Class(Box::new(class))
+                    }
+                    _ => literal(escaped),
+                }
+            }
+            _ => literal(byte),
+        })
+    }
+
+    /// The inside of a bracket expression, after its '['
+    fn bracket(&mut self) -> Result<[bool; 256], String> {
+        let unmatched = || "Unmatched [, [^, [:, [., or [=".to_string();
+        let mut class = [false; 256];
+        let negate = self.peek() == Some(b'^');
+        if negate {
+            self.pos += 1;
+        }
+        let mut first = true;
+        loop {
+            let byte = self.peek().ok_or_else(unmatched)?;
+            self.pos += 1;
+            if byte == b']' && !first {
+                break;
+            }
+            first = false;
+            if byte == b'[' && self.peek() == Some(b':') {
+                let rest = &self.pattern[self.pos + 1..];
+                let end = rest
+                    .windows(2)
+                    .position(|w| w == b":]")
+                    .ok_or_else(unmatched)?;
+                let name = std::str::from_utf8(&rest[..end]).unwrap_or_default();
+                let test = named_class(name).ok_or("Invalid character class name")?;
+                for (b, slot) in class.iter_mut().enumerate() {
+                    *slot |= test(b as u8);
+                }
+                self.pos += end + 3;
+                continue;
+            }
+            let mut last = byte;
+            if self.peek() == Some(b'-')
+                && self.pattern.get(self.pos + 1).is_some_and(|&b| b != b']')
+            {
+                last = self.pattern[self.pos + 1];
+                self.pos += 2;
+                if last < byte {
+                    return Err("Invalid range end".to_string());
+                }
+            }
+            for b in byte..=last {
+                class[b as usize] = true;
+            }
+        }
+        if negate {
+            for slot in class.iter_mut() {
+                *slot = !*slot;
+            }
+            class[b'\n' as usize] = false;
+        }
+        Ok(class)
+    }
+}
+
+/// Rewrite a basic regular expression as the equivalent extended one
+fn basic_to_extended(pattern: &str) -> String {
+    let bytes = pattern.as_bytes();
+    let mut out = String::new();
+    // where an operator can't go, so '*' is literal there and '^' an anchor
+    let mut at_start = true;
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i] as char;
+        let start = at_start;
+        at_start = false;
+        match c {
+            '\\' if i + 1 < bytes.len() => {
+                let next = bytes[i + 1] as char;
+                i += 1;
+                match next {
+                    '(' | '|' => {
+                        out.push(next);
+                        at_start = true;
+                    }
+                    ')' | '{' | '}' | '+' | '?' => out.push(next),
+                    _ => {
+                        out.push('\\');
+                        out.push(next);
+                    }
+                }
+            }
+            '+' | '?' | '|' | '(' | ')' | '{' | '}' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '*' if start => out.push_str("\\*"),
+            '^' if start => {
+                out.push(c);
+                at_start = true;
+            }
+            '^' => out.push_str("\\^"),
+            '$' if !ends_expression(&bytes[i + 1..]) => out.push_str("\\$"),
+            '[' => {
+                // a bracket expression is the same in both, so copy it as it is
+                let end = bracket_end(bytes, i + 1).unwrap_or(bytes.len());
+                out.push_str(&pattern[i..end]);
+                i = end;
+                continue;
+            }
+            _ => out.push(c),
+        }
+        i += 1;
+    }
+    out
+}
+
+/// Whether a '$' followed by `rest` ends the expression or a group, and so is an anchor
+fn ends_expression(rest: &[u8]) -> bool {
+    rest.is_empty() || rest.starts_with(b"\\)") || rest.starts_with(b"\\|")
+}
+
+/// Just past the ']' closing a bracket expression whose inside starts at `from`
+fn bracket_end(bytes: &[u8], from: usize) -> Option<usize> {
+    let mut i = from;
+    if bytes.get(i) == Some(&b'^') {
+        i += 1;
+    }
+    // a ']' straight away is part of the set
+    if bytes.get(i) == Some(&b']') {
+        i += 1;
+    }
+    while i < bytes.len() {
+        match bytes[i] {
+            b'[' if bytes.get(i + 1) == Some(&b':') => {
+                let close = bytes[i + 2..].windows(2).position(|w| w == b":]")?;
+                i += close + 4;
+            }
+            b']' => return Some(i + 1),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+fn literal(byte: u8) -> Node {
+    let mut class = [false; 256];
+    class[byte as usize] = true;
+    Node::Class(Box::new(class))
+}
+
+/// The POSIX `[:name:]` classes, in the C locale
+fn named_class(name: &str) -> Option<fn(u8) -> bool> {
+    Some(match name {
+        "alpha" => |b: u8| b.is_ascii_alphabetic(),
+        "digit" => |b: u8| b.is_ascii_digit(),
+        "alnum" => |b: u8| b.is_ascii_alphanumeric(),
+        "upper" => |b: u8| b.is_ascii_uppercase(),
+        "lower" => |b: u8| b.is_ascii_lowercase(),
+        "space" => |b: u8| b.is_ascii_whitespace() || b == 0x0b,
+        "blank" => |b: u8| b == b' ' || b == b'\t',
+        "punct" => |b: u8| b.is_ascii_punctuation(),
+        "print" => |b: u8| (0x20..0x7f).contains(&b),
+        "graph" => |b: u8| b.is_ascii_graphic(),
+        "cntrl" => |b: u8| b.is_ascii_control(),
+        "xdigit" => |b: u8| b.is_ascii_hexdigit(),
+        _ => return None,
+    })
+}
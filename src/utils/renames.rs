@@ -0,0 +1,344 @@
+// renames: files don't move, they die and someone suspiciously similar shows up elsewhere
+
+use crate::utils::config;
+use crate::utils::diff::{FilePair, Side, Snapshot, is_binary};
+use std::collections::HashMap;
+
+/// Similarity scores are out of this, like git's
+pub const MAX_SCORE: u64 = 60000;
+
+/// How hard to look for renamed and copied files
+#[derive(Clone, Copy)]
+pub struct RenameOptions {
+    /// Pair up deleted and added files at all (`-M`, `--no-renames`)
+    pub renames: bool,
+    /// Also look for copies of files changed in the same diff (`-C`)
+    pub copies: bool,
+    /// ...or of any file at all (`--find-copies-harder`)
+    pub find_copies_harder: bool,
+    /// How similar two files must be to count, out of MAX_SCORE (`-M<n>`, 50% by default)
+    pub threshold: u64,
+    /// Only score files against each other when there are at most this many
+    /// on each side (`diff.renameLimit`); exact matches are always found
+    pub limit: usize,
+}
+
+impl Default for RenameOptions {
+    fn default() -> Self {
+        RenameOptions::configured("diff.renames")
+    }
+}
+
+impl RenameOptions {
+    /// Options from `key` (e.g. `status.renames`), falling back to `diff.renames`:
+    /// a boolean, or "copies" to look for copies too
+    pub fn configured(key: &str) -> Self {
+        let value = config::get(key)
+            .or_else(|| config::get("diff.renames"))
+            .map(|v| v.to_lowercase());
+        let copies = matches!(value.as_deref(), Some("copy" | "copies"));
+        let renames =
+            copies || !matches!(value.as_deref(), Some("false" | "no" | "off" | "0" | ""));
+        RenameOptions {
+            renames,
+            copies,
+            find_copies_harder: false,
+            threshold: MAX_SCORE / 2,
+            limit: config::get("diff.renameLimit")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1000),
+        }
+    }
+
+    /// Apply a command-line flag if it's one of ours: `-M[<n>]`, `--find-renames[=<n>]`,
+    /// `-C[<n>]`, `--find-copies[=<n>]`, `--find-copies-harder` or `--no-renames`
+    ///
+    /// Returns false for anything else, including a malformed score.
+    pub fn parse_flag(&mut self, flag: &str) -> bool {
+        let (copies, score) = if let Some(score) = flag
+            .strip_prefix("-M")
+            .or_else(|| flag.strip_prefix("--find-renames="))
+        {
+            (false, score)
+        } else if let Some(score) = flag
+            .strip_prefix("-C")
+            .or_else(|| flag.strip_prefix("--find-copies="))
+        {
+            (true, score)
+        } else {
+            match flag {
+                "--find-renames" => (false, ""),
+                "--find-copies" => (true, ""),
+                "--find-copies-harder" => {
+                    self.renames = true;
+                    self.copies = true;
+                    self.find_copies_harder = true;
+                    return true;
+                }
+                "--no-renames" => {
+                    self.renames = false;
+                    self.copies = false;
+                    return true;
+                }
+                _ => return false,
+            }
+        };
+        if !score.is_empty() {
+            match parse_score(score) {
+                Some(threshold) => self.threshold = threshold,
+                None => return false,
+            }
+        }
+        self.renames = true;
+        self.copies |= copies;
+        true
+    }
+}
+
+/// Read a similarity like git: "90%" is 90%, and bare digits are a fraction,
+/// so "5" is 50% and "05" is 5%
+pub fn parse_score(text: &str) -> Option<u64> {
+    let (mut num, mut scale) = (0u64, 1u64);
+    let mut dot = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '%' => {
+                scale = if dot { scale * 100 } else { 100 };
+                if chars.peek().is_some() {
+                    return None;
+                }
+            }
+            '.' if !dot => dot = true,
+            '0'..='9' => {
+                if scale < 100000 {
+                    scale *= 10;
+                    num = num * 10 + c.to_digit(10).unwrap_or(0) as u64;
+                }
+            }
+            _ => return None,
+        }
+    }
+    Some(if num >= scale {
+        MAX_SCORE
+    } else {
+        MAX_SCORE * num / scale
+    })
+}
+
+/// A file that could have been renamed or copied to one of the added files
+struct Source {
+    side: Side,
+    /// Gone from the new side, so it can be renamed rather than just copied
+    deleted: bool,
+    /// Already matched up, which only copies can do twice
+    used: bool,
+}
+
+/// Turn added/deleted pairs in `pairs` into renames (and copies, if asked)
+/// where their contents are similar enough
+///
+/// Exact matches are found first, preferring a file with the same name, then
+/// the rest are scored against each other and matched up best-first. `old`
+/// is the full old side, used as copy sources with `find_copies_harder`.
+pub fn detect(pairs: Vec<FilePair>, old: &Snapshot, options: &RenameOptions) -> Vec<FilePair> {
+    if !options.renames {
+        return pairs;
+    }
+
+    let mut sources: Vec<Source> = Vec::new();
+    for pair in &pairs {
+        match (&pair.old, &pair.new) {
+            (Some(side), None) => sources.push(Source {
+                side: side.clone(),
+                deleted: true,
+                used: false,
+            }),
+            (Some(side), Some(_)) if options.copies => sources.push(Source {
+                side: side.clone(),
+                deleted: false,
+                used: false,
+            }),
+            _ => {}
+        }
+    }
+    if options.find_copies_harder {
+        for side in old.values() {
+            if !sources.iter().any(|source| source.side.path == side.path) {
+                sources.push(Source {
+                    side: side.clone(),
+                    deleted: false,
+                    used: false,
+                });
+            }
+        }
+    }
+
+    let added: Vec<usize> = (0..pairs.len())
+        .filter(|&i| pairs[i].old.is_none())
+        .collect();
+    if added.is_empty() || sources.is_empty() {
+        return pairs;
+    }
+
+    // (index into pairs, index into sources, score)
+    let mut matches: Vec<(usize, usize, u64)> = Vec::new();
+    let mut unmatched = Vec::new();
+    for &i in &added {
+        let new = pairs[i].new.as_ref().map(|side| side.path.as_str());
+        let exact: Vec<usize> = (0..sources.len())
+            .filter(|&s| {
+                pairs[i]
+                    .new
+                    .as_ref()
+                    .is_some_and(|side| side.sha == sources[s].side.sha)
+            })
+            .filter(|&s| options.copies || !sources[s].used)
+            .collect();
+        // an unused deleted file first, ideally with the same name
+        let pick = exact
+            .iter()
+            .copied()
+            .filter(|&s| sources[s].deleted && !sources[s].used)
+            .min_by_key(|&s| basename(&sources[s].side.path) != new.map(basename).unwrap_or(""))
+            .or_else(|| exact.first().copied());
+        match pick {
+            Some(s) => {
+                sources[s].used = true;
+                matches.push((i, s, MAX_SCORE));
+            }
+            None => unmatched.push(i),
+        }
+    }
+
+    let candidates: Vec<usize> = (0..sources.len())
+        .filter(|&s| options.copies || !sources[s].used)
+        .collect();
+    if !unmatched.is_empty()
+        && !candidates.is_empty()
+        && unmatched.len() <= options.limit
+        && candidates.len() <= options.limit
+    {
+        let contents: HashMap<usize, Vec<u8>> = candidates
+            .iter()
+            .map(|&s| (s, sources[s].side.content()))
+            .collect();
+        let mut scored = Vec::new();
+        for &i in &unmatched {
+            let new = pairs[i].new.as_ref().map(Side::content).unwrap_or_default();
+            for &s in &candidates {
+                let score = similarity(&contents[&s], &new, options.threshold);
+                if score >= options.threshold {
+                    scored.push((i, s, score));
+                }
+            }
+        }
+        // best first; ties go to the earlier path on either side
+        scored.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)).then(a.1.cmp(&b.1)));
+        let mut taken = vec![false; pairs.len()];
+        for (i, s, score) in scored {
+            if taken[i] || (!options.copies && sources[s].used) {
+                continue;
+            }
+            taken[i] = true;
+            sources[s].used = true;
+            matches.push((i, s, score));
+        }
+    }
+
+    // like git, a deleted file is renamed to the last of its matches (by
+    // path) and copied to the others
+    let mut rename_to = vec![None; sources.len()];
+    for &(i, s, _) in &matches {
+        if sources[s].deleted {
+            rename_to[s] = rename_to[s].max(Some(i));
+        }
+    }
+    let mut pairs = pairs;
+    for (i, s, score) in matches {
+        pairs[i].old = Some(sources[s].side.clone());
+        pairs[i].score = score;
+        pairs[i].copy = rename_to[s] != Some(i);
+    }
+    let renamed_from: Vec<&str> = (0..sources.len())
+        .filter(|&s| rename_to[s].is_some())
+        .map(|s| sources[s].side.path.as_str())
+        .collect();
+    let mut result: Vec<FilePair> = pairs
+        .into_iter()
+        .filter(|pair| {
+            !(pair.new.is_none()
+                && pair
+                    .old
+                    .as_ref()
+                    .is_some_and(|side| renamed_from.contains(&side.path.as_str())))
+        })
+        .collect();
+    result.sort_by(|a, b| a.path().cmp(b.path()));
+    result
+}
+
+fn basename(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// How much of `old` survives in `new`, out of MAX_SCORE, like git's estimate:
+/// both are cut into lines (or 64-byte pieces) and the bytes of matching
+/// pieces are counted, relative to the bigger file
+///
+/// Files whose sizes alone rule out reaching `threshold` score 0.
+pub fn similarity(old: &[u8], new: &[u8], threshold: u64) -> u64 {
+    let max_size = old.len().max(new.len()) as u64;
+    let delta = old.len().abs_diff(new.len()) as u64;
+    if max_size == 0 || max_size * (MAX_SCORE - threshold.min(MAX_SCORE)) < delta * MAX_SCORE {
+        return 0;
+    }
+
+    let (copied, _) = count_changes(old, new);
+    copied as u64 * MAX_SCORE / max_size
+}
+
+/// Bytes of `old` that are still in `new`, and bytes `new` added, going by
+/// whole lines like `similarity`
+pub fn count_changes(old: &[u8], new: &[u8]) -> (usize, usize) {
+    let old_chunks = chunks(old);
+    let new_chunks = chunks(new);
+    let copied = old_chunks
+        .iter()
+        .map(|(chunk, &count)| count.min(new_chunks.get(chunk).copied().unwrap_or(0)))
+        .sum();
+    let added = new_chunks
+        .iter()
+        .map(|(chunk, &count)| count.saturating_sub(old_chunks.get(chunk).copied().unwrap_or(0)))
+        .sum();
+    (copied, added)
+}
+
+/// Bytes in each distinct line (by hash, as git does), lines longer than 64
+/// bytes being cut into pieces; in text "\r\n" counts as "\n", and like git
+/// an unfinished last line is left out
+fn chunks(data: &[u8]) -> HashMap<u32, usize> {
+    /// git's hash table size; lines that collide count as the same
+    const HASHBASE: u32 = 107927;
+
+    let text = !is_binary(data);
+    let mut counts: HashMap<u32, usize> = HashMap::new();
+    let (mut accum1, mut accum2, mut len) = (0u32, 0u32, 0usize);
+    for (k, &byte) in data.iter().enumerate() {
+        if text && byte == b'\r' && data.get(k + 1) == Some(&b'\n') {
+            continue;
+        }
+        let old = accum1;
+        accum1 = (accum1 << 7) ^ (accum2 >> 25);
+        accum2 = (accum2 << 7) ^ (old >> 25);
+        accum1 = accum1.wrapping_add(byte as u32);
+        len += 1;
+        if len < 64 && byte != b'\n' {
+            continue;
+        }
+        let hash = accum1.wrapping_add(accum2.wrapping_mul(0x61)) % HASHBASE;
+        *counts.entry(hash).or_default() += len;
+        (accum1, accum2, len) = (0, 0, 0);
+    }
+    counts
+}
@@ -0,0 +1,80 @@
+// where the repository actually lives, which stopped being obvious once worktrees showed up
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Files and directories shared by every worktree; everything else
+/// (HEAD, index, logs/HEAD, ORIG_HEAD, ...) belongs to a single worktree
+const COMMON_PATHS: [&str; 7] = [
+    "objects",
+    "refs",
+    "config",
+    "hooks",
+    "logs/refs",
+    "worktrees",
+    "info",
+];
+
+/// The current worktree's admin directory
+///
+/// That's `.hit` itself in the main worktree. In a linked worktree `.hit` is
+/// a file holding "hitdir: <path>", pointing into `.hit/worktrees/<name>` of
+/// the main repository. HIT_DIR overrides all of that (hooks get it set).
+pub fn hit_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("HIT_DIR").filter(|d| !d.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    let dot_hit = PathBuf::from(".hit");
+    if dot_hit.is_file()
+        && let Ok(content) = fs::read_to_string(&dot_hit)
+        && let Some(target) = content.trim().strip_prefix("hitdir:")
+    {
+        return PathBuf::from(target.trim());
+    }
+    dot_hit
+}
+
+/// True if there's a repository here: `hit_dir` names a directory, so `.hit`
+/// is one or its "hitdir:" line points at one
+pub fn exists() -> bool {
+    hit_dir().is_dir()
+}
+
+/// The directory holding objects, refs and config shared by all worktrees
+pub fn common_dir() -> PathBuf {
+    let dir = hit_dir();
+    match fs::read_to_string(dir.join("commondir")) {
+        Ok(common) => dir.join(common.trim()),
+        Err(_) => dir,
+    }
+}
+
+/// Resolve a path inside the repository, e.g. path("HEAD") or path("refs/heads/master")
+///
+/// The index can be swapped out with HIT_INDEX_FILE.
+pub fn path(rel: &str) -> PathBuf {
+    if rel == "index"
+        && let Some(index) = env::var_os("HIT_INDEX_FILE").filter(|i| !i.is_empty())
+    {
+        return PathBuf::from(index);
+    }
+    let shared = COMMON_PATHS
+        .iter()
+        .any(|p| rel == *p || (rel.starts_with(p) && rel.as_bytes().get(p.len()) == Some(&b'/')));
+    if shared {
+        common_dir().join(rel)
+    } else {
+        hit_dir().join(rel)
+    }
+}
+
+/// Path of a loose object
+pub fn object_path(sha: &str) -> PathBuf {
+    path("objects").join(&sha[..2]).join(&sha[2..])
+}
+
+/// True if we are inside a linked (non-main) worktree
+pub fn is_linked_worktree() -> bool {
+    hit_dir().join("commondir").is_file()
+}
@@ -0,0 +1,345 @@
+// revwalk: a stroll back through history, newest first, never the same commit twice
+
+use crate::utils::objects::{Commit, Object};
+use crate::utils::refs;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+/// How to walk (`--topo-order`, `--reverse`, ...)
+#[derive(Clone, Default)]
+pub struct WalkOptions {
+    /// No parent before any of its children, each line of history kept together
+    pub topo_order: bool,
+    /// Oldest first; applied after `max_count`, like git
+    pub reverse: bool,
+    /// Only follow the first parent of merges
+    pub first_parent: bool,
+    /// Only commits that descend from a left out one (`a` in `a..b`)
+    pub ancestry_path: bool,
+    /// Stop after this many commits
+    pub max_count: Option<usize>,
+}
+
+/// A commit waiting to be walked: the newest comes out first, and of
+/// those made at the same time, the one found first
+struct Queued {
+    time: i64,
+    order: Reverse<usize>,
+    sha: String,
+    commit: Commit,
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.time, self.order).cmp(&(other.time, other.order))
+    }
+}
+
+/// Picks the one parent of a merge to follow, from its relevant parents
+type Pick<'a> = Box<dyn Fn(&Commit, &[String]) -> Option<String> + 'a>;
+
+/// Walks commits from newest to oldest, through every parent, each commit
+/// once; revisions like "^<rev>" and "<a>..<b>" leave out everything
+/// reachable from what they exclude
+pub struct RevWalk<'a> {
+    options: WalkOptions,
+    /// Everything reachable from an excluded commit
+    excluded: HashSet<String>,
+    /// The excluded commits as named, where `--ancestry-path` starts from
+    bottoms: Vec<String>,
+    /// With `--ancestry-path`, the bottoms and what descends from them,
+    /// once the walk is sorted
+    descendants: Option<HashSet<String>>,
+    seen: HashSet<String>,
+    queue: BinaryHeap<Queued>,
+    /// How many commits have been queued, to break ties in date
+    queued: usize,
+    /// How many commits have been walked, for `max_count`
+    walked: usize,
+    /// The whole walk, when it has to be worked out before the first commit
+    /// can come out (topo order, reverse, ancestry path)
+    sorted: Option<std::vec::IntoIter<(String, Commit)>>,
+    /// For a merge, the one parent to follow instead of all of them
+    simplify: Option<Pick<'a>>,
+}
+
+impl<'a> RevWalk<'a> {
+    /// Walk from `revs` ("<rev>", "^<rev>", "<a>..<b>", where an empty side
+    /// means HEAD) and with `all` from every ref and HEAD too; HEAD when
+    /// nothing is given
+    pub fn new(revs: &[String], all: bool, options: WalkOptions) -> Result<Self, String> {
+        let mut starts = Vec::new();
+        let mut stops = Vec::new();
+        for rev in revs {
+            if let Some((from, to)) = rev.split_once("..") {
+                stops.push(if from.is_empty() { "HEAD" } else { from });
+                starts.push(if to.is_empty() { "HEAD" } else { to });
+            } else if let Some(rev) = rev.strip_prefix('^') {
+                stops.push(rev);
+            } else {
+                starts.push(rev);
+            }
+        }
+        if revs.is_empty() && !all {
+            starts.push("HEAD");
+        }
+        let resolve = |rev: &str| refs::resolve_commit(rev).map(|(sha, _)| sha);
+        let mut starts = starts
+            .into_iter()
+            .map(resolve)
+            .collect::<Result<Vec<_>, _>>()?;
+        if all {
+            // refs that aren't commits, like a tag of a tree, are left out
+            let tips = refs::all_refs().into_iter().map(|(_, sha)| sha);
+            starts
+                .extend(tips.filter_map(|sha| refs::peel_to_commit(&sha).ok().map(|(sha, _)| sha)));
+            starts.extend(resolve("HEAD").ok());
+        }
+        let bottoms = stops
+            .into_iter()
+            .map(resolve)
+            .collect::<Result<Vec<_>, _>>()?;
+        if options.ancestry_path && bottoms.is_empty() {
+            return Err("--ancestry-path given but there are no bottom commits".to_string());
+        }
+
+        // everything reachable from a stop, through every parent
+        let mut excluded = HashSet::new();
+        let mut pending = bottoms.clone();
+        while let Some(sha) = pending.pop() {
+            if excluded.insert(sha.clone())
+                && let Some(commit) = read_commit(&sha)
+            {
+                pending.extend(commit.parents);
+            }
+        }
+
+        let mut walk = RevWalk {
+            options,
+            excluded,
+            bottoms,
+            descendants: None,
+            seen: HashSet::new(),
+            queue: BinaryHeap::new(),
+            queued: 0,
+            walked: 0,
+            sorted: None,
+            simplify: None,
+        };
+        for sha in starts {
+            walk.push(sha);
+        }
+        Ok(walk)
+    }
+
+    /// Follow only the parent `pick` chooses for a merge from its relevant
+    /// parents, when it chooses one: how `log -- <path>` leaves out the side
+    /// a merge took nothing from
+    pub fn simplify(&mut self, pick: impl Fn(&Commit, &[String]) -> Option<String> + 'a) {
+        self.simplify = Some(Box::new(pick));
+    }
+
+    /// The parents the walk follows from `commit`
+    pub fn parents(&self, commit: &Commit) -> Vec<String> {
+        match self.options.first_parent {
+            true => commit.parents.iter().take(1).cloned().collect(),
+            false => self.simplified_parents(commit),
+        }
+    }
+
+    /// Every parent of `commit`, or the one `simplify` picks: like git,
+    /// `--first-parent` changes where the walk goes but not the order
+    pub fn simplified_parents(&self, commit: &Commit) -> Vec<String> {
+        if commit.parents.len() > 1
+            && let Some(pick) = &self.simplify
+            && let Some(parent) = pick(commit, &self.relevant_parents(commit))
+        {
+            return vec![parent];
+        }
+        commit.parents.clone()
+    }
+
+    /// The parents of `commit` that aren't left out (or were named to be
+    /// left out, like `a` in `a..b`), which are the ones that count when
+    /// deciding what a merge changed; only the first with `--first-parent`,
+    /// and only those on the ancestry path with `--ancestry-path`
+    pub fn relevant_parents(&self, commit: &Commit) -> Vec<String> {
+        let parents = match self.options.first_parent {
+            true => &commit.parents[..commit.parents.len().min(1)],
+            false => &commit.parents[..],
+        };
+        parents
+            .iter()
+            .filter(|parent| match &self.descendants {
+                Some(descendants) => descendants.contains(*parent),
+                None => !self.excluded.contains(*parent) || self.bottoms.contains(parent),
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Whether `sha` is reachable from a commit left out, or off the
+    /// ancestry path with `--ancestry-path`
+    pub fn is_excluded(&self, sha: &str) -> bool {
+        self.excluded.contains(sha)
+            || self
+                .descendants
+                .as_ref()
+                .is_some_and(|descendants| !descendants.contains(sha))
+    }
+
+    fn push(&mut self, sha: String) {
+        if self.excluded.contains(&sha) || !self.seen.insert(sha.clone()) {
+            return;
+        }
+        if let Some(commit) = read_commit(&sha) {
+            self.queued += 1;
+            self.queue.push(Queued {
+                time: commit.committer.timestamp,
+                order: Reverse(self.queued),
+                sha,
+                commit,
+            });
+        }
+    }
+
+    /// The newest commit waiting, putting its parents in the queue
+    fn next_by_date(&mut self) -> Option<(String, Commit)> {
+        let Queued { sha, commit, .. } = self.queue.pop()?;
+        for parent in self.parents(&commit) {
+            self.push(parent);
+        }
+        Some((sha, commit))
+    }
+
+    /// The whole walk at once, in the order asked for
+    fn sort(&mut self) -> Vec<(String, Commit)> {
+        let mut commits = Vec::new();
+        while let Some(entry) = self.next_by_date() {
+            commits.push(entry);
+        }
+        if self.options.ancestry_path {
+            commits = self.ancestry_path(commits);
+        }
+        if self.options.topo_order {
+            commits = self.topo_order(commits);
+        }
+        if let Some(max) = self.options.max_count {
+            commits.truncate(max);
+        }
+        if self.options.reverse {
+            commits.reverse();
+        }
+        commits
+    }
+
+    /// Only the commits with an excluded commit named as an ancestor
+    fn ancestry_path(&mut self, commits: Vec<(String, Commit)>) -> Vec<(String, Commit)> {
+        let mut descendants: HashSet<&str> = self.bottoms.iter().map(String::as_str).collect();
+        // oldest first, so parents are usually marked before their children;
+        // clocks can be wrong though, so go round until nothing changes
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (sha, commit) in commits.iter().rev() {
+                if !descendants.contains(sha.as_str())
+                    && self
+                        .parents(commit)
+                        .iter()
+                        .any(|parent| descendants.contains(parent.as_str()))
+                {
+                    descendants.insert(sha);
+                    changed = true;
+                }
+            }
+        }
+        let descendants: HashSet<String> = descendants.into_iter().map(str::to_string).collect();
+        let commits = commits
+            .into_iter()
+            .filter(|(sha, _)| descendants.contains(sha))
+            .collect();
+        self.descendants = Some(descendants);
+        commits
+    }
+
+    /// Reordered so that no parent comes before any of its children,
+    /// keeping each line of history together like git: the newest tip
+    /// first, and after a merge, its last parent's side first
+    fn topo_order(&self, commits: Vec<(String, Commit)>) -> Vec<(String, Commit)> {
+        // how many children each commit has among those walked
+        let mut children: HashMap<String, usize> =
+            commits.iter().map(|(sha, _)| (sha.clone(), 0)).collect();
+        for (_, commit) in &commits {
+            for parent in self.simplified_parents(commit) {
+                if let Some(count) = children.get_mut(&parent) {
+                    *count += 1;
+                }
+            }
+        }
+        let tips: Vec<String> = commits
+            .iter()
+            .filter(|(sha, _)| children[sha] == 0)
+            .map(|(sha, _)| sha.clone())
+            .collect();
+        let mut by_sha: HashMap<String, Commit> = commits.into_iter().collect();
+
+        // a stack, so a parent is taken as soon as its last child is out
+        let mut stack: Vec<String> = tips.into_iter().rev().collect();
+        let mut sorted = Vec::new();
+        while let Some(sha) = stack.pop() {
+            let Some(commit) = by_sha.remove(&sha) else {
+                continue;
+            };
+            for parent in self.simplified_parents(&commit) {
+                if let Some(count) = children.get_mut(&parent) {
+                    *count -= 1;
+                    if *count == 0 {
+                        stack.push(parent);
+                    }
+                }
+            }
+            sorted.push((sha, commit));
+        }
+        sorted
+    }
+}
+
+impl Iterator for RevWalk<'_> {
+    type Item = (String, Commit);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let options = &self.options;
+        if options.topo_order || options.reverse || options.ancestry_path {
+            if self.sorted.is_none() {
+                self.sorted = Some(self.sort().into_iter());
+            }
+            return self.sorted.as_mut()?.next();
+        }
+        if self.options.max_count.is_some_and(|max| self.walked >= max) {
+            return None;
+        }
+        self.walked += 1;
+        self.next_by_date()
+    }
+}
+
+fn read_commit(sha: &str) -> Option<Commit> {
+    match Object::read(sha).ok()? {
+        Object::Commit(commit) => Some(*commit),
+        _ => None,
+    }
+}
@@ -0,0 +1,289 @@
+// signatures: proving it was you, for when blame isn't enough
+
+use crate::utils::config;
+use crate::utils::ident::Ident;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Kinds of signature, chosen with `gpg.format`
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    OpenPgp,
+    X509,
+    Ssh,
+}
+
+impl Format {
+    /// The format configured for new signatures
+    pub fn configured() -> Result<Format, String> {
+        match config::get("gpg.format").as_deref() {
+            None | Some("openpgp") => Ok(Format::OpenPgp),
+            Some("x509") => Ok(Format::X509),
+            Some("ssh") => Ok(Format::Ssh),
+            Some(other) => Err(format!("invalid value for 'gpg.format': '{}'", other)),
+        }
+    }
+
+    /// Work out which format an existing signature is in from its armor
+    fn detect(signature: &str) -> Option<Format> {
+        let first = signature.lines().next()?;
+        match first {
+            "-----BEGIN PGP SIGNATURE-----" | "-----BEGIN PGP MESSAGE-----" => {
+                Some(Format::OpenPgp)
+            }
+            "-----BEGIN SIGNED MESSAGE-----" => Some(Format::X509),
+            "-----BEGIN SSH SIGNATURE-----" => Some(Format::Ssh),
+            _ => None,
+        }
+    }
+
+    /// The program to run: `gpg.<format>.program`, else the usual tool
+    fn program(self) -> String {
+        let (key, default) = match self {
+            Format::OpenPgp => ("gpg.openpgp.program", "gpg"),
+            Format::X509 => ("gpg.x509.program", "gpgsm"),
+            Format::Ssh => ("gpg.ssh.program", "ssh-keygen"),
+        };
+        config::get(key)
+            .or_else(|| config::get("gpg.program").filter(|_| self == Format::OpenPgp))
+            .unwrap_or_else(|| default.to_string())
+    }
+}
+
+/// The outcome of checking a signature
+pub struct Verification {
+    /// True only for a good signature from a known key
+    pub good: bool,
+    /// What the verifying tool had to say, for showing to the user
+    pub output: String,
+    /// Machine-readable status lines (gpg's --status-fd output)
+    pub raw: String,
+}
+
+/// Sign `payload`, returning the armored signature without its final newline
+///
+/// `key` (from `-S<key>`) falls back to `user.signingKey`; for OpenPGP the
+/// committer's "Name <email>" is used if neither is set, like git does.
+pub fn sign(payload: &[u8], key: Option<&str>, committer: &Ident) -> Result<String, String> {
+    let format = Format::configured()?;
+    let key = key
+        .map(str::to_string)
+        .or_else(|| config::get("user.signingKey"));
+
+    let signature = match format {
+        Format::OpenPgp | Format::X509 => {
+            let key = key.unwrap_or_else(|| committer.name_email());
+            let output = run(
+                Command::new(format.program()).args(["--status-fd=2", "-bsau", &key]),
+                Some(payload),
+            )?;
+            let status = String::from_utf8_lossy(&output.stderr);
+            if !output.status.success() || !status.contains("[GNUPG:] SIG_CREATED ") {
+                return Err(format!(
+                    "gpg failed to sign the data:\n{}",
+                    status.trim_end()
+                ));
+            }
+            String::from_utf8_lossy(&output.stdout).into_owned()
+        }
+        Format::Ssh => {
+            let key = key.ok_or(
+                "user.signingKey needs to be set to an SSH key (or key file) for ssh signing",
+            )?;
+            sign_ssh(payload, &key, &format.program())?
+        }
+    };
+
+    Ok(signature.trim_end_matches('\n').to_string())
+}
+
+/// `ssh-keygen -Y sign`, which only signs files
+fn sign_ssh(payload: &[u8], key: &str, program: &str) -> Result<String, String> {
+    let buffer = TempFile::new(payload)?;
+
+    // a literal public key means the private half lives in ssh-agent
+    let literal = key.strip_prefix("key::").map(str::to_string).or_else(|| {
+        key.starts_with("ssh-")
+            .then(|| key.to_string())
+            .filter(|k| k.contains(' '))
+    });
+    let key_file = match &literal {
+        Some(public) => Some(TempFile::new(format!("{}\n", public).as_bytes())?),
+        None => None,
+    };
+
+    let mut command = Command::new(program);
+    command.args(["-Y", "sign", "-n", "git", "-f"]);
+    match &key_file {
+        Some(file) => command.arg(&file.path).arg("-U"),
+        None => command.arg(expand_home(key)),
+    };
+    command.arg(&buffer.path);
+
+    let output = run(&mut command, None)?;
+    let sig_path = buffer.path.with_extension("sig");
+    let signature = fs::read_to_string(&sig_path);
+    let _ = fs::remove_file(&sig_path);
+    match signature {
+        Ok(signature) if output.status.success() => Ok(signature),
+        _ => Err(format!(
+            "ssh-keygen failed to sign the data:\n{}",
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        )),
+    }
+}
+
+/// Check `signature` over `payload` with whatever tool matches its format
+///
+/// OpenPGP and X.509 signatures are checked against the local keyring; SSH
+/// signatures against `gpg.ssh.allowedSignersFile`.
+pub fn verify(payload: &[u8], signature: &str) -> Result<Verification, String> {
+    let format = Format::detect(signature).ok_or("unknown signature format")?;
+    let mut signature = signature.to_string();
+    if !signature.ends_with('\n') {
+        signature.push('\n');
+    }
+    let sig_file = TempFile::new(signature.as_bytes())?;
+
+    match format {
+        Format::OpenPgp | Format::X509 => {
+            let output = run(
+                Command::new(format.program())
+                    .args(["--keyid-format=long", "--status-fd=1", "--verify"])
+                    .arg(&sig_file.path)
+                    .arg("-"),
+                Some(payload),
+            )?;
+            let raw = String::from_utf8_lossy(&output.stdout).into_owned();
+            let bad = ["[GNUPG:] BADSIG", "[GNUPG:] ERRSIG", "[GNUPG:] EXPKEYSIG"]
+                .iter()
+                .any(|s| raw.contains(s));
+            Ok(Verification {
+                good: output.status.success() && raw.contains("[GNUPG:] GOODSIG") && !bad,
+                output: String::from_utf8_lossy(&output.stderr).into_owned(),
+                raw,
+            })
+        }
+        Format::Ssh => verify_ssh(payload, &sig_file, &format.program()),
+    }
+}
+
+fn verify_ssh(payload: &[u8], sig_file: &TempFile, program: &str) -> Result<Verification, String> {
+    let allowed = config::get_path("gpg.ssh.allowedSignersFile")
+        .filter(|path| path.is_file())
+        .ok_or(
+            "gpg.ssh.allowedSignersFile needs to be configured and exist for ssh signature verification",
+        )?;
+    let revocations = config::get_path("gpg.ssh.revocationFile");
+
+    let output = run(
+        Command::new(program)
+            .args(["-Y", "find-principals", "-f"])
+            .arg(&allowed)
+            .arg("-s")
+            .arg(&sig_file.path),
+        None,
+    )?;
+    let principals = String::from_utf8_lossy(&output.stdout).into_owned();
+
+    let principal = principals.lines().find(|p| !p.trim().is_empty());
+    let Some(principal) = principal.filter(|_| output.status.success()) else {
+        // still say whether the signature itself holds up
+        let output = run(
+            Command::new(program)
+                .args(["-Y", "check-novalidate", "-n", "git", "-s"])
+                .arg(&sig_file.path),
+            Some(payload),
+        )?;
+        let text = format!(
+            "{}{}No principal matched.\n",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return Ok(Verification {
+            good: false,
+            raw: text.clone(),
+            output: text,
+        });
+    };
+
+    let mut command = Command::new(program);
+    command
+        .args(["-Y", "verify", "-n", "git", "-f"])
+        .arg(&allowed)
+        .args(["-I", principal.trim(), "-s"])
+        .arg(&sig_file.path);
+    if let Some(revocations) = revocations {
+        command.arg("-r").arg(revocations);
+    }
+    let output = run(&mut command, Some(payload))?;
+    let text = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Ok(Verification {
+        good: output.status.success(),
+        raw: text.clone(),
+        output: text,
+    })
+}
+
+/// Run a command, feeding it `input` on stdin, and collect its output
+fn run(command: &mut Command, input: Option<&[u8]>) -> Result<Output, String> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("cannot run {}: {}", program, e))?;
+    let stdin = child.stdin.take();
+    // feed stdin from another thread, so a tool that writes a lot before it
+    // has read everything can't leave us both waiting on a full pipe
+    std::thread::scope(|scope| {
+        if let Some(mut stdin) = stdin {
+            scope.spawn(move || {
+                // a tool that exits early closes the pipe; its exit status tells the story
+                let _ = stdin.write_all(input.unwrap_or_default());
+            });
+        }
+        child
+            .wait_with_output()
+            .map_err(|e| format!("{} failed: {}", program, e))
+    })
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => home::home_dir().map_or_else(|| PathBuf::from(path), |h| h.join(rest)),
+        None => PathBuf::from(path),
+    }
+}
+
+/// A scratch file for tools that won't read from stdin, removed when dropped
+struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    fn new(content: &[u8]) -> Result<Self, String> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            ".hit_sig_{}_{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&path, content).map_err(|e| format!("cannot write {}: {}", path.display(), e))?;
+        Ok(TempFile { path })
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
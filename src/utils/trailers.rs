@@ -0,0 +1,418 @@
+// trailers: the fine print at the bottom of a commit message
+
+use crate::utils::config;
+
+/// Where a new trailer goes relative to the existing ones
+#[derive(Clone, Copy, PartialEq)]
+pub enum Where {
+    End,
+    Start,
+    After,
+    Before,
+}
+
+/// What to do when a trailer with the same key is already there
+#[derive(Clone, Copy, PartialEq)]
+pub enum IfExists {
+    AddIfDifferentNeighbor,
+    AddIfDifferent,
+    Add,
+    Replace,
+    DoNothing,
+}
+
+/// What to do when no trailer with the same key is there yet
+#[derive(Clone, Copy, PartialEq)]
+pub enum IfMissing {
+    Add,
+    DoNothing,
+}
+
+impl Where {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "end" => Some(Where::End),
+            "start" => Some(Where::Start),
+            "after" => Some(Where::After),
+            "before" => Some(Where::Before),
+            _ => None,
+        }
+    }
+}
+
+impl IfExists {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "addifdifferentneighbor" => Some(IfExists::AddIfDifferentNeighbor),
+            "addifdifferent" => Some(IfExists::AddIfDifferent),
+            "add" => Some(IfExists::Add),
+            "replace" => Some(IfExists::Replace),
+            "donothing" => Some(IfExists::DoNothing),
+            _ => None,
+        }
+    }
+}
+
+impl IfMissing {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "add" => Some(IfMissing::Add),
+            "donothing" => Some(IfMissing::DoNothing),
+            _ => None,
+        }
+    }
+}
+
+/// How to place a new trailer; unset fields come from `trailer.<name>.*`,
+/// then `trailer.*`, then git's defaults
+#[derive(Default, Clone, Copy)]
+pub struct Placement {
+    pub where_: Option<Where>,
+    pub if_exists: Option<IfExists>,
+    pub if_missing: Option<IfMissing>,
+}
+
+/// One entry in a trailer block; lines that aren't trailers have no key
+struct Item {
+    key: Option<String>,
+    /// The value with continuation lines folded into one line
+    value: String,
+    /// The entry as written, continuation lines included
+    raw: String,
+}
+
+impl Item {
+    /// A new trailer; a configured key may already end in its separator
+    fn new(key: &str, value: &str) -> Self {
+        let key = key.trim_end();
+        let bare = key
+            .trim_end_matches(|c| separators().contains(c))
+            .trim_end();
+        let line = if bare.len() == key.len() {
+            format!("{}{} {}", key, separator(), value)
+        } else {
+            format!("{} {}", key, value)
+        };
+        Item {
+            key: Some(bare.to_string()),
+            value: value.to_string(),
+            raw: format!("{}\n", line),
+        }
+    }
+
+    fn same_key(&self, key: &str) -> bool {
+        self.key
+            .as_deref()
+            .is_some_and(|k| k.eq_ignore_ascii_case(key))
+    }
+
+    fn same(&self, other: &Item) -> bool {
+        other.key.as_deref().is_some_and(|key| self.same_key(key)) && self.value == other.value
+    }
+}
+
+/// A message split around its trailer block
+pub struct Message {
+    /// Everything before the trailer block (subject, body, separating blank line)
+    body: String,
+    items: Vec<Item>,
+    /// Comments and a patch, if any, after the trailers
+    tail: String,
+}
+
+impl Message {
+    /// Find the trailer block: the last paragraph, if it isn't the subject
+    /// and is made of "Key: value" lines
+    ///
+    /// Like git, a paragraph that is only partly trailers still counts when at
+    /// least a quarter of it is, and one of them is a sign-off or configured key.
+    pub fn parse(message: &str) -> Self {
+        let lines: Vec<&str> = message.split_inclusive('\n').collect();
+
+        // comments and patches come after the trailers
+        let mut end = lines
+            .iter()
+            .position(|l| *l == "---\n" || *l == "---" || l.starts_with("--- "))
+            .unwrap_or(lines.len());
+        while end > 0 && (is_comment(lines[end - 1]) || lines[end - 1].trim().is_empty()) {
+            end -= 1;
+        }
+        let tail: String = lines[end..].concat();
+
+        // comment lines don't end a paragraph, and don't count as part of one
+        let blank = lines[..end]
+            .iter()
+            .rposition(|l| !is_comment(l) && l.trim().is_empty());
+        let start = match blank {
+            // the first paragraph is the subject, never trailers
+            Some(blank)
+                if lines[..blank]
+                    .iter()
+                    .any(|l| !is_comment(l) && !l.trim().is_empty()) =>
+            {
+                blank + 1
+            }
+            _ => end,
+        };
+
+        let items = group(&lines[start..end]);
+        let trailers = items.iter().filter(|i| i.key.is_some()).count();
+        let others = items.len() - trailers;
+        let recognised = items.iter().any(|item| {
+            item.key.as_deref().is_some_and(|key| {
+                key.eq_ignore_ascii_case("Signed-off-by") || configured_key(key).is_some()
+            })
+        });
+        let is_block =
+            trailers + others > 0 && (others == 0 || (recognised && trailers * 3 >= others));
+
+        if is_block {
+            Message {
+                body: lines[..start].concat(),
+                items,
+                tail,
+            }
+        } else {
+            Message {
+                body: lines[..end].concat(),
+                items: Vec::new(),
+                tail,
+            }
+        }
+    }
+
+    /// Add a trailer following `placement` and the trailer config
+    ///
+    /// `token` may be a configured trailer name or a prefix of its key
+    /// ("sign" for `trailer.sign.key = Signed-off-by`).
+    pub fn add(&mut self, token: &str, value: &str, placement: Placement) {
+        let name = configured_name(token);
+        let setting = |what: &str| {
+            name.as_ref()
+                .and_then(|n| config::get(&format!("trailer.{}.{}", n, what)))
+                .or_else(|| config::get(&format!("trailer.{}", what)))
+        };
+        let where_ = placement
+            .where_
+            .or_else(|| setting("where").as_deref().and_then(Where::parse))
+            .unwrap_or(Where::End);
+        let if_exists = placement
+            .if_exists
+            .or_else(|| setting("ifexists").as_deref().and_then(IfExists::parse))
+            .unwrap_or(IfExists::AddIfDifferentNeighbor);
+        let if_missing = placement
+            .if_missing
+            .or_else(|| setting("ifmissing").as_deref().and_then(IfMissing::parse))
+            .unwrap_or(IfMissing::Add);
+
+        let key = name
+            .as_ref()
+            .and_then(|n| config::get(&format!("trailer.{}.key", n)))
+            .unwrap_or_else(|| token.to_string());
+        let new = Item::new(&key, value);
+
+        let matching: Vec<usize> = (0..self.items.len())
+            .filter(|&i| self.items[i].same_key(&key))
+            .collect();
+        let (first, last) = match (matching.first(), matching.last()) {
+            (Some(&first), Some(&last)) => (first, last),
+            _ => {
+                if if_missing == IfMissing::Add {
+                    let at = match where_ {
+                        Where::Start | Where::Before => 0,
+                        Where::End | Where::After => self.items.len(),
+                    };
+                    self.items.insert(at, new);
+                }
+                return;
+            }
+        };
+
+        let at = match where_ {
+            Where::End => self.items.len(),
+            Where::Start => 0,
+            Where::After => last + 1,
+            Where::Before => first,
+        };
+        match if_exists {
+            IfExists::DoNothing => {}
+            IfExists::Add => self.items.insert(at, new),
+            IfExists::AddIfDifferent => {
+                if !self.items.iter().any(|item| item.same(&new)) {
+                    self.items.insert(at, new);
+                }
+            }
+            IfExists::AddIfDifferentNeighbor => {
+                // the trailer the new one would end up next to
+                let neighbour = match where_ {
+                    Where::End | Where::After => at.checked_sub(1),
+                    Where::Start | Where::Before => Some(at),
+                };
+                if !neighbour
+                    .and_then(|n| self.items.get(n))
+                    .is_some_and(|item| item.same(&new))
+                {
+                    self.items.insert(at, new);
+                }
+            }
+            IfExists::Replace => {
+                // only the nearest one goes, like git
+                let old = match where_ {
+                    Where::End | Where::After => last,
+                    Where::Start | Where::Before => first,
+                };
+                self.items.remove(old);
+                let at = match where_ {
+                    Where::End => self.items.len(),
+                    Where::Start => 0,
+                    Where::After | Where::Before => old,
+                };
+                self.items.insert(at, new);
+            }
+        }
+    }
+
+    /// Drop trailers with an empty value (`--trim-empty`)
+    pub fn trim_empty(&mut self) {
+        self.items
+            .retain(|item| item.key.is_none() || !item.value.trim().is_empty());
+    }
+
+    /// Put the message back together
+    ///
+    /// A message that had no trailers gets a blank line before the new ones.
+    pub fn render(&self) -> String {
+        let mut text = self.body.clone();
+        if !self.items.is_empty() {
+            if !text.is_empty() && !text.ends_with('\n') {
+                text.push('\n');
+            }
+            if !text.lines().last().is_some_and(|l| l.trim().is_empty()) {
+                text.push('\n');
+            }
+        }
+        for item in &self.items {
+            text += &item.raw;
+        }
+        text += &self.tail;
+        text
+    }
+
+    /// Just the trailer block (`--only-trailers`)
+    pub fn render_trailers(&self) -> String {
+        self.items
+            .iter()
+            .filter(|item| item.key.is_some())
+            .map(|item| item.raw.as_str())
+            .collect()
+    }
+
+    /// Join continuation lines so each trailer is on one line (`--unfold`)
+    pub fn unfold(&mut self) {
+        for item in &mut self.items {
+            if let Some(key) = &item.key {
+                item.raw = format!("{}{} {}\n", key, separator(), item.value);
+            }
+        }
+    }
+}
+
+/// Split a "--trailer" argument into key and value at '=' or a configured separator
+pub fn split_argument(argument: &str) -> (String, String) {
+    let separators = format!("={}", separators());
+    match argument.find(|c| separators.contains(c)) {
+        Some(at) => (
+            argument[..at].trim().to_string(),
+            argument[at + 1..].trim().to_string(),
+        ),
+        None => (argument.trim().to_string(), String::new()),
+    }
+}
+
+/// Characters that may separate a key from its value (`trailer.separators`)
+fn separators() -> String {
+    config::get("trailer.separators")
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| ":".to_string())
+}
+
+/// The separator written into new trailers
+fn separator() -> char {
+    separators().chars().next().unwrap_or(':')
+}
+
+/// Whether a line is a comment, which git skips when looking for trailers
+fn is_comment(line: &str) -> bool {
+    line.starts_with('#')
+}
+
+/// Split lines into items, folding indented continuation lines into the one
+/// before and dropping comments
+fn group(lines: &[&str]) -> Vec<Item> {
+    let separators = separators();
+    let mut items: Vec<Item> = Vec::new();
+    let mut after_comment = false;
+    for line in lines {
+        if is_comment(line) {
+            after_comment = true;
+            continue;
+        }
+        // a comment in between breaks the continuation, like git
+        let continues = line.starts_with([' ', '\t']) && !after_comment;
+        after_comment = false;
+        if continues && let Some(last) = items.last_mut() {
+            last.raw.push_str(line);
+            last.value.push(' ');
+            last.value.push_str(line.trim());
+            continue;
+        }
+
+        let parsed = line.find(|c| separators.contains(c)).and_then(|at| {
+            let key = line[..at].trim_end();
+            let valid = !key.is_empty()
+                && !line.starts_with(char::is_whitespace)
+                && key.chars().all(|c| c.is_alphanumeric() || c == '-');
+            valid.then(|| (key.to_string(), line[at + 1..].trim().to_string()))
+        });
+        items.push(match parsed {
+            Some((key, value)) => Item {
+                key: Some(key),
+                value,
+                raw: line.to_string(),
+            },
+            None => Item {
+                key: None,
+                value: line.trim_end().to_string(),
+                raw: line.to_string(),
+            },
+        });
+    }
+    // the last line of a message may lack its newline
+    if let Some(last) = items.last_mut()
+        && !last.raw.ends_with('\n')
+    {
+        last.raw.push('\n');
+    }
+    items
+}
+
+/// The `trailer.<name>` section a token refers to, matching the name or a
+/// prefix of the configured key, case-insensitively
+fn configured_name(token: &str) -> Option<String> {
+    let token = token.to_lowercase();
+    config::subsections("trailer").into_iter().find(|name| {
+        name.to_lowercase().starts_with(&token)
+            || config::get(&format!("trailer.{}.key", name))
+                .is_some_and(|key| key.to_lowercase().starts_with(&token))
+    })
+}
+
+/// The configured trailer whose key is `key`, if any
+fn configured_key(key: &str) -> Option<String> {
+    config::subsections("trailer").into_iter().find(|name| {
+        config::get(&format!("trailer.{}.key", name)).is_some_and(|k| {
+            k.trim_end_matches(|c: char| c.is_whitespace() || separators().contains(c))
+                .eq_ignore_ascii_case(key)
+        })
+    })
+}
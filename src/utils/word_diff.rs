@@ -0,0 +1,239 @@
+// word_diff: for when one changed word shouldn't cost you the whole paragraph
+
+use crate::utils::diff::{self, Algorithm, DiffColors, Edit, Hunk, is_space};
+use crate::utils::regex::Regex;
+
+/// How `--word-diff` marks changed words
+#[derive(Clone, Copy, PartialEq)]
+pub enum WordDiff {
+    /// `[-old-]{+new+}`
+    Plain,
+    /// Only colors, no markers
+    Color,
+    /// A line per run of words, for scripts: '-', '+' or ' ' first, and "~" for a newline
+    Porcelain,
+}
+
+impl WordDiff {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "plain" => Some(WordDiff::Plain),
+            "color" => Some(WordDiff::Color),
+            "porcelain" => Some(WordDiff::Porcelain),
+            _ => None,
+        }
+    }
+}
+
+/// How one kind of text is written: its color, and what goes around it
+struct Style<'a> {
+    color: &'a str,
+    prefix: &'a str,
+    suffix: &'a str,
+}
+
+/// Text gathered from a run of removed and added lines
+#[derive(Default)]
+struct Buffer {
+    text: Vec<u8>,
+}
+
+impl Buffer {
+    fn push(&mut self, line: &[u8]) {
+        self.text.extend_from_slice(line);
+        // like git, a missing newline at the end of the file doesn't count
+        if !line.ends_with(b"\n") {
+            self.text.push(b'\n');
+        }
+    }
+
+    /// The words in the text as (start, end) byte ranges, after a fake empty
+    /// word at the very start to stand for "before the first word"
+    fn words(&self, regex: Option<&Regex>) -> Vec<(usize, usize)> {
+        let text = &self.text;
+        let mut words = vec![(0, 0)];
+        let mut begin = 0;
+        while begin < text.len() {
+            let end = match regex {
+                Some(regex) => {
+                    // like git, matching starts afresh at each word, so '^' matches there
+                    let Some((start, end)) = regex.find_at(&text[begin..], 0) else {
+                        break;
+                    };
+                    let (start, end) = (begin + start, begin + end);
+                    // a word never spans lines
+                    let end = match text[start..end].iter().position(|&b| b == b'\n') {
+                        Some(newline) => start + newline,
+                        None => end,
+                    };
+                    begin = start;
+                    // like git, an empty match ends the words
+                    if begin >= end {
+                        break;
+                    }
+                    end
+                }
+                None => {
+                    while begin < text.len() && is_space(text[begin]) {
+                        begin += 1;
+                    }
+                    if begin >= text.len() {
+                        break;
+                    }
+                    let mut end = begin + 1;
+                    while end < text.len() && !is_space(text[end]) {
+                        end += 1;
+                    }
+                    end
+                }
+            };
+            words.push((begin, end));
+            begin = end;
+        }
+        words
+    }
+}
+
+/// Write one hunk with changed words marked inside the lines rather than
+/// whole lines removed and added
+pub fn format_hunk(
+    out: &mut Vec<u8>,
+    hunk: &Hunk,
+    old: &[&[u8]],
+    new: &[&[u8]],
+    mode: WordDiff,
+    regex: Option<&Regex>,
+    colors: &DiffColors,
+) {
+    diff::format_hunk_header(out, hunk, old, colors);
+    let styles = Styles::new(mode, colors);
+    let (mut minus, mut plus) = (Buffer::default(), Buffer::default());
+    for edit in &hunk.edits {
+        match *edit {
+            Edit::Delete(i) => minus.push(old[i]),
+            Edit::Insert(j) => plus.push(new[j]),
+            Edit::Equal(i, _) => {
+                show(out, &minus, &plus, regex, &styles);
+                (minus, plus) = (Buffer::default(), Buffer::default());
+
+                // porcelain keeps the ' ' of an unchanged line, the others drop it
+                let mut line = old[i].strip_suffix(b"\n").unwrap_or(old[i]).to_vec();
+                if mode == WordDiff::Porcelain {
+                    line.insert(0, b' ');
+                }
+                if !line.is_empty() {
+                    out.extend_from_slice(colors.context.as_bytes());
+                    out.extend_from_slice(&line);
+                    out.extend_from_slice(colors.reset.as_bytes());
+                }
+                out.push(b'\n');
+                if mode == WordDiff::Porcelain {
+                    out.extend_from_slice(b"~\n");
+                }
+            }
+        }
+    }
+    show(out, &minus, &plus, regex, &styles);
+}
+
+struct Styles<'a> {
+    old: Style<'a>,
+    new: Style<'a>,
+    context: Style<'a>,
+    /// What a newline inside the text becomes
+    newline: &'a str,
+    reset: &'a str,
+}
+
+impl<'a> Styles<'a> {
+    fn new(mode: WordDiff, colors: &'a DiffColors) -> Self {
+        let style = |color: &'a str, prefix: &'a str, suffix: &'a str| Style {
+            color,
+            prefix,
+            suffix,
+        };
+        let (old, new, context, newline) = match mode {
+            WordDiff::Porcelain => (("-", "\n"), ("+", "\n"), (" ", "\n"), "~\n"),
+            WordDiff::Plain => (("[-", "-]"), ("{+", "+}"), ("", ""), "\n"),
+            WordDiff::Color => (("", ""), ("", ""), ("", ""), "\n"),
+        };
+        Styles {
+            old: style(&colors.old, old.0, old.1),
+            new: style(&colors.new, new.0, new.1),
+            context: style(&colors.context, context.0, context.1),
+            newline,
+            reset: &colors.reset,
+        }
+    }
+
+    /// Write `text` in `style`, a line at a time
+    fn write(&self, out: &mut Vec<u8>, style: &Style, text: &[u8]) {
+        let mut lines = text.split(|&b| b == b'\n').peekable();
+        while let Some(line) = lines.next() {
+            if !line.is_empty() {
+                let colored = !style.color.is_empty();
+                out.extend_from_slice(style.color.as_bytes());
+                out.extend_from_slice(style.prefix.as_bytes());
+                out.extend_from_slice(line);
+                out.extend_from_slice(style.suffix.as_bytes());
+                if colored {
+                    out.extend_from_slice(self.reset.as_bytes());
+                }
+            }
+            if lines.peek().is_some() {
+                out.extend_from_slice(self.newline.as_bytes());
+            }
+        }
+    }
+}
+
+/// Diff the words of the removed lines against those of the added ones and
+/// write the added text with the changes marked, like git's diff_words_show
+fn show<'a>(
+    out: &mut Vec<u8>,
+    minus: &'a Buffer,
+    plus: &'a Buffer,
+    regex: Option<&Regex>,
+    styles: &Styles,
+) {
+    if plus.text.is_empty() {
+        styles.write(out, &styles.old, &minus.text);
+        return;
+    }
+
+    let minus_words = minus.words(regex);
+    let plus_words = plus.words(regex);
+    let slices = |buffer: &'a Buffer, words: &[(usize, usize)]| -> Vec<&'a [u8]> {
+        words[1..]
+            .iter()
+            .map(|&(start, end)| &buffer.text[start..end])
+            .collect()
+    };
+    let (a, b) = (slices(minus, &minus_words), slices(plus, &plus_words));
+    let script = diff::diff_lines(&a, &b, Algorithm::Myers);
+
+    // how much of the added text has been written
+    let mut current = 0;
+    for hunk in diff::hunks(&script, 0) {
+        // a range of words, or an empty spot just after the word before
+        let range = |words: &[(usize, usize)], start: usize, count: usize| match count {
+            0 => (words[start].1, words[start].1),
+            _ => (words[start].0, words[start + count - 1].1),
+        };
+        let (minus_begin, minus_end) = range(&minus_words, hunk.old_start, hunk.old_count);
+        let (plus_begin, plus_end) = range(&plus_words, hunk.new_start, hunk.new_count);
+        if current != plus_begin {
+            styles.write(out, &styles.context, &plus.text[current..plus_begin]);
+        }
+        if minus_begin != minus_end {
+            styles.write(out, &styles.old, &minus.text[minus_begin..minus_end]);
+        }
+        if plus_begin != plus_end {
+            styles.write(out, &styles.new, &plus.text[plus_begin..plus_end]);
+        }
+        current = plus_end;
+    }
+    if current != plus.text.len() {
+        styles.write(out, &styles.context, &plus.text[current..]);
+    }
+}
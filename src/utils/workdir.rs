@@ -0,0 +1,208 @@
+// the working directory: where files go to be edited, and occasionally to die
+
+use crate::utils::hash_object::{hash_object, resolve_head};
+use crate::utils::index::{Index, IndexEntry};
+use crate::utils::objects::Object;
+use crate::utils::pathspec;
+use crate::utils::refs;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+/// Write a staged blob out to the working directory
+pub fn write_entry(entry: &IndexEntry) -> Result<(), String> {
+    let data = match Object::read(&entry.sha)? {
+        Object::Blob(data) => data,
+        _ => return Err(format!("{} is not a blob", entry.sha)),
+    };
+
+    let path = Path::new(&entry.path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("cannot create {:?}: {}", parent, e))?;
+    }
+    // a directory sitting where the file should be has to go first
+    if path.is_dir() {
+        fs::remove_dir_all(path).map_err(|e| format!("cannot remove {}: {}", entry.path, e))?;
+    }
+    fs::write(path, data).map_err(|e| format!("cannot write {}: {}", entry.path, e))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = if entry.mode == "100755" { 0o755 } else { 0o644 };
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))
+            .map_err(|e| format!("cannot chmod {}: {}", entry.path, e))?;
+    }
+
+    Ok(())
+}
+
+/// Delete a file and any directories it leaves empty
+pub fn remove_entry(path: &str) {
+    let path = Path::new(path);
+    if path.is_file() {
+        let _ = fs::remove_file(path);
+    }
+
+    let mut dir = path.parent();
+    while let Some(d) = dir {
+        if d.as_os_str().is_empty() || fs::remove_dir(d).is_err() {
+            break;
+        }
+        dir = d.parent();
+    }
+}
+
+/// The mode to record for a file: "100755" if it's executable, else "100644"
+#[cfg(unix)]
+pub fn file_mode(path: &str) -> String {
+    use std::os::unix::fs::PermissionsExt;
+    let executable = fs::metadata(path).is_ok_and(|meta| meta.permissions().mode() & 0o111 != 0);
+    if executable { "100755" } else { "100644" }.to_string()
+}
+
+#[cfg(not(unix))]
+pub fn file_mode(_path: &str) -> String {
+    "100644".to_string()
+}
+
+/// True if the file on disk no longer matches what the index recorded
+pub fn is_modified(entry: &IndexEntry) -> bool {
+    let path = Path::new(&entry.path);
+    !path.is_file() || hash_object(&entry.path, false, false) != entry.sha
+}
+
+/// Check out a tree: move the index and working directory from HEAD's tree
+/// to `tree_sha`
+///
+/// Like git, only paths that differ between the two trees are touched, so
+/// staged changes, local edits and untracked files elsewhere carry over.
+/// Unless `force` is set, refuses if the index or working directory differs
+/// from HEAD on a path that has to change; with it, every path in HEAD or the
+/// index is made to match the tree.
+pub fn checkout_tree(tree_sha: &str, force: bool) -> Result<(), String> {
+    let head = match resolve_head() {
+        Some(sha) => Index::from_tree(&refs::resolve_commit(&sha)?.1)?,
+        None => Index {
+            entries: Vec::new(),
+        },
+    };
+    let target = Index::from_tree(tree_sha)?;
+    let mut index: BTreeMap<String, IndexEntry> = Index::load()
+        .entries
+        .into_iter()
+        .map(|e| (e.path.clone(), e))
+        .collect();
+
+    let mut paths: BTreeSet<String> = head
+        .entries
+        .iter()
+        .chain(&target.entries)
+        .map(|e| e.path.clone())
+        .collect();
+    if force {
+        paths.extend(index.keys().cloned());
+    }
+
+    let same = |a: Option<&IndexEntry>, b: Option<&IndexEntry>| match (a, b) {
+        (Some(a), Some(b)) => a.sha == b.sha && a.mode == b.mode,
+        (a, b) => a.is_none() && b.is_none(),
+    };
+
+    let mut clobbered = Vec::new();
+    let mut changes = Vec::new();
+    for path in &paths {
+        let (old, new, staged) = (head.get(path), target.get(path), index.get(path));
+        if force {
+            if new.is_none_or(|new| !same(staged, Some(new)) || is_modified(new)) {
+                changes.push((path, new));
+            }
+            continue;
+        }
+        // nothing to do, or already staged the way the tree has it
+        if same(old, new) || same(staged, new) {
+            continue;
+        }
+        let dirty = match staged {
+            Some(entry) if same(staged, old) => Path::new(path).exists() && is_modified(entry),
+            // an untracked file in the way
+            None if old.is_none() => {
+                new.is_some_and(|new| Path::new(path).is_file() && is_modified(new))
+            }
+            _ => true,
+        };
+        if dirty {
+            clobbered.push(path.as_str());
+        }
+        changes.push((path, new));
+    }
+
+    if !clobbered.is_empty() {
+        return Err(format!(
+            "your local changes to the following files would be overwritten:\n{}\nplease commit or stash them before you switch",
+            clobbered
+                .iter()
+                .map(|p| format!("\t{}", p))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ));
+    }
+
+    for (path, new) in changes {
+        match new {
+            Some(entry) => {
+                write_entry(entry)?;
+                index.insert(path.clone(), entry.clone());
+            }
+            None => {
+                remove_entry(path);
+                index.remove(path);
+            }
+        }
+    }
+    Index {
+        entries: index.into_values().collect(),
+    }
+    .save();
+    Ok(())
+}
+
+/// Every file in the working directory outside .hit and .hitignore'd paths,
+/// as '/'-separated paths relative to the repository root
+pub fn list_files() -> Vec<String> {
+    let ignored: Vec<String> = fs::read_to_string(".hitignore")
+        .unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| pathspec::normalize(line.trim_start_matches('/')))
+        .filter(|line| !line.is_empty())
+        .collect();
+
+    let mut files = Vec::new();
+    collect_files(Path::new("."), &ignored, &mut files);
+    files.sort();
+    files
+}
+
+fn collect_files(dir: &Path, ignored: &[String], files: &mut Vec<String>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        let rel = path.strip_prefix(".").unwrap_or(&path);
+        let rel = rel.to_string_lossy().replace('\\', "/");
+
+        if rel == ".hit" || rel.starts_with(".hit/") || pathspec::matches_any(&rel, ignored) {
+            continue;
+        }
+
+        if path.is_dir() {
+            collect_files(&path, ignored, files);
+        } else {
+            files.push(rel);
+        }
+    }
+}